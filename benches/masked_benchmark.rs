@@ -1,7 +1,9 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
+use fixsliced_gift::gift128::hooks::{RefreshPerQuintupleRound, RefreshPerSbox};
 use fixsliced_gift::gift128::key_schedule::mask_key;
-use fixsliced_gift::gift128::{encrypt_masked, mask_block, Block, Key};
+use fixsliced_gift::gift128::{encrypt_masked, encrypt_masked_with_hook, mask_block, Block, Key};
+use fixsliced_gift::random::XorShiftRng;
 
 const KEY: Key = [
     0xd0, 0xf5, 0xc5, 0x9a, 0x77, 0x00, 0xd3, 0xe7, 0x99, 0x02, 0x8f, 0xa9, 0xf9, 0x0a, 0xd8, 0x37,
@@ -22,11 +24,43 @@ const PLAINTEXT_MASKS: Block = [
 fn masked_benchmark(c: &mut Criterion) {
     let masked_key = mask_key(&KEY, &KEY_MASKS);
     let masked_plaintext = mask_block(&PLAINTEXT, &PLAINTEXT_MASKS);
+    let mut rng = XorShiftRng::new(1);
 
     c.bench_function("uhm", |b| {
         b.iter(|| {
             let mut masked_ciphertext = [Default::default(); PLAINTEXT.len()];
-            encrypt_masked(&masked_plaintext, &masked_key, &mut masked_ciphertext);
+            encrypt_masked(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            );
+        })
+    });
+
+    c.bench_function("refresh per sbox", |b| {
+        b.iter(|| {
+            let mut masked_ciphertext = [Default::default(); PLAINTEXT.len()];
+            encrypt_masked_with_hook(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut RefreshPerSbox,
+                &mut rng,
+            );
+        })
+    });
+
+    c.bench_function("refresh per quintuple round", |b| {
+        b.iter(|| {
+            let mut masked_ciphertext = [Default::default(); PLAINTEXT.len()];
+            encrypt_masked_with_hook(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut RefreshPerQuintupleRound,
+                &mut rng,
+            );
         })
     });
 }
@@ -0,0 +1,31 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use fixsliced_gift::gift128::{encrypt, encrypt_lanes, encrypt_parallel, Key};
+
+const KEY: Key = [
+    0xd0, 0xf5, 0xc5, 0x9a, 0x77, 0x00, 0xd3, 0xe7, 0x99, 0x02, 0x8f, 0xa9, 0xf9, 0x0a, 0xd8, 0x37,
+];
+
+fn parallel_benchmark(c: &mut Criterion) {
+    let plaintext = [0x5a; 4096];
+    let mut ciphertext = [0; 4096];
+
+    let mut group = c.benchmark_group("4 KiB");
+    group.throughput(Throughput::Bytes(plaintext.len() as u64));
+    group.bench_function("encrypt", |b| {
+        b.iter(|| encrypt(&plaintext, &KEY, &mut ciphertext))
+    });
+    group.bench_function("encrypt parallel", |b| {
+        b.iter(|| encrypt_parallel(&plaintext, &KEY, &mut ciphertext))
+    });
+    group.bench_function("encrypt lanes 4", |b| {
+        b.iter(|| encrypt_lanes::<4>(&plaintext, &KEY, &mut ciphertext))
+    });
+    group.bench_function("encrypt lanes 8", |b| {
+        b.iter(|| encrypt_lanes::<8>(&plaintext, &KEY, &mut ciphertext))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parallel_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+// Fails to link in release builds if any of the `try_` or `_protected` functions or methods can
+// panic. The guard calls an undefined function while unwinding, and that call is only removed if
+// the optimizer proves that no panic is reachable from the closure. Debug builds keep their panics, so it only checks with
+//
+//     cargo build --release --example no_panic
+
+#[cfg(not(debug_assertions))]
+mod check {
+    use core::hint::black_box;
+
+    use fixsliced_gift::gift128::key_schedule::mask_key;
+    use fixsliced_gift::gift128::{
+        decrypt_masked_protected, decrypt_protected, encrypt_masked_protected, encrypt_protected,
+        mask_block, try_decrypt, try_decrypt_masked, try_encrypt, try_encrypt_masked, Gift128,
+        Gift128Error, MaskedBlock, MaskedGift128, ProtectedError,
+    };
+    use fixsliced_gift::random::XorShiftRng;
+
+    struct Guard;
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            extern "C" {
+                fn try_function_may_panic() -> !;
+            }
+            unsafe { try_function_may_panic() }
+        }
+    }
+
+    #[inline(never)]
+    fn no_panic<T>(f: impl FnOnce() -> T) -> T {
+        let guard = Guard;
+        let result = f();
+        core::mem::forget(guard);
+        result
+    }
+
+    pub fn run() {
+        let key = black_box([0x42; 16]);
+        let plaintext = black_box([0x5a; 64]);
+        let mut ciphertext = [0; 64];
+        let mut decrypted = [0; 64];
+        let result = no_panic(|| try_encrypt(black_box(&plaintext[..]), &key, &mut ciphertext));
+        assert_eq!(result, Ok(()));
+        let result = no_panic(|| try_decrypt(black_box(&ciphertext[..]), &key, &mut decrypted));
+        assert_eq!(result, Ok(()));
+        assert_eq!(plaintext, decrypted);
+
+        let result = no_panic(|| try_encrypt(black_box(&plaintext[..15]), &key, &mut ciphertext));
+        assert_eq!(result, Err(Gift128Error::InvalidLength));
+
+        let cipher = Gift128::new(&key);
+        let result = no_panic(|| cipher.try_encrypt(black_box(&plaintext[..]), &mut ciphertext));
+        assert_eq!(result, Ok(()));
+        let result = no_panic(|| cipher.try_decrypt(black_box(&ciphertext[..]), &mut decrypted));
+        assert_eq!(result, Ok(()));
+        assert_eq!(plaintext, decrypted);
+        let result = no_panic(|| cipher.try_encrypt(black_box(&plaintext[..15]), &mut ciphertext));
+        assert_eq!(result, Err(Gift128Error::InvalidLength));
+
+        let result =
+            no_panic(|| encrypt_protected(black_box(&plaintext[..]), &key, &mut ciphertext));
+        assert_eq!(result, Ok(()));
+        let result =
+            no_panic(|| decrypt_protected(black_box(&ciphertext[..]), &key, &mut decrypted));
+        assert_eq!(result, Ok(()));
+        assert_eq!(plaintext, decrypted);
+        let result =
+            no_panic(|| encrypt_protected(black_box(&plaintext[..15]), &key, &mut ciphertext));
+        assert_eq!(
+            result,
+            Err(ProtectedError::Length(Gift128Error::InvalidLength))
+        );
+
+        let masked_key = mask_key(&key, &black_box([0xa5; 16]));
+        let masked_plaintext = mask_block(&black_box([0x5a; 16]), &black_box([0x3c; 16]));
+        let mut masked_ciphertext: MaskedBlock = Default::default();
+        let mut masked_decrypted: MaskedBlock = Default::default();
+        let mut rng = XorShiftRng::new(black_box(1));
+        let result = no_panic(|| {
+            try_encrypt_masked(
+                black_box(&masked_plaintext[..]),
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            )
+        });
+        assert_eq!(result, Ok(()));
+        let result = no_panic(|| {
+            try_decrypt_masked(
+                black_box(&masked_ciphertext[..]),
+                &masked_key,
+                &mut masked_decrypted,
+                &mut rng,
+            )
+        });
+        assert_eq!(result, Ok(()));
+        let masked_cipher = MaskedGift128::new(&masked_key);
+        let result = no_panic(|| {
+            masked_cipher.try_encrypt(
+                black_box(&masked_plaintext[..]),
+                &mut masked_ciphertext,
+                &mut rng,
+            )
+        });
+        assert_eq!(result, Ok(()));
+        let result = no_panic(|| {
+            masked_cipher.try_decrypt(
+                black_box(&masked_ciphertext[..]),
+                &mut masked_decrypted,
+                &mut rng,
+            )
+        });
+        assert_eq!(result, Ok(()));
+        let result = no_panic(|| {
+            encrypt_masked_protected(
+                black_box(&masked_plaintext[..]),
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            )
+        });
+        assert_eq!(result, Ok(()));
+        let result = no_panic(|| {
+            decrypt_masked_protected(
+                black_box(&masked_ciphertext[..]),
+                &masked_key,
+                &mut masked_decrypted,
+                &mut rng,
+            )
+        });
+        assert_eq!(result, Ok(()));
+    }
+}
+
+fn main() {
+    #[cfg(not(debug_assertions))]
+    check::run();
+}
@@ -0,0 +1,339 @@
+use std::vec::Vec;
+
+use crate::gift128::key_schedule::precompute_round_keys;
+use crate::gift128::packing::pack;
+use crate::gift128::rounds::{StateOperations, ROUND_CONSTANTS};
+use crate::gift128::sbox::sbox;
+use crate::gift128::{Block, Key, State};
+use crate::random::NoRng;
+use crate::swap_move_single;
+
+// The first S-box layer comes before any key addition, so the attack targets the second one.
+// Each of its 32 S-boxes takes one bit of `round_keys[0]` and `round_keys[1]`, i.e. two bits of
+// key bytes 12 to 15 and 4 to 7 after `rearrange_round_key0`, for 64 key bits in total.
+pub const SBOXES: usize = 32;
+pub const CANDIDATES: usize = 4;
+
+// Output slices, in their real order, of the second S-box layer for the given round key words.
+#[must_use]
+pub(crate) fn second_sbox(plaintext: &Block, round_key0: u32, round_key1: u32) -> [u32; 4] {
+    let State(s0, s1, s2, s3) = sbox(pack(plaintext), &mut NoRng);
+    let s3 = s3.nibble_ror_1();
+    let s1 = s1.nibble_ror_2() ^ round_key0;
+    let s2 = s2.nibble_ror_3() ^ round_key1;
+    let s0 = s0 ^ ROUND_CONSTANTS[0];
+    let State(s3, s1, s2, s0) = sbox(State(s3, s1, s2, s0), &mut NoRng);
+    [s0, s1, s2, s3]
+}
+
+// Every point of a trace leaks one word, which holds a single bit of each S-box, so the output
+// bits are predicted separately.
+const BITS: usize = 4;
+
+type Predictions = [[[f64; BITS]; CANDIDATES]; SBOXES];
+
+// The output bits of every S-box for every candidate of its two key bits.
+#[must_use]
+pub(crate) fn predictions(plaintext: &Block) -> Predictions {
+    let mut predictions = [[[0.0; BITS]; CANDIDATES]; SBOXES];
+    for candidate in 0..CANDIDATES {
+        let round_key0 = if candidate & 1 == 1 { 0xffffffff } else { 0 };
+        let round_key1 = if candidate & 2 == 2 { 0xffffffff } else { 0 };
+        let slices = second_sbox(plaintext, round_key0, round_key1);
+        for (sbox, prediction) in predictions.iter_mut().enumerate() {
+            for (bit, slice) in slices.iter().enumerate() {
+                prediction[candidate][bit] = f64::from((slice >> sbox) & 1);
+            }
+        }
+    }
+    predictions
+}
+
+fn add_predictions(sums: &mut Predictions, predictions: &Predictions, x: f64) {
+    for (sums, predictions) in sums.iter_mut().flatten().zip(predictions.iter().flatten()) {
+        for (sum, prediction) in sums.iter_mut().zip(predictions) {
+            *sum += prediction * x;
+        }
+    }
+}
+
+fn inv_rearrange_round_key0(mut round_key: u32) -> u32 {
+    round_key = swap_move_single(round_key, 0x000000ff, 24);
+    round_key = swap_move_single(round_key, 0x00003333, 18);
+    round_key = swap_move_single(round_key, 0x000f000f, 12);
+    swap_move_single(round_key, 0x00550055, 9)
+}
+
+// The key bits of every S-box as a candidate index.
+#[must_use]
+pub fn subkeys(key: &Key) -> [usize; SBOXES] {
+    let round_keys = precompute_round_keys(key);
+    let (round_key0, round_key1) = (round_keys[0], round_keys[1]);
+    core::array::from_fn(|sbox| {
+        (((round_key0 >> sbox) & 1) | (((round_key1 >> sbox) & 1) << 1)) as usize
+    })
+}
+
+// Accumulates the correlation between the predictions and every point of the traces. The traces
+// can be cut to the points around the first rounds to speed the attack up.
+#[derive(Clone, Debug)]
+pub struct Cpa {
+    traces: f64,
+    sum_x: Vec<f64>,
+    sum_x2: Vec<f64>,
+    // The predictions are bits, so their sum of squares is their sum.
+    sum_h: Predictions,
+    sum_hx: Vec<Predictions>,
+}
+
+impl Cpa {
+    #[must_use]
+    pub fn new(points: usize) -> Self {
+        Cpa {
+            traces: 0.0,
+            sum_x: std::vec![0.0; points],
+            sum_x2: std::vec![0.0; points],
+            sum_h: [[[0.0; BITS]; CANDIDATES]; SBOXES],
+            sum_hx: std::vec![[[[0.0; BITS]; CANDIDATES]; SBOXES]; points],
+        }
+    }
+
+    pub fn add_trace(&mut self, plaintext: &Block, trace: &[f64]) {
+        if trace.len() != self.sum_x.len() {
+            panic!("trace length differs from the number of points");
+        }
+
+        let predictions = predictions(plaintext);
+        self.traces += 1.0;
+        add_predictions(&mut self.sum_h, &predictions, 1.0);
+
+        for (point, &x) in trace.iter().enumerate() {
+            self.sum_x[point] += x;
+            self.sum_x2[point] += x * x;
+            add_predictions(&mut self.sum_hx[point], &predictions, x);
+        }
+    }
+
+    // The highest correlation of every output bit with any point, per S-box, candidate and bit.
+    // Only positive correlations count, as a complemented bit would otherwise match as well.
+    #[must_use]
+    fn bit_scores(&self) -> [[[f64; BITS]; CANDIDATES]; SBOXES] {
+        let n = self.traces;
+        let mut scores = [[[0.0f64; BITS]; CANDIDATES]; SBOXES];
+        for (point, sum_hx) in self.sum_hx.iter().enumerate() {
+            let sum_x = self.sum_x[point];
+            let variance_x = n * self.sum_x2[point] - sum_x * sum_x;
+            let sums = self.sum_h.iter().flatten().zip(sum_hx.iter().flatten());
+            for (scores, (sum_h, sum_hx)) in scores.iter_mut().flatten().zip(sums) {
+                for (score, (&sum_h, &sum_hx)) in scores.iter_mut().zip(sum_h.iter().zip(sum_hx)) {
+                    let variance_h = n * sum_h - sum_h * sum_h;
+                    let denominator = (variance_h * variance_x).sqrt();
+                    if denominator > 0.0 {
+                        let correlation = (n * sum_hx - sum_h * sum_x) / denominator;
+                        *score = score.max(correlation);
+                    }
+                }
+            }
+        }
+        scores
+    }
+
+    // The highest correlation of any output bit with any point, per S-box and candidate.
+    #[must_use]
+    pub fn scores(&self) -> [[f64; CANDIDATES]; SBOXES] {
+        self.bit_scores()
+            .map(|candidates| candidates.map(|bits| bits.into_iter().fold(0.0, f64::max)))
+    }
+
+    // Like `scores`, but the highest correlations of the output bits are summed. This uses the
+    // leakage of all four bits, which helps when every one of them correlates only weakly, e.g.
+    // in combined traces of a masked implementation or in shuffled traces.
+    #[must_use]
+    pub fn summed_scores(&self) -> [[f64; CANDIDATES]; SBOXES] {
+        self.bit_scores()
+            .map(|candidates| candidates.map(|bits| bits.iter().sum()))
+    }
+
+    // The best candidate of every S-box, combined into the recovered key bytes.
+    #[must_use]
+    pub fn best_key(&self) -> [Option<u8>; 16] {
+        let scores = self.scores();
+        let mut round_key0 = 0;
+        let mut round_key1 = 0;
+        for (sbox, scores) in scores.iter().enumerate() {
+            let best = (0..CANDIDATES)
+                .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+                .unwrap_or_default();
+            round_key0 |= ((best & 1) as u32) << sbox;
+            round_key1 |= (((best >> 1) & 1) as u32) << sbox;
+        }
+
+        let mut key = [None; 16];
+        let bytes0 = inv_rearrange_round_key0(round_key0).to_be_bytes();
+        let bytes1 = inv_rearrange_round_key0(round_key1).to_be_bytes();
+        for i in 0..4 {
+            key[12 + i] = Some(bytes0[i]);
+            key[4 + i] = Some(bytes1[i]);
+        }
+        key
+    }
+
+    // Rank of the correct candidate of every S-box, starting from 1.
+    #[must_use]
+    pub fn ranks(&self, key: &Key) -> [usize; SBOXES] {
+        let scores = self.scores();
+        let subkeys = subkeys(key);
+        core::array::from_fn(|sbox| {
+            let correct = scores[sbox][subkeys[sbox]];
+            1 + scores[sbox]
+                .iter()
+                .filter(|&&score| score > correct)
+                .count()
+        })
+    }
+
+    // The base-2 logarithm of the number of guesses left to find the 64 attacked key bits when
+    // enumerating the candidates of every S-box in order of their score. 0 means that the key
+    // bits have been recovered.
+    #[must_use]
+    pub fn guessing_entropy(&self, key: &Key) -> f64 {
+        self.ranks(key)
+            .iter()
+            .map(|&rank| (rank as f64).log2())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::cpa::{inv_rearrange_round_key0, second_sbox, Cpa};
+    use crate::analysis::simulation::{
+        add_noise, leakage, trace_encrypt, trace_encrypt_masked, trace_encrypt_shuffled,
+        LeakageModel,
+    };
+    use crate::gift128::hooks::RoundHook;
+    use crate::gift128::key_schedule::{mask_key_with_rng, precompute_round_keys};
+    use crate::gift128::mask_block_with_rng;
+    use crate::gift128::packing::pack;
+    use crate::gift128::rounds::rounds_with_hook;
+    use crate::gift128::tests::KEY;
+    use crate::random::{NoRng, Rng, XorShiftRng};
+
+    struct SecondSbox(Option<[u32; 4]>);
+
+    impl RoundHook<u32> for SecondSbox {
+        fn after_sbox<R: Rng>(&mut self, round: usize, slices: &mut [u32; 4], _rng: &mut R) {
+            if round == 1 {
+                self.0 = Some(*slices);
+            }
+        }
+    }
+
+    #[test]
+    fn test_second_sbox() {
+        let plaintext = [0x42; 16];
+        let round_keys = precompute_round_keys(&KEY);
+        let mut hook = SecondSbox(None);
+        let _ = rounds_with_hook(pack(&plaintext), &round_keys, &mut hook, &mut NoRng);
+        assert_eq!(
+            hook.0,
+            Some(second_sbox(&plaintext, round_keys[0], round_keys[1]))
+        );
+    }
+
+    #[test]
+    fn test_inv_rearrange_round_key0() {
+        let round_keys = precompute_round_keys(&KEY);
+        assert_eq!(
+            inv_rearrange_round_key0(round_keys[0]).to_be_bytes(),
+            KEY[12..16]
+        );
+        assert_eq!(
+            inv_rearrange_round_key0(round_keys[1]).to_be_bytes(),
+            KEY[4..8]
+        );
+    }
+
+    const POINTS: usize = 100;
+
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum Target {
+        Unprotected,
+        Masked,
+        Shuffled,
+    }
+
+    // Attacks up to `traces` traces and stops early once `done`, which is checked every 100
+    // traces. Returns the attack and the number of traces it stopped after.
+    fn run_attack(
+        traces: usize,
+        target: Target,
+        done: impl Fn(&Cpa) -> bool,
+    ) -> (Cpa, Option<usize>) {
+        let mut rng = XorShiftRng::new(1);
+        let mut cpa = Cpa::new(POINTS);
+        for i in 0..traces {
+            let mut plaintext = [0; 16];
+            rng.fill_bytes(&mut plaintext);
+
+            let samples = match target {
+                Target::Unprotected => trace_encrypt(&plaintext, &KEY).1,
+                Target::Masked => {
+                    let key = mask_key_with_rng(&KEY, &mut rng);
+                    let masked_plaintext = mask_block_with_rng(&plaintext, &mut rng);
+                    trace_encrypt_masked(&masked_plaintext, &key, &mut rng).1
+                }
+                Target::Shuffled => trace_encrypt_shuffled(&plaintext, &KEY, &mut rng).1,
+            };
+
+            let mut trace = leakage(&samples[..POINTS], LeakageModel::HammingWeight);
+            add_noise(&mut trace, 1.0, &mut rng);
+            cpa.add_trace(&plaintext, &trace);
+            if (i + 1) % 100 == 0 && done(&cpa) {
+                return (cpa, Some(i + 1));
+            }
+        }
+        (cpa, None)
+    }
+
+    fn attack(traces: usize, target: Target) -> Cpa {
+        run_attack(traces, target, |_| false).0
+    }
+
+    // The number of traces after which the attacked key bits are recovered, or `None` if `limit`
+    // traces do not suffice.
+    fn traces_needed(target: Target, limit: usize) -> Option<usize> {
+        run_attack(limit, target, |cpa| cpa.guessing_entropy(&KEY) == 0.0).1
+    }
+
+    #[test]
+    fn test_recovers_key() {
+        let cpa = attack(1000, Target::Unprotected);
+        assert_eq!(cpa.guessing_entropy(&KEY), 0.0);
+        let key = cpa.best_key();
+        for i in (4..8).chain(12..16) {
+            assert_eq!(key[i], Some(KEY[i]));
+        }
+        assert_eq!(key[0], None);
+
+        let (scores, summed_scores) = (cpa.scores(), cpa.summed_scores());
+        for (scores, summed_scores) in scores.iter().flatten().zip(summed_scores.iter().flatten()) {
+            assert!(*scores <= *summed_scores && *summed_scores <= 4.0 * *scores);
+        }
+    }
+
+    #[test]
+    fn test_fails_on_masked() {
+        let cpa = attack(1000, Target::Masked);
+        assert!(cpa.guessing_entropy(&KEY) > 16.0);
+    }
+
+    // The unprotected implementation falls after around 900 traces, the shuffled one only after
+    // around 4400, as the dummy operations spread every intermediate value over several points.
+    #[test]
+    fn test_shuffling_delays_recovery() {
+        let unprotected = traces_needed(Target::Unprotected, 2000).unwrap();
+        let shuffled = traces_needed(Target::Shuffled, 10000).unwrap();
+        assert!(shuffled >= 4 * unprotected, "{unprotected} vs {shuffled}");
+    }
+}
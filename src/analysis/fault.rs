@@ -0,0 +1,343 @@
+use std::vec::Vec;
+
+use crate::analysis::cpa::{CANDIDATES, SBOXES};
+use crate::analysis::traced::{capture, skipping, SboxLayers, Traced};
+use crate::gift128::hooks::RoundHook;
+use crate::gift128::key_schedule::precompute_round_keys;
+use crate::gift128::packing::{pack, unpack};
+use crate::gift128::rounds::{rounds_with_hook, ROUNDS, ROUND_CONSTANTS};
+use crate::gift128::sbox::inv_sbox;
+use crate::gift128::{Block, Key, State};
+use crate::random::{NoRng, Rng};
+
+// Faults are injected into one of the four slices, in their real order, right after the S-box
+// layer of a round, i.e. they reach the S-boxes of the next round through the linear layer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    BitFlip {
+        round: usize,
+        word: usize,
+        bit: u32,
+    },
+    // The byte is XORed with `value`, which flips one bit in each of eight S-boxes.
+    ByteFault {
+        round: usize,
+        word: usize,
+        byte: u32,
+        value: u8,
+    },
+    // The operation with the given index within the S-box layer of the round is skipped, so the
+    // register it writes keeps the value of its first operand.
+    Skip {
+        round: usize,
+        operation: usize,
+    },
+}
+
+impl Fault {
+    // Whether the fault hits a round, a word and a bit or byte of the state. A skipped operation
+    // past the end of the S-box layer leaves the encryption unchanged.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Fault::BitFlip { round, word, bit } => round < ROUNDS && word < 4 && bit < 32,
+            Fault::ByteFault {
+                round, word, byte, ..
+            } => round < ROUNDS && word < 4 && byte < 4,
+            Fault::Skip { round, .. } => round < ROUNDS,
+        }
+    }
+}
+
+struct FaultHook(Fault);
+
+impl RoundHook<Traced<u32>> for FaultHook {
+    fn before_sbox(&mut self, round: usize) {
+        RoundHook::<Traced<u32>>::before_sbox(&mut SboxLayers, round);
+    }
+
+    fn after_sbox<R: Rng>(&mut self, round: usize, slices: &mut [Traced<u32>; 4], rng: &mut R) {
+        RoundHook::<Traced<u32>>::after_sbox(&mut SboxLayers, round, slices, rng);
+        match self.0 {
+            Fault::BitFlip {
+                round: fault_round,
+                word,
+                bit,
+            } if fault_round == round => slices[word].0 ^= 1 << bit,
+            Fault::ByteFault {
+                round: fault_round,
+                word,
+                byte,
+                value,
+            } if fault_round == round => slices[word].0 ^= u32::from(value) << (8 * byte),
+            _ => {}
+        }
+    }
+}
+
+// Encrypts a single block with the fault injected. Panics if the fault is not valid.
+#[must_use]
+pub fn encrypt_with_fault(plaintext: &Block, key: &Key, fault: Fault) -> Block {
+    assert!(fault.is_valid(), "fault outside of the state: {fault:?}");
+    let round_keys = precompute_round_keys(key).map(Traced);
+    let state = pack(plaintext).map(Traced);
+    let encrypt = || rounds_with_hook(state, &round_keys, &mut FaultHook(fault), &mut NoRng);
+    let state = match fault {
+        Fault::Skip { round, operation } => skipping(round, operation, encrypt),
+        _ => encrypt(),
+    };
+    unpack(state.map(|word| word.0))
+}
+
+// The number of operations in an S-box layer, i.e. the range of `Fault::Skip::operation`.
+#[must_use]
+pub fn sbox_operations() -> usize {
+    let round_keys = precompute_round_keys(&[0; 16]).map(Traced);
+    let state = pack(&[0; 16]).map(Traced);
+    let (_, samples) =
+        capture(|| rounds_with_hook(state, &round_keys, &mut SboxLayers, &mut NoRng));
+    samples
+        .iter()
+        .filter(|sample| sample.sbox_round == Some(0))
+        .count()
+}
+
+// Output slices of the last S-box layer in their real order, up to the two slices that the last
+// round key is added to.
+#[must_use]
+fn last_sbox_output(ciphertext: &Block) -> [u32; 4] {
+    let State(s0, s1, s2, s3) = pack(ciphertext);
+    [
+        s0.rotate_left(24),
+        s1.rotate_left(16),
+        s2.rotate_left(8),
+        s3 ^ ROUND_CONSTANTS[ROUNDS - 1],
+    ]
+}
+
+// Differential fault analysis of the last round. A fault that flips a single bit in the input of
+// an S-box of the last round, e.g. a bit flip or byte fault after the S-box layer of round 38,
+// only leaves the candidates of its two key bits for which inverting the S-box on both
+// ciphertexts gives a single-bit difference.
+#[derive(Clone, Debug)]
+pub struct Dfa {
+    pairs: [usize; SBOXES],
+    consistent: [[usize; CANDIDATES]; SBOXES],
+}
+
+impl Default for Dfa {
+    fn default() -> Self {
+        Dfa::new()
+    }
+}
+
+impl Dfa {
+    #[must_use]
+    pub fn new() -> Self {
+        Dfa {
+            pairs: [0; SBOXES],
+            consistent: [[0; CANDIDATES]; SBOXES],
+        }
+    }
+
+    pub fn add_pair(&mut self, correct: &Block, faulty: &Block) {
+        let (output, faulty_output) = (last_sbox_output(correct), last_sbox_output(faulty));
+        let faulted = output
+            .iter()
+            .zip(&faulty_output)
+            .fold(0, |faulted, (a, b)| faulted | (a ^ b));
+
+        for candidate in 0..CANDIDATES {
+            let key1 = if candidate & 1 == 1 { 0xffffffff } else { 0 };
+            let key2 = if candidate & 2 == 2 { 0xffffffff } else { 0 };
+            let input = |[s0, s1, s2, s3]: [u32; 4]| {
+                let State(s0, s1, s2, s3) =
+                    inv_sbox(State(s0, s1 ^ key1, s2 ^ key2, s3), &mut NoRng);
+                [s0, s1, s2, s3]
+            };
+            let (input, faulty_input) = (input(output), input(faulty_output));
+
+            for sbox in (0..SBOXES).filter(|sbox| (faulted >> sbox) & 1 == 1) {
+                let difference: u32 = input
+                    .iter()
+                    .zip(&faulty_input)
+                    .map(|(a, b)| ((a ^ b) >> sbox) & 1)
+                    .sum();
+                if difference == 1 {
+                    self.consistent[sbox][candidate] += 1;
+                }
+            }
+        }
+
+        for sbox in (0..SBOXES).filter(|sbox| (faulted >> sbox) & 1 == 1) {
+            self.pairs[sbox] += 1;
+        }
+    }
+
+    // The candidates of the S-box that are consistent with all of its faulty pairs.
+    #[must_use]
+    pub fn candidates(&self, sbox: usize) -> Vec<usize> {
+        (0..CANDIDATES)
+            .filter(|&candidate| self.consistent[sbox][candidate] == self.pairs[sbox])
+            .collect()
+    }
+
+    // The last two round key words, `precompute_round_keys(key)[78..80]`, once every S-box is
+    // left with a single candidate.
+    #[must_use]
+    pub fn round_keys(&self) -> Option<(u32, u32)> {
+        let (mut key1, mut key2) = (0u32, 0u32);
+        for sbox in 0..SBOXES {
+            let [candidate] = self.candidates(sbox)[..] else {
+                return None;
+            };
+            key1 |= ((candidate & 1) as u32) << sbox;
+            key2 |= ((candidate >> 1) as u32) << sbox;
+        }
+        Some((key1.rotate_right(16), key2.rotate_right(8)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::fault::{encrypt_with_fault, sbox_operations, Dfa, Fault};
+    use crate::gift128::key_schedule::precompute_round_keys;
+    use crate::gift128::tests::KEY;
+    use crate::gift128::{encrypt, Block};
+    use crate::random::{Rng, XorShiftRng};
+
+    fn correct(plaintext: &Block) -> Block {
+        let mut ciphertext = [0; 16];
+        encrypt(plaintext, &KEY, &mut ciphertext);
+        ciphertext
+    }
+
+    #[test]
+    fn test_faults() {
+        let plaintext = [0x5a; 16];
+        let ciphertext = correct(&plaintext);
+        let faults = [
+            Fault::BitFlip {
+                round: 20,
+                word: 1,
+                bit: 5,
+            },
+            Fault::ByteFault {
+                round: 39,
+                word: 3,
+                byte: 2,
+                value: 0x81,
+            },
+            Fault::Skip {
+                round: 39,
+                operation: 0,
+            },
+        ];
+        for fault in faults {
+            assert_ne!(encrypt_with_fault(&plaintext, &KEY, fault), ciphertext);
+        }
+
+        let operations = sbox_operations();
+        assert!(operations > 0);
+        let fault = Fault::Skip {
+            round: 39,
+            operation: operations,
+        };
+        assert_eq!(encrypt_with_fault(&plaintext, &KEY, fault), ciphertext);
+
+        // After the last S-box layer, a bit flip only changes a single ciphertext bit.
+        let fault = Fault::BitFlip {
+            round: 39,
+            word: 0,
+            bit: 0,
+        };
+        let faulty = encrypt_with_fault(&plaintext, &KEY, fault);
+        let difference: u32 = ciphertext
+            .iter()
+            .zip(&faulty)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        assert_eq!(difference, 1);
+    }
+
+    #[test]
+    fn test_validation() {
+        let valid = Fault::ByteFault {
+            round: 39,
+            word: 3,
+            byte: 3,
+            value: 0xff,
+        };
+        assert!(valid.is_valid());
+        let invalid = [
+            Fault::BitFlip {
+                round: 0,
+                word: 0,
+                bit: 32,
+            },
+            Fault::BitFlip {
+                round: 0,
+                word: 4,
+                bit: 0,
+            },
+            Fault::ByteFault {
+                round: 0,
+                word: 0,
+                byte: 4,
+                value: 1,
+            },
+            Fault::Skip {
+                round: 40,
+                operation: 0,
+            },
+        ];
+        for fault in invalid {
+            assert!(!fault.is_valid(), "{fault:?}");
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_fault() {
+        let fault = Fault::BitFlip {
+            round: 0,
+            word: 0,
+            bit: 32,
+        };
+        let _ = encrypt_with_fault(&[0; 16], &KEY, fault);
+    }
+
+    fn faults_needed(fault: impl Fn(&mut XorShiftRng) -> Fault) -> usize {
+        let mut rng = XorShiftRng::new(1);
+        let round_keys = precompute_round_keys(&KEY);
+        let mut dfa = Dfa::new();
+        for faults in 1..=1000 {
+            let mut plaintext = [0; 16];
+            rng.fill_bytes(&mut plaintext);
+            let faulty = encrypt_with_fault(&plaintext, &KEY, fault(&mut rng));
+            dfa.add_pair(&correct(&plaintext), &faulty);
+            if let Some(keys) = dfa.round_keys() {
+                assert_eq!(keys, (round_keys[78], round_keys[79]));
+                return faults;
+            }
+        }
+        panic!("round keys not recovered");
+    }
+
+    #[test]
+    fn test_dfa() {
+        let bit_flips = faults_needed(|rng| Fault::BitFlip {
+            round: 38,
+            word: (rng.next_u32() % 4) as usize,
+            bit: rng.next_u32() % 32,
+        });
+        let byte_faults = faults_needed(|rng| Fault::ByteFault {
+            round: 38,
+            word: (rng.next_u32() % 4) as usize,
+            byte: rng.next_u32() % 4,
+            value: 0xff,
+        });
+        assert!(bit_flips < 300);
+        assert!(byte_faults < bit_flips);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod cpa;
+pub mod fault;
+pub mod probing;
+pub mod second_order;
+pub mod simulation;
+pub mod traced;
+pub mod transition;
+pub mod tvla;
@@ -0,0 +1,1135 @@
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not, Shl, Shr};
+use core::panic::Location;
+use std::boxed::Box;
+use std::collections::{BTreeMap, BTreeSet};
+use std::thread_local;
+use std::vec::Vec;
+
+use crate::gift128::dom::DomShares;
+use crate::gift128::hooks::NoHook;
+use crate::gift128::key_schedule::fill_round_keys;
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::rounds::{quintuple_round, ROUNDS, ROUND_CONSTANTS};
+use crate::gift128::sbox::sbox;
+use crate::gift128::shares::Shares;
+use crate::gift128::threshold::TiShares;
+use crate::gift128::traits::{Rotate, SwapBytes};
+use crate::gift128::State;
+use crate::random::{NoRng, Random, Rng};
+
+const MAX_VARIABLES: usize = 8192;
+// Larger expressions are only tracked by the variables they may depend on.
+const MAX_MONOMIALS: usize = 256;
+// Expressions on at most this many variables are checked by enumerating all assignments.
+const MAX_ENUMERATED: usize = 12;
+// The values of the first six variables over 64 consecutive assignments.
+const VARIABLE_PATTERNS: [u64; 6] = [
+    0xaaaaaaaaaaaaaaaa,
+    0xcccccccccccccccc,
+    0xf0f0f0f0f0f0f0f0,
+    0xff00ff00ff00ff00,
+    0xffff0000ffff0000,
+    0xffffffff00000000,
+];
+
+type Variables = [u64; MAX_VARIABLES / 64];
+type Monomial = Vec<u16>;
+// The random variable that replaces a node plus one, or 0 if it is not replaced, indexed by node.
+type Substitutes = Vec<u16>;
+
+#[derive(Clone, Debug)]
+enum Expression {
+    // Algebraic normal form, i.e. a XOR of products of variables. The empty product is 1.
+    Exact(BTreeSet<Monomial>),
+    Opaque(Box<Variables>),
+}
+
+// How a node was computed, so that its expression can be rebuilt with some nodes substituted.
+#[derive(Copy, Clone, Debug)]
+enum Node {
+    Constant,
+    Variable(u16),
+    Xor(u32, u32),
+    And(u32, u32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Class {
+    // Proven to have the same distribution for every value of the secrets.
+    Secure,
+    // Proven to have a distribution that depends on the secrets.
+    Leak,
+    Unproven,
+}
+
+const ZERO: u32 = 0;
+const ONE: u32 = 1;
+
+struct Context {
+    secret: Vec<bool>,
+    expressions: Vec<Expression>,
+    nodes: Vec<Node>,
+    classes: Vec<Option<Class>>,
+    probes: Vec<(&'static Location<'static>, Symbolic)>,
+}
+
+impl Context {
+    fn new() -> Self {
+        let mut one = BTreeSet::new();
+        one.insert(Vec::new());
+        Context {
+            secret: Vec::new(),
+            expressions: std::vec![Expression::Exact(BTreeSet::new()), Expression::Exact(one)],
+            nodes: std::vec![Node::Constant, Node::Constant],
+            classes: std::vec![Some(Class::Secure), Some(Class::Secure)],
+            probes: Vec::new(),
+        }
+    }
+
+    fn variable(&mut self, secret: bool) -> u32 {
+        let variable = self.secret.len();
+        if variable == MAX_VARIABLES {
+            panic!("too many symbolic variables");
+        }
+        self.secret.push(secret);
+
+        self.push(
+            Expression::variable(variable as u16),
+            Node::Variable(variable as u16),
+        )
+    }
+
+    fn push(&mut self, expression: Expression, node: Node) -> u32 {
+        self.expressions.push(expression.bounded());
+        self.nodes.push(node);
+        self.classes.push(None);
+        (self.expressions.len() - 1) as u32
+    }
+
+    fn xor(&mut self, a: u32, b: u32) -> u32 {
+        if a == ZERO {
+            return b;
+        }
+        if b == ZERO {
+            return a;
+        }
+
+        let expression = self.expressions[a as usize].xor(&self.expressions[b as usize]);
+        self.push(expression, Node::Xor(a, b))
+    }
+
+    fn and(&mut self, a: u32, b: u32) -> u32 {
+        if a == ZERO || b == ZERO {
+            return ZERO;
+        }
+        if a == ONE {
+            return b;
+        }
+        if b == ONE {
+            return a;
+        }
+
+        let expression = self.expressions[a as usize].and(&self.expressions[b as usize]);
+        self.push(expression, Node::And(a, b))
+    }
+
+    // The variables a node may depend on, in ascending order.
+    fn support(&self, node: u32) -> Vec<u16> {
+        match &self.expressions[node as usize] {
+            Expression::Exact(monomials) => {
+                let mut support: Vec<u16> = monomials.iter().flatten().copied().collect();
+                support.sort_unstable();
+                support.dedup();
+                support
+            }
+            Expression::Opaque(variables) => (0..MAX_VARIABLES)
+                .filter(|&variable| variables[variable / 64] >> (variable % 64) & 1 == 1)
+                .map(|variable| variable as u16)
+                .collect(),
+        }
+    }
+
+    // The class of the XOR of two probed bits with the given supports. Bits on disjoint variables
+    // are independent, so the pair can only leak if one of them does on its own, which the single
+    // probes already report.
+    fn pair_class(
+        &mut self,
+        (a, a_support): (u32, &[u16]),
+        (b, b_support): (u32, &[u16]),
+    ) -> Class {
+        if disjoint(a_support, b_support) {
+            return Class::Secure;
+        }
+        let combined = self.xor(a, b);
+        self.class(combined)
+    }
+
+    fn class(&mut self, node: u32) -> Class {
+        if let Some(class) = self.classes[node as usize] {
+            return class;
+        }
+
+        let class = match self.exact_class(node) {
+            Class::Unproven => self.sampled(node),
+            class => class,
+        };
+        self.classes[node as usize] = Some(class);
+        class
+    }
+
+    // The class of the expression of `node` alone. Unlike `class`, this stays fast on the
+    // computation of a whole encryption.
+    fn exact_class(&self, node: u32) -> Class {
+        self.expressions[node as usize].class(&self.secret)
+    }
+
+    // Optimistic sampling: an XOR with a random variable that occurs nowhere else in the
+    // computation of `node` is uniform and independent of everything else, so it can be replaced
+    // by that variable. This removes the fresh randomness of a gadget together with everything it
+    // masks, however large the masked products have become.
+    fn sampled(&self, node: u32) -> Class {
+        let substitutes = self.substitutes(node);
+        let order = self.post_order(node, &substitutes);
+        let support = self.support_of(&order, &substitutes);
+        if support.len() <= MAX_ENUMERATED {
+            self.enumerated(&order, &substitutes, &support)
+        } else if substitutes.iter().all(|&substitute| substitute == 0) {
+            Class::Unproven
+        } else {
+            self.rebuilt(&order, &substitutes).class(&self.secret)
+        }
+    }
+
+    // The substitutes for the computation of `root`, found in a single pass.
+    fn substitutes(&self, root: u32) -> Substitutes {
+        let mut substitutes = std::vec![0; self.nodes.len()];
+        // The number of uses of every node and the XOR of the nodes using it, which is the only
+        // one left when the node is used once.
+        let mut uses = std::vec![0u32; self.nodes.len()];
+        let mut parents = std::vec![0u32; self.nodes.len()];
+        let order = self.post_order(root, &substitutes);
+        for &parent in &order {
+            if let Node::Xor(a, b) | Node::And(a, b) = self.nodes[parent as usize] {
+                for child in [a, b] {
+                    uses[child as usize] += 1;
+                    parents[child as usize] ^= parent;
+                }
+            }
+        }
+
+        // Leaves of random variables that are used once, i.e. candidates for a substitution.
+        let random = |node: u32, substitutes: &Substitutes| {
+            self.leaf(node, substitutes)
+                .is_some_and(|variable| !self.secret[variable as usize])
+        };
+        let mut pending: Vec<u32> = order
+            .iter()
+            .copied()
+            .filter(|&node| uses[node as usize] == 1 && random(node, &substitutes))
+            .collect();
+        // Nodes whose uses of other nodes were removed, as they are substituted or unused.
+        let mut removed = std::vec![false; self.nodes.len()];
+        while let Some(leaf) = pending.pop() {
+            if uses[leaf as usize] != 1 {
+                continue;
+            }
+            let Some(variable) = self.leaf(leaf, &substitutes) else {
+                continue;
+            };
+            let parent = parents[leaf as usize];
+            if !matches!(self.nodes[parent as usize], Node::Xor(..)) {
+                continue;
+            }
+
+            substitutes[parent as usize] = variable + 1;
+            let mut unused = std::vec![parent];
+            while let Some(node) = unused.pop() {
+                removed[node as usize] = true;
+                if let Node::Xor(a, b) | Node::And(a, b) = self.nodes[node as usize] {
+                    for child in [a, b] {
+                        uses[child as usize] -= 1;
+                        parents[child as usize] ^= node;
+                        match uses[child as usize] {
+                            0 if !removed[child as usize] => unused.push(child),
+                            1 if random(child, &substitutes) => pending.push(child),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            pending.push(parent);
+        }
+        substitutes
+    }
+
+    // The expression of the last node of `order` with the substituted nodes replaced by their
+    // variables.
+    fn rebuilt(&self, order: &[u32], substitutes: &Substitutes) -> Expression {
+        let mut expressions: BTreeMap<u32, Expression> = BTreeMap::new();
+        for &node in order {
+            let expression = match (
+                self.substitute(node, substitutes),
+                self.nodes[node as usize],
+            ) {
+                (Some(variable), _) => Expression::variable(variable),
+                (None, Node::Xor(a, b)) => expressions[&a].xor(&expressions[&b]).bounded(),
+                (None, Node::And(a, b)) => expressions[&a].and(&expressions[&b]).bounded(),
+                (None, _) => self.expressions[node as usize].clone(),
+            };
+            expressions.insert(node, expression);
+        }
+        order
+            .last()
+            .and_then(|node| expressions.remove(node))
+            .unwrap_or(Expression::Exact(BTreeSet::new()))
+    }
+
+    fn substitute(&self, node: u32, substitutes: &Substitutes) -> Option<u16> {
+        substitutes[node as usize].checked_sub(1)
+    }
+
+    // The variable of a leaf of the computation, which is either a variable or substituted.
+    fn leaf(&self, node: u32, substitutes: &Substitutes) -> Option<u16> {
+        match (
+            self.substitute(node, substitutes),
+            self.nodes[node as usize],
+        ) {
+            (Some(variable), _) | (None, Node::Variable(variable)) => Some(variable),
+            _ => None,
+        }
+    }
+
+    // The variables the last node of `order` is computed from, in ascending order.
+    fn support_of(&self, order: &[u32], substitutes: &Substitutes) -> Vec<u16> {
+        let mut support: Vec<u16> = order
+            .iter()
+            .filter_map(|&node| self.leaf(node, substitutes))
+            .collect();
+        support.sort_unstable();
+        support.dedup();
+        support
+    }
+
+    // Decides `node` by evaluating its computation for all assignments of the variables, which
+    // also works for expressions with too many monomials to be kept exactly.
+    fn enumerated(&self, order: &[u32], substitutes: &Substitutes, support: &[u16]) -> Class {
+        // The randoms are the low bits of the assignment, as in `classify`.
+        let (secrets, randoms): (Vec<u16>, Vec<u16>) = support
+            .iter()
+            .partition(|&&variable| self.secret[variable as usize]);
+        let order_of_variables: Vec<u16> = randoms.iter().chain(&secrets).copied().collect();
+        let words = (1usize << support.len()).div_ceil(64);
+        let mut tables: BTreeMap<u32, Vec<u64>> = BTreeMap::new();
+        for &node in order {
+            let table = match (self.leaf(node, substitutes), self.nodes[node as usize]) {
+                (Some(variable), _) => {
+                    let i = order_of_variables
+                        .iter()
+                        .position(|&v| v == variable)
+                        .unwrap_or_default();
+                    (0..words)
+                        .map(|word| match i.checked_sub(6) {
+                            None => VARIABLE_PATTERNS[i],
+                            Some(shift) if word >> shift & 1 == 1 => !0,
+                            Some(_) => 0,
+                        })
+                        .collect()
+                }
+                (None, Node::Xor(a, b)) => tables[&a]
+                    .iter()
+                    .zip(&tables[&b])
+                    .map(|(x, y)| x ^ y)
+                    .collect(),
+                (None, Node::And(a, b)) => tables[&a]
+                    .iter()
+                    .zip(&tables[&b])
+                    .map(|(x, y)| x & y)
+                    .collect(),
+                (None, _) => std::vec![if node == ONE { !0 } else { 0 }; words],
+            };
+            tables.insert(node, table);
+        }
+
+        let Some(table) = order.last().and_then(|node| tables.get(node)) else {
+            return Class::Secure;
+        };
+        let mut counts = (0..1usize << secrets.len()).map(|assignment| {
+            let (first, len) = (assignment << randoms.len(), 1usize << randoms.len());
+            if len < 64 {
+                let bits = table[first / 64] >> (first % 64) & ((1 << len) - 1);
+                bits.count_ones()
+            } else {
+                let words = &table[first / 64..(first + len) / 64];
+                words.iter().map(|word| word.count_ones()).sum()
+            }
+        });
+        let expected = counts.next();
+        if counts.all(|count| Some(count) == expected) {
+            Class::Secure
+        } else {
+            Class::Leak
+        }
+    }
+
+    // The nodes that `node` is computed from, children first. Substituted nodes are leaves.
+    fn post_order(&self, node: u32, substitutes: &Substitutes) -> Vec<u32> {
+        let mut visited = std::vec![false; self.nodes.len()];
+        let mut order = Vec::new();
+        let mut stack = std::vec![(node, false)];
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                order.push(node);
+                continue;
+            }
+            if core::mem::replace(&mut visited[node as usize], true) {
+                continue;
+            }
+            stack.push((node, true));
+            if substitutes[node as usize] != 0 {
+                continue;
+            }
+            if let Node::Xor(a, b) | Node::And(a, b) = self.nodes[node as usize] {
+                stack.push((b, false));
+                stack.push((a, false));
+            }
+        }
+        order
+    }
+}
+
+impl Expression {
+    fn variable(variable: u16) -> Self {
+        let mut monomials = BTreeSet::new();
+        monomials.insert(std::vec![variable]);
+        Expression::Exact(monomials)
+    }
+
+    fn bounded(self) -> Self {
+        match self {
+            Expression::Exact(monomials) if monomials.len() > MAX_MONOMIALS => {
+                Expression::Opaque(Box::new(exact_variables(&monomials)))
+            }
+            expression => expression,
+        }
+    }
+
+    fn variables(&self) -> Variables {
+        match self {
+            Expression::Exact(monomials) => exact_variables(monomials),
+            Expression::Opaque(variables) => **variables,
+        }
+    }
+
+    fn xor(&self, rhs: &Self) -> Self {
+        match (self, rhs) {
+            (Expression::Exact(a), Expression::Exact(b)) => {
+                Expression::Exact(a.symmetric_difference(b).cloned().collect())
+            }
+            _ => Expression::Opaque(Box::new(union(&self.variables(), &rhs.variables()))),
+        }
+    }
+
+    fn and(&self, rhs: &Self) -> Self {
+        match (self, rhs) {
+            (Expression::Exact(a), Expression::Exact(b))
+                if a.len() * b.len() <= MAX_MONOMIALS * MAX_MONOMIALS =>
+            {
+                let mut product = BTreeSet::new();
+                for x in a {
+                    for y in b {
+                        let monomial = merge(x, y);
+                        if !product.remove(&monomial) {
+                            product.insert(monomial);
+                        }
+                    }
+                }
+                Expression::Exact(product)
+            }
+            _ => Expression::Opaque(Box::new(union(&self.variables(), &rhs.variables()))),
+        }
+    }
+
+    fn class(&self, secret: &[bool]) -> Class {
+        match self {
+            Expression::Exact(monomials) => classify(monomials, secret),
+            Expression::Opaque(variables) if !contains_secret(variables, secret) => Class::Secure,
+            Expression::Opaque(_) => Class::Unproven,
+        }
+    }
+}
+
+fn merge(a: &Monomial, b: &Monomial) -> Monomial {
+    let mut monomial: Monomial = a.iter().chain(b).copied().collect();
+    monomial.sort_unstable();
+    monomial.dedup();
+    monomial
+}
+
+fn exact_variables(monomials: &BTreeSet<Monomial>) -> Variables {
+    let mut variables = [0; MAX_VARIABLES / 64];
+    for &variable in monomials.iter().flatten() {
+        variables[variable as usize / 64] |= 1 << (variable % 64);
+    }
+    variables
+}
+
+fn union(a: &Variables, b: &Variables) -> Variables {
+    core::array::from_fn(|i| a[i] | b[i])
+}
+
+fn disjoint(a: &[u16], b: &[u16]) -> bool {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => return false,
+        }
+    }
+    true
+}
+
+fn contains_secret(variables: &Variables, secret: &[bool]) -> bool {
+    secret
+        .iter()
+        .enumerate()
+        .any(|(variable, &s)| s && variables[variable / 64] >> (variable % 64) & 1 == 1)
+}
+
+fn classify(monomials: &BTreeSet<Monomial>, secret: &[bool]) -> Class {
+    let mut support: Vec<u16> = monomials.iter().flatten().copied().collect();
+    support.sort_unstable();
+    support.dedup();
+
+    let (secrets, randoms): (Vec<u16>, Vec<u16>) = support
+        .iter()
+        .partition(|&&variable| secret[variable as usize]);
+    if secrets.is_empty() {
+        return Class::Secure;
+    }
+    if randoms.is_empty() {
+        return Class::Leak;
+    }
+
+    // A random that only occurs on its own masks everything else.
+    let masked = randoms.iter().any(|&random| {
+        monomials
+            .iter()
+            .filter(|monomial| monomial.contains(&random))
+            .all(|monomial| monomial.len() == 1)
+    });
+    if masked {
+        return Class::Secure;
+    }
+
+    if support.len() > MAX_ENUMERATED {
+        return Class::Unproven;
+    }
+
+    // Turn the normal form into a truth table with the randoms in the low bits of the index.
+    let order: Vec<u16> = randoms.iter().chain(&secrets).copied().collect();
+    let mut table = std::vec![false; 1 << order.len()];
+    for monomial in monomials {
+        let index: usize = monomial
+            .iter()
+            .map(|variable| 1 << order.iter().position(|v| v == variable).unwrap_or_default())
+            .sum();
+        table[index] ^= true;
+    }
+    for i in 0..order.len() {
+        for index in 0..table.len() {
+            if index >> i & 1 == 1 {
+                table[index] ^= table[index ^ (1 << i)];
+            }
+        }
+    }
+
+    // Count the assignments of the randoms that give 1, for every assignment of the secrets.
+    let mut counts = table
+        .chunks(1 << randoms.len())
+        .map(|chunk| chunk.iter().filter(|&&value| value).count());
+    let expected = counts.next();
+    if counts.all(|count| Some(count) == expected) {
+        Class::Secure
+    } else {
+        Class::Leak
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+fn with_context<R>(f: impl FnOnce(&mut Context) -> R) -> R {
+    CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        let context = context
+            .as_mut()
+            .expect("symbolic words can only be used inside `verify`");
+        f(context)
+    })
+}
+
+// Runs `f` in a fresh context for symbolic words, restoring the outer one afterwards.
+pub(crate) fn with_symbolic<R>(f: impl FnOnce() -> R) -> R {
+    let outer = CONTEXT.with(|context| context.replace(Some(Context::new())));
+    let result = f();
+    CONTEXT.with(|context| context.replace(outer));
+    result
+}
+
+// A word whose bits are tracked as expressions in secret and random variables. Every result of
+// an operation is a probe, i.e. a value that an attacker can observe.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Symbolic([u32; 32]);
+
+impl Symbolic {
+    #[must_use]
+    pub fn secret() -> Self {
+        Symbolic::variables(true, 32)
+    }
+
+    #[must_use]
+    pub fn fresh() -> Self {
+        Symbolic::variables(false, 32)
+    }
+
+    // A word whose low bits are new variables and whose other bits are 0.
+    #[must_use]
+    pub(crate) fn variables(secret: bool, bits: usize) -> Self {
+        Symbolic(core::array::from_fn(|bit| {
+            if bit < bits {
+                with_context(|context| context.variable(secret))
+            } else {
+                ZERO
+            }
+        }))
+    }
+
+    // Whether the XOR of a bit of both words, e.g. the Hamming distance between them, depends on
+    // the secrets.
+    #[must_use]
+    pub(crate) fn xor_class(self, rhs: Self, bit: usize) -> Class {
+        with_context(|context| {
+            let combined = context.xor(self.0[bit], rhs.0[bit]);
+            context.exact_class(combined)
+        })
+    }
+
+    #[must_use]
+    pub fn constant(value: u32) -> Self {
+        Symbolic(core::array::from_fn(|bit| (value >> bit) & 1))
+    }
+
+    #[track_caller]
+    fn probed(self) -> Self {
+        let location = Location::caller();
+        with_context(|context| context.probes.push((location, self)));
+        self
+    }
+
+    #[track_caller]
+    fn zip(self, rhs: Self, f: impl Fn(&mut Context, u32, u32) -> u32) -> Self {
+        with_context(|context| {
+            Symbolic(core::array::from_fn(|bit| {
+                f(context, self.0[bit], rhs.0[bit])
+            }))
+        })
+        .probed()
+    }
+
+    #[track_caller]
+    fn permute(self, f: impl Fn(usize) -> Option<usize>) -> Self {
+        Symbolic(core::array::from_fn(|bit| {
+            f(bit).map_or(ZERO, |from| self.0[from])
+        }))
+        .probed()
+    }
+}
+
+// Fresh randomness is drawn as new random variables, not from the generator.
+impl Random for Symbolic {
+    fn random<R: Rng>(_rng: &mut R) -> Self {
+        Symbolic::fresh()
+    }
+}
+
+impl BitAnd for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.zip(rhs, Context::and)
+    }
+}
+
+impl BitAnd<u32> for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        self.zip(Symbolic::constant(rhs), Context::and)
+    }
+}
+
+impl BitOr for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.zip(rhs, |context, a, b| {
+            let product = context.and(a, b);
+            let sum = context.xor(a, b);
+            context.xor(sum, product)
+        })
+    }
+}
+
+impl BitXor for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.zip(rhs, Context::xor)
+    }
+}
+
+impl BitXor<u32> for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitxor(self, rhs: u32) -> Self::Output {
+        self.zip(Symbolic::constant(rhs), Context::xor)
+    }
+}
+
+impl BitXorAssign for Symbolic {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = self.zip(rhs, Context::xor);
+    }
+}
+
+impl BitXorAssign<u32> for Symbolic {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        *self = self.zip(Symbolic::constant(rhs), Context::xor);
+    }
+}
+
+impl Not for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn not(self) -> Self::Output {
+        self.zip(Symbolic::constant(0xffffffff), Context::xor)
+    }
+}
+
+impl<R: TryInto<usize>> Shl<R> for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn shl(self, rhs: R) -> Self::Output {
+        let n = rhs.try_into().unwrap_or(32);
+        self.permute(|bit| bit.checked_sub(n))
+    }
+}
+
+impl<R: TryInto<usize>> Shr<R> for Symbolic {
+    type Output = Self;
+
+    #[track_caller]
+    fn shr(self, rhs: R) -> Self::Output {
+        let n = rhs.try_into().unwrap_or(32);
+        self.permute(|bit| Some(bit + n).filter(|&from| from < 32))
+    }
+}
+
+impl Rotate for Symbolic {
+    #[track_caller]
+    fn rotate_right(self, rhs: u32) -> Self {
+        self.permute(|bit| Some((bit + rhs as usize) % 32))
+    }
+}
+
+impl SwapBytes for Symbolic {
+    #[track_caller]
+    fn swap_bytes(self) -> Self {
+        self.permute(|bit| Some((3 - bit / 8) * 8 + bit % 8))
+    }
+}
+
+// The generic implementations only accept constants of the share type.
+impl BitAnd<u32> for BinaryMask<Symbolic> {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        BinaryMask(self.0 & rhs, self.1 & rhs)
+    }
+}
+
+impl BitXorAssign<u32> for BinaryMask<Symbolic> {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0 ^= rhs;
+    }
+}
+
+impl<const N: usize> BitAnd<u32> for Shares<Symbolic, N> {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        Shares(self.0.map(|share| share & rhs))
+    }
+}
+
+impl<const N: usize> BitXorAssign<u32> for Shares<Symbolic, N> {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0[0] ^= rhs;
+    }
+}
+
+impl<const N: usize> BitXorAssign<u32> for DomShares<Symbolic, N> {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0[0] ^= rhs;
+    }
+}
+
+impl BitXorAssign<u32> for TiShares<Symbolic> {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0[0] ^= rhs;
+    }
+}
+
+// A bit of an intermediate value, identified by the operation that computed it.
+#[derive(Copy, Clone, Debug)]
+pub struct Finding {
+    pub location: &'static Location<'static>,
+    pub bit: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PairFinding {
+    pub first: Finding,
+    pub second: Finding,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    // Single probes whose distribution depends on the secrets, which refutes first-order probing
+    // security.
+    pub leaks: Vec<Finding>,
+    // Single probes that could not be proven secure either, because their expressions got too
+    // large to analyze exactly.
+    pub unproven: Vec<Finding>,
+    // Pairs of probed bits whose joint distribution depends on the secrets, which refutes
+    // second-order probing security. Two bits are jointly independent of the secrets if each of
+    // them and their XOR are, so only the XOR is checked here. Joint distributions of more than
+    // two bits, e.g. of two whole words, are not covered.
+    pub pair_leaks: Vec<PairFinding>,
+    // Pairs of probed bits whose XOR could not be decided.
+    pub pair_unproven: Vec<PairFinding>,
+}
+
+impl Report {
+    // First-order probing security is proven when no probe leaks or is left unproven.
+    #[must_use]
+    pub fn is_first_order_secure(&self) -> bool {
+        self.leaks.is_empty() && self.unproven.is_empty()
+    }
+}
+
+// Runs `f` on symbolic words and checks every probe it makes. Pairs of probes are checked when
+// they are at most `pair_distance` operations apart, which is skipped for 0.
+pub fn verify(pair_distance: usize, f: impl FnOnce()) -> Report {
+    with_symbolic(|| {
+        f();
+        with_context(|context| {
+            let mut report = Report::default();
+            let probes = core::mem::take(&mut context.probes);
+            for &(location, word) in &probes {
+                for (bit, &node) in word.0.iter().enumerate() {
+                    match context.class(node) {
+                        Class::Secure => {}
+                        Class::Leak => report.leaks.push(Finding { location, bit }),
+                        Class::Unproven => report.unproven.push(Finding { location, bit }),
+                    }
+                }
+            }
+
+            if pair_distance == 0 {
+                return report;
+            }
+            let supports: Vec<[Vec<u16>; 32]> = probes
+                .iter()
+                .map(|(_, word)| word.0.map(|node| context.support(node)))
+                .collect();
+            for (i, &(first, a)) in probes.iter().enumerate() {
+                for (j, &(second, b)) in probes.iter().enumerate().skip(i + 1).take(pair_distance) {
+                    for (first_bit, &x) in a.0.iter().enumerate() {
+                        for (second_bit, &y) in b.0.iter().enumerate() {
+                            let class = context.pair_class(
+                                (x, &supports[i][first_bit]),
+                                (y, &supports[j][second_bit]),
+                            );
+                            let pair = PairFinding {
+                                first: Finding {
+                                    location: first,
+                                    bit: first_bit,
+                                },
+                                second: Finding {
+                                    location: second,
+                                    bit: second_bit,
+                                },
+                            };
+                            match class {
+                                Class::Secure => {}
+                                Class::Leak => report.pair_leaks.push(pair),
+                                Class::Unproven => report.pair_unproven.push(pair),
+                            }
+                        }
+                    }
+                }
+            }
+            report
+        })
+    })
+}
+
+#[must_use]
+fn masked_secret() -> BinaryMask<Symbolic> {
+    BinaryMask::make_shares(Symbolic::secret(), Symbolic::fresh())
+}
+
+#[must_use]
+fn shared_secret<const N: usize>() -> Shares<Symbolic, N> {
+    let masks: Vec<Symbolic> = (1..N).map(|_| Symbolic::fresh()).collect();
+    Shares::make_shares(Symbolic::secret(), &masks)
+}
+
+// The S-box layer on a state masked by `BinaryMask`.
+#[must_use]
+pub fn verify_masked_sbox(pair_distance: usize) -> Report {
+    verify(pair_distance, || {
+        let state = State(
+            masked_secret(),
+            masked_secret(),
+            masked_secret(),
+            masked_secret(),
+        );
+        let _ = sbox(state, &mut NoRng);
+    })
+}
+
+// The S-box layer on a state split into N shares.
+#[must_use]
+pub fn verify_shared_sbox<const N: usize>(pair_distance: usize) -> Report {
+    verify(pair_distance, || {
+        let state = State(
+            shared_secret::<N>(),
+            shared_secret(),
+            shared_secret(),
+            shared_secret(),
+        );
+        let _ = sbox(state, &mut NoRng);
+    })
+}
+
+// The S-box layer on a state split into N shares that are multiplied with domain-oriented masking.
+#[must_use]
+pub fn verify_dom_sbox<const N: usize>(pair_distance: usize) -> Report {
+    verify(pair_distance, || {
+        let dom_secret = || DomShares::from(shared_secret::<N>());
+        let _ = sbox(
+            State(dom_secret(), dom_secret(), dom_secret(), dom_secret()),
+            &mut NoRng,
+        );
+    })
+}
+
+// The S-box layer of the threshold implementation.
+#[must_use]
+pub fn verify_ti_sbox(pair_distance: usize) -> Report {
+    verify(pair_distance, || {
+        let ti_secret =
+            || TiShares::make_shares(Symbolic::secret(), [Symbolic::fresh(), Symbolic::fresh()]);
+        let _ = sbox(
+            State(ti_secret(), ti_secret(), ti_secret(), ti_secret()),
+            &mut NoRng,
+        );
+    })
+}
+
+// The first five rounds on a masked state with masked round keys.
+#[must_use]
+pub fn verify_masked_quintuple_round(pair_distance: usize) -> Report {
+    verify(pair_distance, || {
+        let state = State(
+            masked_secret(),
+            masked_secret(),
+            masked_secret(),
+            masked_secret(),
+        );
+        let round_keys: [BinaryMask<Symbolic>; 10] = core::array::from_fn(|_| masked_secret());
+        let _ = quintuple_round(
+            state,
+            &round_keys,
+            &ROUND_CONSTANTS[..5],
+            0,
+            &mut NoHook,
+            &mut NoRng,
+        );
+    })
+}
+
+// The expansion of a masked key into all round keys.
+#[must_use]
+pub fn verify_masked_key_schedule(pair_distance: usize) -> Report {
+    verify(pair_distance, || {
+        let zero = BinaryMask(Symbolic::constant(0), Symbolic::constant(0));
+        let mut round_keys = [zero; ROUNDS * 2];
+        for round_key in &mut round_keys[..4] {
+            *round_key = masked_secret();
+        }
+        fill_round_keys(&mut round_keys);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::probing::{
+        verify, verify_dom_sbox, verify_masked_key_schedule, verify_masked_quintuple_round,
+        verify_masked_sbox, verify_shared_sbox, verify_ti_sbox, Symbolic,
+    };
+    use crate::gift128::masking::BinaryMask;
+    use crate::gift128::traits::Rotate;
+
+    #[test]
+    fn test_classification() {
+        let report = verify(0, || {
+            let x = Symbolic::secret();
+            let r = Symbolic::fresh();
+            let masked = x ^ r;
+            // Proven by enumeration.
+            let _ = masked & Symbolic::fresh();
+            let _ = masked & (Symbolic::secret() ^ Symbolic::fresh());
+            // Recombined.
+            let _ = masked ^ r;
+        });
+        assert_eq!(report.leaks.len(), 32);
+        assert!(report.unproven.is_empty());
+        assert_eq!(report.leaks[0].location.file(), file!());
+    }
+
+    #[test]
+    fn test_pair_leaks() {
+        let report = verify(1, || {
+            let r = Symbolic::fresh();
+            let _ = Symbolic::secret() ^ r;
+            let _ = r & 0xffffffff;
+        });
+        assert!(report.leaks.is_empty());
+        assert_eq!(report.pair_leaks.len(), 32);
+
+        // Different bits of the two probes that share a mask.
+        let report = verify(1, || {
+            let r = Symbolic::fresh();
+            let _ = Symbolic::secret() ^ r;
+            let _ = r.rotate_right(1);
+        });
+        assert!(report.leaks.is_empty());
+        assert_eq!(report.pair_leaks.len(), 32);
+        assert!(report
+            .pair_leaks
+            .iter()
+            .all(|pair| pair.first.bit == (pair.second.bit + 1) % 32));
+    }
+
+    #[test]
+    fn test_optimistic_sampling() {
+        // The product of seven masked secrets depends on too many variables to be enumerated, but
+        // every factor can be replaced by its mask.
+        let masked = || Symbolic::secret() ^ Symbolic::fresh();
+        let report = verify(0, || {
+            let _ = (0..6).fold(masked(), |product, _| product & masked());
+        });
+        assert!(report.is_first_order_secure());
+
+        // The product of the masks is biased, so adding a secret to it leaks.
+        let report = verify(0, || {
+            let product = (0..6).fold(masked(), |product, _| product & masked());
+            let _ = product ^ Symbolic::secret();
+        });
+        assert_eq!(report.leaks.len(), 32);
+        assert!(report.unproven.is_empty());
+    }
+
+    #[test]
+    fn test_broken_gadget() {
+        let report = verify(0, || {
+            let a = BinaryMask::make_shares(Symbolic::secret(), Symbolic::fresh());
+            let b = BinaryMask::make_shares(Symbolic::secret(), Symbolic::fresh());
+            // Computes the AND on the unmasked values.
+            let _ = BinaryMask(((a.0 ^ a.1) & (b.0 ^ b.1)) ^ a.1, a.1);
+        });
+        assert!(!report.leaks.is_empty());
+    }
+
+    #[test]
+    fn test_masked_sbox() {
+        let report = verify_masked_sbox(1);
+        assert!(report.is_first_order_secure());
+        // Two shares cannot withstand two probes.
+        assert!(!report.pair_leaks.is_empty());
+    }
+
+    #[test]
+    fn test_shared_sbox() {
+        let report = verify_shared_sbox::<3>(4);
+        assert!(report.is_first_order_secure());
+        assert!(report.pair_leaks.is_empty());
+        assert!(report.pair_unproven.is_empty());
+    }
+
+    #[test]
+    fn test_dom_sbox() {
+        let report = verify_dom_sbox::<2>(0);
+        assert!(report.is_first_order_secure());
+        let report = verify_dom_sbox::<3>(4);
+        assert!(report.is_first_order_secure());
+        assert!(report.pair_leaks.is_empty());
+        assert!(report.pair_unproven.is_empty());
+    }
+
+    #[test]
+    fn test_ti_sbox() {
+        let report = verify_ti_sbox(0);
+        assert!(report.is_first_order_secure());
+    }
+
+    #[test]
+    fn test_masked_quintuple_round() {
+        let report = verify_masked_quintuple_round(0);
+        assert!(report.is_first_order_secure());
+    }
+
+    #[test]
+    fn test_masked_key_schedule() {
+        let report = verify_masked_key_schedule(0);
+        assert!(report.is_first_order_secure());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_outside_verify() {
+        let _ = Symbolic::fresh();
+    }
+}
@@ -0,0 +1,288 @@
+use core::ops::Range;
+use std::vec::Vec;
+
+use crate::analysis::cpa::Cpa;
+use crate::gift128::{Block, Key};
+
+// Ways of combining the two points that leak the shares of a `BinaryMask` into one point that
+// leaks the recombined value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Combining {
+    CenteredProduct,
+    AbsoluteDifference,
+}
+
+// All pairs of distinct points in the range that are at most `max_distance` apart.
+#[must_use]
+pub fn pairs(points: Range<usize>, max_distance: usize) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in points.clone() {
+        for j in i + 1..(i + max_distance + 1).min(points.end) {
+            pairs.push((i, j));
+        }
+    }
+    pairs
+}
+
+#[must_use]
+pub fn mean_trace(traces: &[Vec<f64>]) -> Vec<f64> {
+    let mut means = std::vec![0.0; traces.first().map_or(0, Vec::len)];
+    for trace in traces {
+        for (mean, x) in means.iter_mut().zip(trace) {
+            *mean += x;
+        }
+    }
+    for mean in &mut means {
+        *mean /= traces.len() as f64;
+    }
+    means
+}
+
+#[must_use]
+pub fn centered_product(trace: &[f64], means: &[f64], pairs: &[(usize, usize)]) -> Vec<f64> {
+    pairs
+        .iter()
+        .map(|&(i, j)| (trace[i] - means[i]) * (trace[j] - means[j]))
+        .collect()
+}
+
+#[must_use]
+pub fn absolute_difference(trace: &[f64], pairs: &[(usize, usize)]) -> Vec<f64> {
+    pairs
+        .iter()
+        .map(|&(i, j)| (trace[i] - trace[j]).abs())
+        .collect()
+}
+
+// Both shares of a bit being equal means that the recombined bit is 0, which makes the centered
+// product positive and the absolute difference small. The centered product is negated so that
+// both correlate positively with the recombined bit, like the leakage of an unmasked value.
+fn combine(
+    trace: &[f64],
+    means: &[f64],
+    pairs: &[(usize, usize)],
+    combining: Combining,
+) -> Vec<f64> {
+    match combining {
+        Combining::CenteredProduct => centered_product(trace, means, pairs)
+            .into_iter()
+            .map(|x| -x)
+            .collect(),
+        Combining::AbsoluteDifference => absolute_difference(trace, pairs),
+    }
+}
+
+// Runs the CPA of `cpa` on the combined pairs of points, i.e. against the recombined shares of the
+// first key-dependent S-box layer.
+#[must_use]
+pub fn second_order_cpa(
+    plaintexts: &[Block],
+    traces: &[Vec<f64>],
+    pairs: &[(usize, usize)],
+    combining: Combining,
+) -> Cpa {
+    let means = mean_trace(traces);
+    let mut cpa = Cpa::new(pairs.len());
+    for (plaintext, trace) in plaintexts.iter().zip(traces) {
+        cpa.add_trace(plaintext, &combine(trace, &means, pairs, combining));
+    }
+    cpa
+}
+
+// The number of traces after which the attacked key bits are recovered, checked every `step`
+// traces, or `None` if all the traces do not suffice.
+#[must_use]
+pub fn traces_needed(
+    plaintexts: &[Block],
+    traces: &[Vec<f64>],
+    pairs: &[(usize, usize)],
+    combining: Combining,
+    key: &Key,
+    step: usize,
+) -> Option<usize> {
+    if step == 0 {
+        panic!("step is zero");
+    }
+
+    let means = mean_trace(traces);
+    let mut cpa = Cpa::new(pairs.len());
+    for (i, (plaintext, trace)) in plaintexts.iter().zip(traces).enumerate() {
+        cpa.add_trace(plaintext, &combine(trace, &means, pairs, combining));
+        if (i + 1) % step == 0 && cpa.guessing_entropy(key) == 0.0 {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::analysis::cpa::{predictions, second_sbox, subkeys, Cpa};
+    use crate::analysis::second_order::{
+        absolute_difference, centered_product, pairs, second_order_cpa, traces_needed, Combining,
+    };
+    use crate::analysis::simulation::{leakage, trace_encrypt_masked, LeakageModel};
+    use crate::analysis::traced::Sample;
+    use crate::gift128::key_schedule::{mask_key_with_rng, precompute_round_keys};
+    use crate::gift128::tests::KEY;
+    use crate::gift128::{mask_block_with_rng, Block};
+    use crate::random::{Rng, XorShiftRng};
+
+    #[test]
+    fn test_combining() {
+        let trace = [1.0, 4.0, 2.0];
+        let means = [0.0, 2.0, 1.0];
+        assert_eq!(pairs(0..3, 1), [(0, 1), (1, 2)]);
+        assert_eq!(pairs(0..3, 5), [(0, 1), (0, 2), (1, 2)]);
+        assert_eq!(
+            centered_product(&trace, &means, &[(0, 1), (1, 2)]),
+            [2.0, 2.0]
+        );
+        assert_eq!(absolute_difference(&trace, &[(0, 1), (1, 2)]), [3.0, 2.0]);
+    }
+
+    // The points of the second S-box layer, which the attacker would find by inspection.
+    fn second_sbox_points(samples: &[Sample]) -> core::ops::Range<usize> {
+        let in_layer = |sample: &Sample| sample.sbox_round == Some(1);
+        let start = samples.iter().position(in_layer).unwrap_or_default();
+        let end = start + samples[start..].iter().take_while(|s| in_layer(s)).count();
+        start..end
+    }
+
+    // The pairs of points of the second S-box layer that hold the two shares of one of its output
+    // slices, which an attacker would find by profiling a device with a known key.
+    fn share_pairs(samples: &[Sample], plaintext: &Block) -> Vec<(usize, usize)> {
+        let round_keys = precompute_round_keys(&KEY);
+        let slices = second_sbox(plaintext, round_keys[0], round_keys[1]);
+        let points = second_sbox_points(samples);
+        let mut pairs = Vec::new();
+        for i in points.clone() {
+            for j in i + 1..points.end {
+                if slices.contains(&(samples[i].value ^ samples[j].value)) {
+                    pairs.push((i - points.start, j - points.start));
+                }
+            }
+        }
+        pairs
+    }
+
+    type MaskedTraces = (Vec<Block>, Vec<Vec<f64>>, Vec<(usize, usize)>);
+
+    // Traces of the second S-box layer of `encrypt_masked`, with fresh masks for every encryption.
+    fn masked_traces(count: usize) -> MaskedTraces {
+        let mut rng = XorShiftRng::new(1);
+        let mut plaintexts = Vec::new();
+        let mut traces = Vec::new();
+        let mut pairs = Vec::new();
+        for _ in 0..count {
+            let mut plaintext = [0; 16];
+            rng.fill_bytes(&mut plaintext);
+            let key = mask_key_with_rng(&KEY, &mut rng);
+            let masked_plaintext = mask_block_with_rng(&plaintext, &mut rng);
+            let samples = trace_encrypt_masked(&masked_plaintext, &key, &mut rng).1;
+
+            if pairs.is_empty() {
+                pairs = share_pairs(&samples, &plaintext);
+            }
+            plaintexts.push(plaintext);
+            let points = second_sbox_points(&samples);
+            traces.push(leakage(&samples[points], LeakageModel::HammingWeight));
+        }
+        (plaintexts, traces, pairs)
+    }
+
+    // Every output bit of the attacked S-boxes is leaked as two Boolean shares in separate points.
+    #[test]
+    fn test_traces_needed() {
+        let mut rng = XorShiftRng::new(1);
+        let subkeys = subkeys(&KEY);
+        let mut plaintexts = Vec::new();
+        let mut traces = Vec::new();
+        for _ in 0..1000 {
+            let mut plaintext = [0; 16];
+            rng.fill_bytes(&mut plaintext);
+            let predictions = predictions(&plaintext);
+
+            let mut trace = Vec::new();
+            for (sbox, &subkey) in subkeys.iter().enumerate() {
+                for &bit in &predictions[sbox][subkey] {
+                    let mask = f64::from(rng.next_u32() & 1);
+                    trace.push(if bit == mask { 0.0 } else { 1.0 });
+                    trace.push(mask);
+                }
+            }
+            plaintexts.push(plaintext);
+            traces.push(trace);
+        }
+
+        let pairs: Vec<_> = (0..traces[0].len())
+            .step_by(2)
+            .map(|i| (i, i + 1))
+            .collect();
+        for combining in [Combining::CenteredProduct, Combining::AbsoluteDifference] {
+            let needed = traces_needed(&plaintexts, &traces, &pairs, combining, &KEY, 50);
+            assert!(needed.is_some_and(|needed| needed < 1000));
+        }
+    }
+
+    // A quick version of the attack below, on too few traces to recover the whole key.
+    #[test]
+    fn test_second_order_on_masked_smoke() {
+        let (plaintexts, traces, share_pairs) = masked_traces(2000);
+        assert_eq!(share_pairs.len(), 4);
+
+        let mut first_order = Cpa::new(traces[0].len());
+        for (plaintext, trace) in plaintexts.iter().zip(&traces) {
+            first_order.add_trace(plaintext, trace);
+        }
+        let cpa = second_order_cpa(
+            &plaintexts,
+            &traces,
+            &share_pairs,
+            Combining::CenteredProduct,
+        );
+        assert!(cpa.guessing_entropy(&KEY) < first_order.guessing_entropy(&KEY) - 10.0);
+    }
+
+    // Recovery takes about 8000 traces, which simulating is too slow for every test run.
+    #[test]
+    #[ignore = "simulates 16000 masked encryptions"]
+    fn test_second_order_on_masked() {
+        let (plaintexts, traces, share_pairs) = masked_traces(16000);
+        assert_eq!(share_pairs.len(), 4);
+
+        let mut first_order = Cpa::new(traces[0].len());
+        for (plaintext, trace) in plaintexts.iter().zip(&traces) {
+            first_order.add_trace(plaintext, trace);
+        }
+        assert!(first_order.guessing_entropy(&KEY) > 30.0);
+
+        // The centered product of the shares recovers all the attacked key bits, which the
+        // first-order attack does not get anywhere near with the same traces.
+        let needed = traces_needed(
+            &plaintexts,
+            &traces,
+            &share_pairs,
+            Combining::CenteredProduct,
+            &KEY,
+            1000,
+        );
+        assert!(needed.is_some_and(|needed| needed > 2000));
+
+        let cpa = second_order_cpa(
+            &plaintexts,
+            &traces,
+            &share_pairs,
+            Combining::CenteredProduct,
+        );
+        assert_eq!(cpa.guessing_entropy(&KEY), 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_traces_needed_step() {
+        let _ = traces_needed(&[], &[], &[], Combining::CenteredProduct, &KEY, 0);
+    }
+}
@@ -0,0 +1,275 @@
+use std::vec::Vec;
+
+use crate::analysis::traced::{capture, Sample, SboxLayers, Traced};
+use crate::gift128::key_schedule::{
+    precompute_masked_round_keys, precompute_round_keys, RoundKeys,
+};
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::packing::{
+    bitsliced_pack, bitsliced_unpack, masked_pack, masked_unpack, pack, unpack,
+};
+use crate::gift128::rounds::rounds_with_hook;
+use crate::gift128::shares::Shares;
+use crate::gift128::shuffling::shuffled_rounds;
+use crate::gift128::{Block, Key, MaskedBlock, MaskedKey, SharedBlock, SharedKey, State};
+use crate::random::{NoRng, Rng};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LeakageModel {
+    HammingWeight,
+    // Distance to the previously computed value, as when every result overwrites the same
+    // register. The first value is compared against zero.
+    HammingDistance,
+}
+
+// Turns the captured intermediate values into a trace with one point per value.
+#[must_use]
+pub fn leakage(samples: &[Sample], model: LeakageModel) -> Vec<f64> {
+    let mut previous = 0;
+    samples
+        .iter()
+        .map(|sample| {
+            let leaked = match model {
+                LeakageModel::HammingWeight => sample.value.count_ones(),
+                LeakageModel::HammingDistance => (sample.value ^ previous).count_ones(),
+            };
+            previous = sample.value;
+            f64::from(leaked)
+        })
+        .collect()
+}
+
+// Adds normally distributed noise with the given standard deviation to every point.
+pub fn add_noise<R: Rng>(trace: &mut [f64], std_dev: f64, rng: &mut R) {
+    for point in trace {
+        *point += std_dev * standard_normal(rng);
+    }
+}
+
+// Box-Muller transform.
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    // Shift into (0, 1] to keep the logarithm finite.
+    let u1 = (f64::from(rng.next_u32()) + 1.0) / 4294967296.0;
+    let u2 = f64::from(rng.next_u32()) / 4294967296.0;
+    (-2.0 * u1.ln()).sqrt() * (2.0 * core::f64::consts::PI * u2).cos()
+}
+
+// The key schedule and the packing are not part of the traces, only the rounds are. Except for the
+// shuffled ones, the samples of the S-box layers carry their round. The masked rounds draw their
+// fresh randomness from `rng`, which is recorded as a load.
+
+#[must_use]
+pub fn trace_encrypt(plaintext: &Block, key: &Key) -> (Block, Vec<Sample>) {
+    let round_keys = precompute_round_keys(key).map(Traced);
+    let state = pack(plaintext).map(Traced);
+    let (state, samples) =
+        capture(|| rounds_with_hook(state, &round_keys, &mut SboxLayers, &mut NoRng));
+    (unpack(state.map(|word| word.0)), samples)
+}
+
+#[must_use]
+pub fn trace_encrypt_masked<R: Rng>(
+    plaintext: &MaskedBlock,
+    key: &MaskedKey,
+    rng: &mut R,
+) -> (MaskedBlock, Vec<Sample>) {
+    let round_keys = precompute_masked_round_keys(key).map(trace_mask);
+    let state = masked_pack(plaintext).map(trace_mask);
+    let (state, samples) = capture(|| rounds_with_hook(state, &round_keys, &mut SboxLayers, rng));
+    (masked_unpack(state.map(untrace_mask)), samples)
+}
+
+#[must_use]
+pub fn trace_encrypt_shared<const N: usize, R: Rng>(
+    plaintext: &SharedBlock<N>,
+    key: &SharedKey<N>,
+    rng: &mut R,
+) -> (SharedBlock<N>, Vec<Sample>) {
+    let round_keys = precompute_masked_round_keys(key).map(trace_shares);
+    let state = masked_pack(plaintext).map(trace_shares);
+    let (state, samples) = capture(|| rounds_with_hook(state, &round_keys, &mut SboxLayers, rng));
+    (masked_unpack(state.map(untrace_shares)), samples)
+}
+
+// With the shuffling of `encrypt_shuffled`, the trace has the same length for every encryption,
+// but the intermediate values are moved around by the dummy operations.
+#[must_use]
+pub fn trace_encrypt_shuffled<R: Rng>(
+    plaintext: &Block,
+    key: &Key,
+    rng: &mut R,
+) -> (Block, Vec<Sample>) {
+    let round_keys = precompute_round_keys(key).map(Traced);
+    let state = pack(plaintext).map(Traced);
+    let (state, samples) = capture(|| shuffled_rounds(state, &round_keys, rng));
+    (unpack(state.map(|word| word.0)), samples)
+}
+
+#[must_use]
+pub fn trace_encrypt_masked_shuffled<R: Rng>(
+    plaintext: &MaskedBlock,
+    key: &MaskedKey,
+    rng: &mut R,
+) -> (MaskedBlock, Vec<Sample>) {
+    let round_keys = precompute_masked_round_keys(key).map(trace_mask);
+    let state = masked_pack(plaintext).map(trace_mask);
+    let (state, samples) = capture(|| shuffled_rounds(state, &round_keys, rng));
+    (masked_unpack(state.map(untrace_mask)), samples)
+}
+
+#[must_use]
+pub fn trace_bitsliced_encrypt_block(
+    plaintext: &Block,
+    round_keys: &RoundKeys<u32>,
+) -> (Block, Vec<Sample>) {
+    let round_keys = round_keys.map(Traced);
+    let state = bitsliced_pack(plaintext).map(Traced);
+    let (state, samples) =
+        capture(|| rounds_with_hook(state, &round_keys, &mut SboxLayers, &mut NoRng));
+    (bitsliced_unpack(state.map(|word| word.0)), samples)
+}
+
+#[must_use]
+pub fn trace_bitsliced_masked_encrypt_block<R: Rng>(
+    plaintext: &Block,
+    mask: (u32, u32, u32, u32),
+    round_keys: &RoundKeys<BinaryMask<u32>>,
+    rng: &mut R,
+) -> (Block, Vec<Sample>) {
+    let round_keys = round_keys.map(trace_mask);
+    let state = bitsliced_pack(plaintext).make_shares(mask).map(trace_mask);
+    let (state, samples) = capture(|| rounds_with_hook(state, &round_keys, &mut SboxLayers, rng));
+    let state: State<u32> = state.map(untrace_mask).recover_shares();
+    (bitsliced_unpack(state), samples)
+}
+
+#[inline]
+fn trace_mask(word: BinaryMask<u32>) -> BinaryMask<Traced<u32>> {
+    BinaryMask(Traced(word.0), Traced(word.1))
+}
+
+#[inline]
+fn untrace_mask(word: BinaryMask<Traced<u32>>) -> BinaryMask<u32> {
+    BinaryMask(word.0 .0, word.1 .0)
+}
+
+#[inline]
+fn trace_shares<const N: usize>(word: Shares<u32, N>) -> Shares<Traced<u32>, N> {
+    Shares(word.0.map(Traced))
+}
+
+#[inline]
+fn untrace_shares<const N: usize>(word: Shares<Traced<u32>, N>) -> Shares<u32, N> {
+    Shares(word.0.map(|share| share.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::simulation::{
+        add_noise, leakage, trace_bitsliced_encrypt_block, trace_bitsliced_masked_encrypt_block,
+        trace_encrypt, trace_encrypt_masked, trace_encrypt_masked_shuffled, trace_encrypt_shared,
+        trace_encrypt_shuffled, LeakageModel,
+    };
+    use crate::gift128::key_schedule::{
+        mask_key, precompute_masked_round_keys, precompute_round_keys, share_key,
+    };
+    use crate::gift128::tests::KEY;
+    use crate::gift128::{
+        bitsliced_encrypt_block, encrypt, mask_block, share_block, unmask_block, unshare_block,
+    };
+    use crate::random::XorShiftRng;
+
+    const PLAINTEXT: [u8; 16] = [
+        0x33, 0x52, 0x7f, 0xfa, 0x6d, 0x1a, 0x2a, 0x3b, 0x86, 0x3e, 0x1c, 0x13, 0xfc, 0x12, 0xa7,
+        0xc4,
+    ];
+
+    #[test]
+    fn test_traces_match_cipher() {
+        let mut rng = XorShiftRng::new(1);
+        let mut expected = [0; 16];
+        encrypt(&PLAINTEXT, &KEY, &mut expected);
+
+        let (ciphertext, samples) = trace_encrypt(&PLAINTEXT, &KEY);
+        assert_eq!(ciphertext, expected);
+        assert!(!samples.is_empty());
+
+        let masked_key = mask_key(&KEY, &[0x5a; 16]);
+        let masked_plaintext = mask_block(&PLAINTEXT, &[0xa5; 16]);
+        let (ciphertext, _) = trace_encrypt_masked(&masked_plaintext, &masked_key, &mut rng);
+        assert_eq!(unmask_block(&ciphertext), expected);
+
+        let shared_key = share_key::<3>(&KEY, &[[0x5a; 16], [0x3c; 16]]);
+        let shared_plaintext = share_block::<3>(&PLAINTEXT, &[[0xa5; 16], [0xc3; 16]]);
+        let (ciphertext, _) = trace_encrypt_shared(&shared_plaintext, &shared_key, &mut rng);
+        assert_eq!(unshare_block(&ciphertext), expected);
+
+        let round_keys = precompute_round_keys(&KEY);
+        let expected = bitsliced_encrypt_block(&PLAINTEXT, &round_keys);
+        let (ciphertext, _) = trace_bitsliced_encrypt_block(&PLAINTEXT, &round_keys);
+        assert_eq!(ciphertext, expected);
+
+        let masked_round_keys = precompute_masked_round_keys(&masked_key);
+        let (ciphertext, _) = trace_bitsliced_masked_encrypt_block(
+            &PLAINTEXT,
+            (1, 2, 3, 4),
+            &masked_round_keys,
+            &mut rng,
+        );
+        assert_eq!(ciphertext, expected);
+    }
+
+    #[test]
+    fn test_trace_length_is_constant() {
+        let (_, a) = trace_encrypt(&PLAINTEXT, &KEY);
+        let (_, b) = trace_encrypt(&[0; 16], &[0xff; 16]);
+        assert_eq!(a.len(), b.len());
+        assert!(a.iter().zip(&b).all(|(a, b)| a.location == b.location));
+    }
+
+    #[test]
+    fn test_shuffled_traces() {
+        let mut expected = [0; 16];
+        encrypt(&PLAINTEXT, &KEY, &mut expected);
+
+        let mut rng = XorShiftRng::new(3);
+        let (ciphertext, a) = trace_encrypt_shuffled(&PLAINTEXT, &KEY, &mut rng);
+        assert_eq!(ciphertext, expected);
+        let (ciphertext, b) = trace_encrypt_shuffled(&PLAINTEXT, &KEY, &mut rng);
+        assert_eq!(ciphertext, expected);
+        assert_eq!(a.len(), b.len());
+        assert!(a.len() > trace_encrypt(&PLAINTEXT, &KEY).1.len());
+        assert!(a.iter().zip(&b).any(|(a, b)| a.location != b.location));
+
+        let masked_key = mask_key(&KEY, &[0x5a; 16]);
+        let masked_plaintext = mask_block(&PLAINTEXT, &[0xa5; 16]);
+        let (ciphertext, _) =
+            trace_encrypt_masked_shuffled(&masked_plaintext, &masked_key, &mut rng);
+        assert_eq!(unmask_block(&ciphertext), expected);
+    }
+
+    #[test]
+    fn test_leakage_models() {
+        let (_, samples) = trace_encrypt(&PLAINTEXT, &KEY);
+        let weights = leakage(&samples, LeakageModel::HammingWeight);
+        let distances = leakage(&samples, LeakageModel::HammingDistance);
+        assert_eq!(weights.len(), samples.len());
+        assert_eq!(weights[0], f64::from(samples[0].value.count_ones()));
+        assert_eq!(distances[0], weights[0]);
+        assert_eq!(
+            distances[1],
+            f64::from((samples[0].value ^ samples[1].value).count_ones())
+        );
+    }
+
+    #[test]
+    fn test_noise() {
+        let mut trace = std::vec![0.0; 10000];
+        add_noise(&mut trace, 2.0, &mut XorShiftRng::new(1));
+
+        let mean = trace.iter().sum::<f64>() / trace.len() as f64;
+        let variance = trace.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / trace.len() as f64;
+        assert!(mean.abs() < 0.1);
+        assert!((variance.sqrt() - 2.0).abs() < 0.1);
+    }
+}
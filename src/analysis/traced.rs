@@ -0,0 +1,341 @@
+use core::cell::{Cell, RefCell};
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not, Shl, Shr};
+use core::panic::Location;
+use std::thread_local;
+use std::vec::Vec;
+
+use crate::gift128::hooks::RoundHook;
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::shares::Shares;
+use crate::gift128::traits::{Rotate, SwapBytes};
+use crate::random::{Random, Rng};
+
+// An intermediate value computed while recording, labelled with the operation that produced it
+// and, if `SboxLayers` marks them, the round of the S-box layer it was computed in.
+#[derive(Copy, Clone, Debug)]
+pub struct Sample {
+    pub value: u32,
+    pub location: &'static Location<'static>,
+    pub sbox_round: Option<usize>,
+}
+
+thread_local! {
+    static RECORDER: RefCell<Option<Vec<Sample>>> = const { RefCell::new(None) };
+    static SBOX_ROUND: Cell<Option<usize>> = const { Cell::new(None) };
+    static SBOX_OPERATION: Cell<usize> = const { Cell::new(0) };
+    static SKIPPED: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+#[track_caller]
+#[inline]
+fn record(value: u32) {
+    let location = Location::caller();
+    let sbox_round = SBOX_ROUND.get();
+    RECORDER.with(|recorder| {
+        if let Some(samples) = recorder.borrow_mut().as_mut() {
+            samples.push(Sample {
+                value,
+                location,
+                sbox_round,
+            });
+        }
+    });
+}
+
+// Marks the S-box layers of the rounds it is passed to, so that the samples computed in them
+// carry their round.
+pub struct SboxLayers;
+
+impl<T> RoundHook<T> for SboxLayers {
+    fn before_sbox(&mut self, round: usize) {
+        SBOX_ROUND.set(Some(round));
+        SBOX_OPERATION.set(0);
+    }
+
+    fn after_sbox<R: Rng>(&mut self, _round: usize, _slices: &mut [T; 4], _rng: &mut R) {
+        SBOX_ROUND.set(None);
+    }
+}
+
+// Runs `f` and returns every value computed by `Traced` words in the meantime, in order.
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, Vec<Sample>) {
+    let outer = RECORDER.with(|recorder| recorder.replace(Some(Vec::new())));
+    let result = f();
+    let samples = RECORDER.with(|recorder| recorder.replace(outer));
+    (result, samples.unwrap_or_default())
+}
+
+// Runs `f` with the operation with the given index within the S-box layer of the given round
+// skipped, so the register it writes keeps the value of its first operand. The S-box layers are
+// those marked by `SboxLayers`.
+pub fn skipping<R>(round: usize, operation: usize, f: impl FnOnce() -> R) -> R {
+    let outer = SKIPPED.replace(Some((round, operation)));
+    let result = f();
+    SKIPPED.set(outer);
+    result
+}
+
+// A word that records the result of every operation on it. It can be used wherever the cipher is
+// generic over its word type, also inside `BinaryMask` and `Shares`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Traced<T>(pub T);
+
+impl Traced<u32> {
+    #[track_caller]
+    #[inline]
+    fn recorded(value: u32) -> Self {
+        record(value);
+        Traced(value)
+    }
+
+    // The result `value` of an operation on `self`, unless `skipping` leaves the operation out.
+    #[track_caller]
+    #[inline]
+    fn result(self, value: u32) -> Self {
+        let Some(round) = SBOX_ROUND.get() else {
+            return Traced::recorded(value);
+        };
+        let operation = SBOX_OPERATION.get();
+        SBOX_OPERATION.set(operation + 1);
+        if SKIPPED.get() == Some((round, operation)) {
+            Traced::recorded(self.0)
+        } else {
+            Traced::recorded(value)
+        }
+    }
+}
+
+impl BitAnd for Traced<u32> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.result(self.0 & rhs.0)
+    }
+}
+
+impl BitAnd<u32> for Traced<u32> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        self.result(self.0 & rhs)
+    }
+}
+
+impl BitOr for Traced<u32> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.result(self.0 | rhs.0)
+    }
+}
+
+impl BitXor for Traced<u32> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.result(self.0 ^ rhs.0)
+    }
+}
+
+impl BitXor<u32> for Traced<u32> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn bitxor(self, rhs: u32) -> Self::Output {
+        self.result(self.0 ^ rhs)
+    }
+}
+
+impl BitXorAssign for Traced<u32> {
+    #[track_caller]
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = self.result(self.0 ^ rhs.0);
+    }
+}
+
+impl BitXorAssign<u32> for Traced<u32> {
+    #[track_caller]
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        *self = self.result(self.0 ^ rhs);
+    }
+}
+
+impl Not for Traced<u32> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn not(self) -> Self::Output {
+        self.result(!self.0)
+    }
+}
+
+impl<R> Shl<R> for Traced<u32>
+where
+    u32: Shl<R, Output = u32>,
+{
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn shl(self, rhs: R) -> Self::Output {
+        self.result(self.0 << rhs)
+    }
+}
+
+impl<R> Shr<R> for Traced<u32>
+where
+    u32: Shr<R, Output = u32>,
+{
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn shr(self, rhs: R) -> Self::Output {
+        self.result(self.0 >> rhs)
+    }
+}
+
+impl Rotate for Traced<u32> {
+    #[track_caller]
+    #[inline]
+    fn rotate_right(self, rhs: u32) -> Self {
+        self.result(self.0.rotate_right(rhs))
+    }
+}
+
+impl SwapBytes for Traced<u32> {
+    #[track_caller]
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        self.result(self.0.swap_bytes())
+    }
+}
+
+// Drawing fresh randomness is a load and is recorded as well.
+impl Random for Traced<u32> {
+    #[track_caller]
+    #[inline]
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        Traced::recorded(u32::random(rng))
+    }
+}
+
+// The generic implementations only accept constants of the share type.
+impl BitAnd<u32> for BinaryMask<Traced<u32>> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        BinaryMask(self.0 & rhs, self.1 & rhs)
+    }
+}
+
+impl BitXorAssign<u32> for BinaryMask<Traced<u32>> {
+    #[track_caller]
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0 ^= rhs;
+    }
+}
+
+impl<const N: usize> BitAnd<u32> for Shares<Traced<u32>, N> {
+    type Output = Self;
+
+    #[track_caller]
+    #[inline]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        Shares(self.0.map(|share| share & rhs))
+    }
+}
+
+impl<const N: usize> BitXorAssign<u32> for Shares<Traced<u32>, N> {
+    #[track_caller]
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0[0] ^= rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::traced::{capture, skipping, SboxLayers, Traced};
+    use crate::gift128::key_schedule::precompute_round_keys;
+    use crate::gift128::packing::pack;
+    use crate::gift128::rounds::{rounds_with_hook, ROUNDS};
+    use crate::random::NoRng;
+
+    #[test]
+    fn test_capture() {
+        let a = Traced(0xf0f0f0f0u32);
+        let b = Traced(0x0ff00ff0u32);
+
+        let (c, samples) = capture(|| (a & b) ^ 0xffffffff);
+        assert_eq!(c, Traced(0xff0fff0f));
+        let values: std::vec::Vec<u32> = samples.iter().map(|sample| sample.value).collect();
+        assert_eq!(values, [0x00f000f0, 0xff0fff0f]);
+        assert_eq!(samples[0].location.file(), file!());
+        assert!(samples.iter().all(|sample| sample.sbox_round.is_none()));
+    }
+
+    #[test]
+    fn test_sbox_layers() {
+        let round_keys = precompute_round_keys(&[0; 16]).map(Traced);
+        let state = pack(&[0; 16]).map(Traced);
+        let (_, samples) =
+            capture(|| rounds_with_hook(state, &round_keys, &mut SboxLayers, &mut NoRng));
+        let layer = |round| {
+            samples
+                .iter()
+                .filter(|sample| sample.sbox_round == Some(round))
+                .count()
+        };
+        assert!(layer(0) > 0);
+        assert_eq!(layer(0), layer(ROUNDS - 1));
+        assert!(samples.iter().any(|sample| sample.sbox_round.is_none()));
+    }
+
+    #[test]
+    fn test_skipping() {
+        let round_keys = precompute_round_keys(&[0; 16]).map(Traced);
+        let state = pack(&[0; 16]).map(Traced);
+        let encrypt = || rounds_with_hook(state, &round_keys, &mut SboxLayers, &mut NoRng);
+        let (expected, samples) = capture(encrypt);
+        let (skipped, skipped_samples) = capture(|| skipping(1, 0, encrypt));
+        assert_ne!(skipped, expected);
+        assert_eq!(skipped_samples.len(), samples.len());
+
+        // Outside of the marked S-box layers, nothing is skipped.
+        assert_eq!(skipping(0, 0, || Traced(1u32) ^ Traced(2)), Traced(3));
+    }
+
+    #[test]
+    fn test_nothing_recorded_outside_capture() {
+        let _ = Traced(1u32) ^ Traced(2);
+        let (_, samples) = capture(|| ());
+        assert!(samples.is_empty());
+    }
+
+    #[test]
+    fn test_nested_capture() {
+        let (inner, outer) = capture(|| {
+            let _ = Traced(1u32) ^ Traced(2);
+            capture(|| Traced(1u32) ^ Traced(3)).1
+        });
+        assert_eq!(outer.len(), 1);
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].value, 2);
+    }
+}
@@ -0,0 +1,463 @@
+use core::cell::RefCell;
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not, Shl, Shr};
+use core::panic::Location;
+use std::thread_local;
+use std::vec::Vec;
+
+use crate::analysis::probing::{with_symbolic, Class, Symbolic};
+use crate::gift128::hooks::RoundHook;
+use crate::gift128::key_schedule::precompute_masked_round_keys;
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::packing::{masked_pack, masked_unpack};
+use crate::gift128::rounds::rounds_with_hook;
+use crate::gift128::traits::{MaskedByte, Rotate, SwapBytes};
+use crate::random::{NoRng, Random, Rng};
+
+// The general purpose registers r0 to r12 of a Cortex-M core.
+pub const CORTEX_M_REGISTERS: usize = 13;
+
+// The named operations of an encryption that the findings are attributed to. The linear layer
+// of a round includes the addition of its round key and constant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    KeySchedule,
+    Pack,
+    Sbox { round: usize },
+    LinearLayer { round: usize },
+    Unpack,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    // A register is overwritten, which leaks the Hamming distance between its old and new value.
+    Transition,
+    // The operands of an instruction meet in the ALU, where glitches leak their combination.
+    Glitch,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Finding {
+    pub operation: Operation,
+    pub location: &'static Location<'static>,
+    pub kind: Kind,
+    pub bit: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    // Bits whose combination depends on the secrets, e.g. a register holding one share of a
+    // `BinaryMask` being overwritten by the other share.
+    pub leaks: Vec<Finding>,
+    // Bits whose combination got too large to analyze exactly.
+    pub unproven: Vec<Finding>,
+}
+
+impl Report {
+    // The operations that leak, in the order in which they ran.
+    #[must_use]
+    pub fn leaking_operations(&self) -> Vec<Operation> {
+        let mut operations: Vec<Operation> = Vec::new();
+        for leak in &self.leaks {
+            if !operations.contains(&leak.operation) {
+                operations.push(leak.operation);
+            }
+        }
+        operations
+    }
+}
+
+// A small register file. Every instruction overwrites the register of its first operand, as the
+// two-operand Thumb instructions do, and an operand that is no longer held by any register is
+// loaded into the least recently used one.
+struct Registers {
+    contents: Vec<Option<Symbolic>>,
+    used: Vec<usize>,
+    time: usize,
+    operation: Operation,
+    report: Report,
+}
+
+impl Registers {
+    fn new(registers: usize) -> Self {
+        if registers < 2 {
+            panic!("at least two registers are needed for the operands");
+        }
+
+        Registers {
+            contents: std::vec![None; registers],
+            used: std::vec![0; registers],
+            time: 0,
+            operation: Operation::Pack,
+            report: Report::default(),
+        }
+    }
+
+    fn touch(&mut self, register: usize) {
+        self.time += 1;
+        self.used[register] = self.time;
+    }
+
+    fn load(
+        &mut self,
+        value: Symbolic,
+        other: Option<usize>,
+        location: &'static Location<'static>,
+    ) -> usize {
+        let register = match self.contents.iter().position(|&c| c == Some(value)) {
+            Some(register) => register,
+            None => {
+                let register = (0..self.contents.len())
+                    .filter(|&register| Some(register) != other)
+                    .min_by_key(|&register| self.used[register])
+                    .unwrap_or_default();
+                self.write(register, value, location);
+                register
+            }
+        };
+        self.touch(register);
+        register
+    }
+
+    fn write(&mut self, register: usize, value: Symbolic, location: &'static Location<'static>) {
+        if let Some(old) = self.contents[register] {
+            self.check(old, value, Kind::Transition, location);
+        }
+        self.contents[register] = Some(value);
+        self.touch(register);
+    }
+
+    fn check(
+        &mut self,
+        a: Symbolic,
+        b: Symbolic,
+        kind: Kind,
+        location: &'static Location<'static>,
+    ) {
+        for bit in 0..32 {
+            let finding = Finding {
+                operation: self.operation,
+                location,
+                kind,
+                bit,
+            };
+            match a.xor_class(b, bit) {
+                Class::Secure => {}
+                Class::Leak => self.report.leaks.push(finding),
+                Class::Unproven => self.report.unproven.push(finding),
+            }
+        }
+    }
+}
+
+thread_local! {
+    static REGISTERS: RefCell<Option<Registers>> = const { RefCell::new(None) };
+}
+
+fn with_registers<R>(f: impl FnOnce(&mut Registers) -> R) -> R {
+    REGISTERS.with(|registers| {
+        let mut registers = registers.borrow_mut();
+        let registers = registers
+            .as_mut()
+            .expect("tracked words can only be used inside `check`");
+        f(registers)
+    })
+}
+
+fn set_operation(operation: Operation) {
+    with_registers(|registers| registers.operation = operation);
+}
+
+// A symbolic word that is followed through the register file, so that the shares it holds can
+// be compared with the ones that overwrite it.
+#[derive(Copy, Clone, Debug)]
+pub struct Tracked(Symbolic);
+
+impl Tracked {
+    #[must_use]
+    pub fn secret() -> Self {
+        Tracked(Symbolic::secret())
+    }
+
+    #[must_use]
+    pub fn fresh() -> Self {
+        Tracked(Symbolic::fresh())
+    }
+
+    #[must_use]
+    pub fn constant(value: u32) -> Self {
+        Tracked(Symbolic::constant(value))
+    }
+
+    #[track_caller]
+    fn unary(self, result: Symbolic) -> Self {
+        let location = Location::caller();
+        with_registers(|registers| {
+            let destination = registers.load(self.0, None, location);
+            registers.write(destination, result, location);
+        });
+        Tracked(result)
+    }
+
+    #[track_caller]
+    fn binary(self, rhs: Self, result: Symbolic) -> Self {
+        let location = Location::caller();
+        with_registers(|registers| {
+            let destination = registers.load(self.0, None, location);
+            registers.load(rhs.0, Some(destination), location);
+            registers.check(self.0, rhs.0, Kind::Glitch, location);
+            registers.write(destination, result, location);
+        });
+        Tracked(result)
+    }
+}
+
+impl Random for Tracked {
+    fn random<R: Rng>(_rng: &mut R) -> Self {
+        Tracked::fresh()
+    }
+}
+
+impl Default for Tracked {
+    fn default() -> Self {
+        Tracked::constant(0)
+    }
+}
+
+impl BitAnd for Tracked {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.binary(rhs, self.0 & rhs.0)
+    }
+}
+
+impl BitAnd<u32> for Tracked {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        self.unary(self.0 & rhs)
+    }
+}
+
+impl BitOr for Tracked {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.binary(rhs, self.0 | rhs.0)
+    }
+}
+
+impl BitXor for Tracked {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.binary(rhs, self.0 ^ rhs.0)
+    }
+}
+
+impl BitXorAssign for Tracked {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = self.binary(rhs, self.0 ^ rhs.0);
+    }
+}
+
+impl BitXorAssign<u32> for Tracked {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        *self = self.unary(self.0 ^ Symbolic::constant(rhs));
+    }
+}
+
+impl Not for Tracked {
+    type Output = Self;
+
+    #[track_caller]
+    fn not(self) -> Self::Output {
+        self.unary(!self.0)
+    }
+}
+
+impl<R: TryInto<usize>> Shl<R> for Tracked {
+    type Output = Self;
+
+    #[track_caller]
+    fn shl(self, rhs: R) -> Self::Output {
+        self.unary(self.0 << rhs)
+    }
+}
+
+impl<R: TryInto<usize>> Shr<R> for Tracked {
+    type Output = Self;
+
+    #[track_caller]
+    fn shr(self, rhs: R) -> Self::Output {
+        self.unary(self.0 >> rhs)
+    }
+}
+
+impl Rotate for Tracked {
+    #[track_caller]
+    fn rotate_right(self, rhs: u32) -> Self {
+        self.unary(self.0.rotate_right(rhs))
+    }
+}
+
+impl SwapBytes for Tracked {
+    #[track_caller]
+    fn swap_bytes(self) -> Self {
+        self.unary(self.0.swap_bytes())
+    }
+}
+
+// The generic implementations only accept constants of the share type.
+impl BitAnd<u32> for BinaryMask<Tracked> {
+    type Output = Self;
+
+    #[track_caller]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        BinaryMask(self.0 & rhs, self.1 & rhs)
+    }
+}
+
+impl BitXorAssign<u32> for BinaryMask<Tracked> {
+    #[track_caller]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0 ^= rhs;
+    }
+}
+
+// A byte of a masked block or key, which is widened into a `Tracked` word when packed.
+#[derive(Copy, Clone, Debug)]
+struct TrackedByte(Tracked);
+
+impl From<BinaryMask<Tracked>> for BinaryMask<TrackedByte> {
+    #[track_caller]
+    fn from(x: BinaryMask<Tracked>) -> Self {
+        BinaryMask(TrackedByte(x.0 & 0xff), TrackedByte(x.1 & 0xff))
+    }
+}
+
+impl From<BinaryMask<TrackedByte>> for BinaryMask<Tracked> {
+    fn from(x: BinaryMask<TrackedByte>) -> Self {
+        BinaryMask(x.0 .0, x.1 .0)
+    }
+}
+
+impl MaskedByte for BinaryMask<TrackedByte> {
+    type Word = BinaryMask<Tracked>;
+}
+
+struct OperationHook;
+
+impl<T> RoundHook<T> for OperationHook {
+    fn before_sbox(&mut self, round: usize) {
+        set_operation(Operation::Sbox { round });
+    }
+
+    fn after_sbox<R: Rng>(&mut self, round: usize, _slices: &mut [T; 4], _rng: &mut R) {
+        set_operation(Operation::LinearLayer { round });
+    }
+}
+
+// Runs `f` on tracked words with the given number of registers and checks every register
+// overwrite and every pair of operands.
+pub fn check(registers: usize, f: impl FnOnce()) -> Report {
+    with_symbolic(|| {
+        let outer = REGISTERS.with(|cell| cell.replace(Some(Registers::new(registers))));
+        f();
+        let report = with_registers(|registers| core::mem::take(&mut registers.report));
+        REGISTERS.with(|cell| cell.replace(outer));
+        report
+    })
+}
+
+fn masked_byte() -> BinaryMask<TrackedByte> {
+    let mask = Symbolic::variables(false, 8);
+    let value = Symbolic::variables(true, 8) ^ mask;
+    BinaryMask(TrackedByte(Tracked(value)), TrackedByte(Tracked(mask)))
+}
+
+// A masked encryption of a single block, from the key schedule and the packing to the unpacking.
+// Past the first rounds, most combinations get too large to analyze and end up unproven.
+#[must_use]
+pub fn check_masked_encrypt(registers: usize) -> Report {
+    check(registers, || {
+        let key: [BinaryMask<TrackedByte>; 16] = core::array::from_fn(|_| masked_byte());
+        let plaintext: [BinaryMask<TrackedByte>; 16] = core::array::from_fn(|_| masked_byte());
+
+        set_operation(Operation::KeySchedule);
+        let round_keys = precompute_masked_round_keys(&key);
+        set_operation(Operation::Pack);
+        let state = masked_pack(&plaintext);
+        let state = rounds_with_hook(state, &round_keys, &mut OperationHook, &mut NoRng);
+        set_operation(Operation::Unpack);
+        let _: [BinaryMask<TrackedByte>; 16] = masked_unpack(state);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::transition::{
+        check, check_masked_encrypt, Kind, Operation, Tracked, CORTEX_M_REGISTERS,
+    };
+    use crate::gift128::masking::BinaryMask;
+
+    #[test]
+    fn test_share_overwrite() {
+        let report = check(2, || {
+            let x = BinaryMask::make_shares(Tracked::secret(), Tracked::fresh());
+            let _ = !x.0;
+            let _ = !Tracked::fresh();
+            // Evicts the complement of the first share.
+            let _ = !x.1;
+        });
+        assert_eq!(report.leaks.len(), 32);
+        assert!(report
+            .leaks
+            .iter()
+            .all(|leak| leak.kind == Kind::Transition));
+        assert_eq!(report.leaks[0].location.file(), file!());
+    }
+
+    #[test]
+    fn test_operands() {
+        let report = check(CORTEX_M_REGISTERS, || {
+            let x = BinaryMask::make_shares(Tracked::secret(), Tracked::fresh());
+            let y = BinaryMask::make_shares(Tracked::secret(), Tracked::fresh());
+            let _ = x.0 ^ y.1;
+            let _ = x.0 ^ x.1;
+        });
+        // Overwriting the first share with the recombined value only leaks the second share.
+        assert_eq!(report.leaks.len(), 32);
+        assert!(report.leaks.iter().all(|leak| leak.kind == Kind::Glitch));
+    }
+
+    #[test]
+    fn test_masked_encrypt() {
+        let report = check_masked_encrypt(CORTEX_M_REGISTERS);
+        let operations = report.leaking_operations();
+        assert!(operations.contains(&Operation::KeySchedule));
+        assert!(operations.contains(&Operation::Pack));
+        // The shares are only mixed when they run out of registers, never in the ALU.
+        assert!(report
+            .leaks
+            .iter()
+            .filter(|leak| leak.operation == Operation::Pack)
+            .all(|leak| leak.kind == Kind::Transition));
+
+        // Enough registers to never evict a value.
+        let report = check_masked_encrypt(4096);
+        assert!(report.leaks.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_outside_check() {
+        let _ = !Tracked::constant(0);
+    }
+}
@@ -0,0 +1,337 @@
+use core::panic::Location;
+use std::vec::Vec;
+
+use crate::analysis::simulation::{add_noise, leakage, LeakageModel};
+use crate::analysis::traced::Sample;
+use crate::gift128::Block;
+use crate::random::Rng;
+
+// The usual threshold of fixed-vs-random leakage assessment.
+pub const THRESHOLD: f64 = 4.5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Order {
+    First,
+    // Compares the variances instead of the means, which exposes leakage of 2-share masking.
+    Second,
+}
+
+// Running central moments up to the fourth, so that both orders can be computed in one pass.
+#[derive(Copy, Clone, Debug, Default)]
+struct Moments {
+    n: f64,
+    mean: f64,
+    m2: f64,
+    m3: f64,
+    m4: f64,
+}
+
+impl Moments {
+    fn add(&mut self, x: f64) {
+        let n = self.n + 1.0;
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let delta_n2 = delta_n * delta_n;
+        let term = delta * delta_n * self.n;
+
+        self.mean += delta_n;
+        self.m4 += term * delta_n2 * (n * n - 3.0 * n + 3.0) + 6.0 * delta_n2 * self.m2
+            - 4.0 * delta_n * self.m3;
+        self.m3 += term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m2 += term;
+        self.n = n;
+    }
+
+    // Mean and variance of the statistic tested at the given order.
+    fn statistic(&self, order: Order) -> (f64, f64) {
+        let variance = self.m2 / self.n;
+        match order {
+            Order::First => (self.mean, variance),
+            Order::Second => (variance, self.m4 / self.n - variance * variance),
+        }
+    }
+}
+
+fn welch(a: &Moments, b: &Moments, order: Order) -> f64 {
+    let (mean_a, variance_a) = a.statistic(order);
+    let (mean_b, variance_b) = b.statistic(order);
+    let difference = mean_a - mean_b;
+    let error = (variance_a / a.n + variance_b / b.n).sqrt();
+
+    if error == 0.0 {
+        // Noise-free traces of unmasked values can be constant within both groups.
+        if difference == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY.copysign(difference)
+        }
+    } else {
+        difference / error
+    }
+}
+
+// Welch t-test per point between a group of traces with fixed inputs and one with random inputs.
+#[derive(Clone, Debug)]
+pub struct TTest {
+    fixed: Vec<Moments>,
+    random: Vec<Moments>,
+}
+
+impl TTest {
+    #[must_use]
+    pub fn new(points: usize) -> Self {
+        TTest {
+            fixed: std::vec![Moments::default(); points],
+            random: std::vec![Moments::default(); points],
+        }
+    }
+
+    pub fn add_fixed(&mut self, trace: &[f64]) {
+        add_trace(&mut self.fixed, trace);
+    }
+
+    pub fn add_random(&mut self, trace: &[f64]) {
+        add_trace(&mut self.random, trace);
+    }
+
+    #[must_use]
+    pub fn t_statistics(&self, order: Order) -> Vec<f64> {
+        self.fixed
+            .iter()
+            .zip(&self.random)
+            .map(|(fixed, random)| welch(fixed, random, order))
+            .collect()
+    }
+}
+
+fn add_trace(moments: &mut [Moments], trace: &[f64]) {
+    if moments.len() != trace.len() {
+        panic!("trace length differs from the number of points");
+    }
+
+    for (moments, &x) in moments.iter_mut().zip(trace) {
+        moments.add(x);
+    }
+}
+
+// A point whose t-statistic crosses the threshold, with the operation that produced it.
+#[derive(Copy, Clone, Debug)]
+pub struct Leak {
+    pub point: usize,
+    pub t: f64,
+    pub location: &'static Location<'static>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub first_order: Vec<f64>,
+    pub second_order: Vec<f64>,
+    pub locations: Vec<&'static Location<'static>>,
+}
+
+impl Report {
+    #[must_use]
+    pub fn leaks(&self, order: Order) -> Vec<Leak> {
+        let t_statistics = match order {
+            Order::First => &self.first_order,
+            Order::Second => &self.second_order,
+        };
+
+        t_statistics
+            .iter()
+            .zip(&self.locations)
+            .enumerate()
+            .filter(|(_, (t, _))| t.abs() > THRESHOLD)
+            .map(|(point, (&t, &location))| Leak { point, t, location })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn max_t(&self, order: Order) -> f64 {
+        let t_statistics = match order {
+            Order::First => &self.first_order,
+            Order::Second => &self.second_order,
+        };
+        t_statistics.iter().fold(0.0, |max, t| max.max(t.abs()))
+    }
+}
+
+// Runs a fixed-vs-random assessment over `traces` simulated traces, choosing the group of every
+// trace at random. `trace` must encrypt the given plaintext, drawing any masks from the
+// generator, and return the captured samples, e.g. using the functions in `simulation`.
+pub fn fixed_vs_random<R: Rng>(
+    traces: usize,
+    fixed: &Block,
+    model: LeakageModel,
+    noise: f64,
+    rng: &mut R,
+    mut trace: impl FnMut(&Block, &mut R) -> Vec<Sample>,
+) -> Report {
+    let mut locations = Vec::new();
+    let mut t_test = TTest::new(0);
+
+    for i in 0..traces {
+        let is_fixed = rng.next_u32() & 1 == 0;
+        let mut plaintext = *fixed;
+        if !is_fixed {
+            rng.fill_bytes(&mut plaintext);
+        }
+
+        let samples = trace(&plaintext, rng);
+        if i == 0 {
+            locations = samples.iter().map(|sample| sample.location).collect();
+            t_test = TTest::new(samples.len());
+        }
+
+        let mut points = leakage(&samples, model);
+        if noise > 0.0 {
+            add_noise(&mut points, noise, rng);
+        }
+
+        if is_fixed {
+            t_test.add_fixed(&points);
+        } else {
+            t_test.add_random(&points);
+        }
+    }
+
+    Report {
+        first_order: t_test.t_statistics(Order::First),
+        second_order: t_test.t_statistics(Order::Second),
+        locations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::analysis::simulation::{
+        add_noise, leakage, trace_bitsliced_masked_encrypt_block, trace_encrypt,
+        trace_encrypt_masked, LeakageModel,
+    };
+    use crate::analysis::tvla::{fixed_vs_random, Order, TTest, THRESHOLD};
+    use crate::gift128::key_schedule::{mask_key_with_rng, precompute_masked_round_keys};
+    use crate::gift128::tests::KEY;
+    use crate::gift128::{mask_block_with_rng, random_state_mask};
+    use crate::random::{Rng, XorShiftRng};
+
+    const FIXED: [u8; 16] = [0; 16];
+
+    #[test]
+    fn test_t_statistics() {
+        let mut t_test = TTest::new(2);
+        t_test.add_fixed(&[1.0, 1.0]);
+        t_test.add_fixed(&[3.0, 3.0]);
+        t_test.add_random(&[1.0, 0.0]);
+        t_test.add_random(&[3.0, 4.0]);
+
+        let first_order = t_test.t_statistics(Order::First);
+        assert_eq!(first_order[0], 0.0);
+        assert_eq!(first_order[1], 0.0);
+        let second_order = t_test.t_statistics(Order::Second);
+        assert_eq!(second_order[0], 0.0);
+        assert!(second_order[1] < 0.0);
+    }
+
+    #[test]
+    fn test_unmasked_leaks() {
+        let mut rng = XorShiftRng::new(1);
+        let report = fixed_vs_random(
+            200,
+            &FIXED,
+            LeakageModel::HammingWeight,
+            1.0,
+            &mut rng,
+            |plaintext, _| trace_encrypt(plaintext, &KEY).1,
+        );
+        assert!(report.max_t(Order::First) > THRESHOLD);
+        assert!(!report.leaks(Order::First).is_empty());
+    }
+
+    #[test]
+    fn test_masked_first_order() {
+        let mut rng = XorShiftRng::new(2);
+        let report = fixed_vs_random(
+            2000,
+            &FIXED,
+            LeakageModel::HammingWeight,
+            1.0,
+            &mut rng,
+            |plaintext, rng| {
+                let key = mask_key_with_rng(&KEY, rng);
+                let plaintext = mask_block_with_rng(plaintext, rng);
+                trace_encrypt_masked(&plaintext, &key, rng).1
+            },
+        );
+        assert!(
+            report.leaks(Order::First).is_empty(),
+            "{:?}",
+            report.leaks(Order::First)
+        );
+    }
+
+    // Most operations on a masked word record its two shares one after the other, so adding up
+    // neighbouring points models a device that leaks both shares at once. The sum has the same
+    // mean for every value, but not the same variance.
+    #[test]
+    fn test_masked_second_order() {
+        let mut rng = XorShiftRng::new(4);
+        let mut t_test = TTest::new(0);
+        for i in 0..1000 {
+            let is_fixed = rng.next_u32() & 1 == 0;
+            let mut plaintext = FIXED;
+            if !is_fixed {
+                rng.fill_bytes(&mut plaintext);
+            }
+
+            let key = mask_key_with_rng(&KEY, &mut rng);
+            let plaintext = mask_block_with_rng(&plaintext, &mut rng);
+            let samples = trace_encrypt_masked(&plaintext, &key, &mut rng).1;
+            let mut points: Vec<f64> = leakage(&samples, LeakageModel::HammingWeight)
+                .chunks_exact(2)
+                .map(|shares| shares[0] + shares[1])
+                .collect();
+            add_noise(&mut points, 1.0, &mut rng);
+            if i == 0 {
+                t_test = TTest::new(points.len());
+            }
+            if is_fixed {
+                t_test.add_fixed(&points);
+            } else {
+                t_test.add_random(&points);
+            }
+        }
+
+        let max_t = |order| {
+            t_test
+                .t_statistics(order)
+                .iter()
+                .fold(0.0, |max: f64, t| max.max(t.abs()))
+        };
+        assert!(max_t(Order::First) < THRESHOLD);
+        assert!(max_t(Order::Second) > THRESHOLD);
+    }
+
+    #[test]
+    fn test_bitsliced_masked_first_order() {
+        let mut rng = XorShiftRng::new(3);
+        let report = fixed_vs_random(
+            2000,
+            &FIXED,
+            LeakageModel::HammingWeight,
+            1.0,
+            &mut rng,
+            |plaintext, rng| {
+                let round_keys = precompute_masked_round_keys(&mask_key_with_rng(&KEY, rng));
+                let mask = random_state_mask(rng);
+                trace_bitsliced_masked_encrypt_block(plaintext, mask, &round_keys, rng).1
+            },
+        );
+        assert!(
+            report.leaks(Order::First).is_empty(),
+            "{:?}",
+            report.leaks(Order::First)
+        );
+    }
+}
@@ -0,0 +1,525 @@
+use crate::gift128::key_schedule::RoundKeys;
+use crate::gift128::keyed::zeroize;
+use crate::gift128::lanes::{pack_lanes, unpack_lanes, Lanes};
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::rounds::rounds;
+use crate::gift128::{
+    mask_block, unmask_block, Block, Gift128, Key, MaskedGift128, MaskedKey, BLOCK_SIZE,
+};
+use crate::random::Rng;
+
+// The number of keystream blocks that are generated at once in the lanes of `Lanes` words, and the
+// number of blocks that are generated for one pass over a buffer.
+const LANES: usize = 4;
+const BATCH_BLOCKS: usize = 2 * LANES;
+
+// Every counter value has been used once, so that any more keystream would repeat earlier keystream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeystreamExhausted;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ByteOrder {
+    // The counter is in the last bytes of the block, most significant byte first.
+    BigEndian,
+    // The counter is in the first bytes of the block, least significant byte first.
+    LittleEndian,
+}
+
+// The initial counter block together with the size and byte order of the counter in it. The rest
+// of the block is a fixed nonce, and the counter wraps around within its bytes. The keystream ends
+// before a counter value comes up a second time, see `Counter::end`.
+#[derive(Clone)]
+struct Counter {
+    initial_block: Block,
+    size: usize,
+    byte_order: ByteOrder,
+}
+
+impl Counter {
+    #[must_use]
+    fn new(initial_block: &Block, size: usize, byte_order: ByteOrder) -> Self {
+        if size == 0 || size > BLOCK_SIZE {
+            panic!("counter size is not between 1 and 16");
+        }
+
+        Counter {
+            initial_block: *initial_block,
+            size,
+            byte_order,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    fn block(&self, index: u64) -> Block {
+        let mut block = self.initial_block;
+        match self.byte_order {
+            ByteOrder::BigEndian => add(block[BLOCK_SIZE - self.size..].iter_mut().rev(), index),
+            ByteOrder::LittleEndian => add(block[..self.size].iter_mut(), index),
+        }
+        block
+    }
+
+    // The length of the keystream in bytes, which is 16 bytes for each of the 2^(8 * size) counter
+    // values, but at most `u64::MAX`.
+    #[must_use]
+    #[inline]
+    fn end(&self) -> u64 {
+        1u64.checked_shl(8 * self.size as u32)
+            .and_then(|values| values.checked_mul(BLOCK_SIZE as u64))
+            .unwrap_or(u64::MAX)
+    }
+}
+
+// Adds to a counter given by its bytes from the least significant one on. The carry out of the
+// most significant byte is dropped.
+#[inline]
+fn add<'a>(bytes: impl Iterator<Item = &'a mut u8>, value: u64) {
+    let mut carry = u128::from(value);
+    for byte in bytes {
+        carry += u128::from(*byte);
+        *byte = carry as u8;
+        carry >>= 8;
+    }
+}
+
+// The position in and the length of the keystream, and the last keystream block, which is kept for
+// a following call that starts in the middle of it. The last block is zeroized on drop.
+#[derive(Clone)]
+struct Stream {
+    position: u64,
+    end: u64,
+    last_block: Option<(u64, Block)>,
+}
+
+impl Stream {
+    #[must_use]
+    fn new(counter: &Counter) -> Self {
+        Stream {
+            position: 0,
+            end: counter.end(),
+            last_block: None,
+        }
+    }
+
+    #[inline]
+    fn seek(&mut self, position: u64) -> Result<(), KeystreamExhausted> {
+        if position > self.end {
+            return Err(KeystreamExhausted);
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    // XORs the keystream into the buffer. `generate` fills its slice with the keystream blocks from
+    // the given index on. The buffer is left untouched if the keystream is too short for it.
+    fn apply(
+        &mut self,
+        buffer: &mut [u8],
+        mut generate: impl FnMut(u64, &mut [Block]),
+    ) -> Result<(), KeystreamExhausted> {
+        match self.position.checked_add(buffer.len() as u64) {
+            Some(end) if end <= self.end => {}
+            _ => return Err(KeystreamExhausted),
+        }
+
+        let mut buffer = buffer;
+
+        let offset = (self.position % BLOCK_SIZE as u64) as usize;
+        if offset != 0 && !buffer.is_empty() {
+            let index = self.position / BLOCK_SIZE as u64;
+            let keystream = match self.last_block {
+                Some((last_index, block)) if last_index == index => block,
+                _ => {
+                    let mut blocks = [[0; BLOCK_SIZE]];
+                    generate(index, &mut blocks);
+                    self.last_block = Some((index, blocks[0]));
+                    blocks[0]
+                }
+            };
+
+            let (head, tail) = buffer.split_at_mut(buffer.len().min(BLOCK_SIZE - offset));
+            xor(head, &keystream[offset..]);
+            self.position += head.len() as u64;
+            buffer = tail;
+        }
+
+        let mut keystream = [[0; BLOCK_SIZE]; BATCH_BLOCKS];
+        let (blocks, remainder) = buffer.as_chunks_mut::<BLOCK_SIZE>();
+        for chunk in blocks.chunks_mut(BATCH_BLOCKS) {
+            let keystream = &mut keystream[..chunk.len()];
+            generate(self.position / BLOCK_SIZE as u64, keystream);
+            for (block, keystream) in chunk.iter_mut().zip(keystream.iter()) {
+                xor(block, keystream);
+            }
+            self.position += (chunk.len() * BLOCK_SIZE) as u64;
+        }
+        zeroize(&mut keystream);
+
+        if !remainder.is_empty() {
+            let index = self.position / BLOCK_SIZE as u64;
+            let mut blocks = [[0; BLOCK_SIZE]];
+            generate(index, &mut blocks);
+            self.last_block = Some((index, blocks[0]));
+            xor(remainder, &blocks[0]);
+            self.position += remainder.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if let Some((_, block)) = self.last_block.as_mut() {
+            zeroize(block);
+        }
+    }
+}
+
+#[inline]
+fn xor(buffer: &mut [u8], keystream: &[u8]) {
+    for (byte, key) in buffer.iter_mut().zip(keystream) {
+        *byte ^= key;
+    }
+}
+
+// GIFT-128 in counter mode. Encryption and decryption are the same operation, and the keystream
+// can be applied in pieces of any size.
+#[derive(Clone)]
+pub struct Ctr {
+    cipher: Gift128,
+    counter: Counter,
+    stream: Stream,
+}
+
+impl Ctr {
+    // `counter_size` is the number of bytes of `initial_block` that hold the counter, e.g. 4 for a
+    // 96-bit nonce and a 32-bit counter.
+    #[must_use]
+    pub fn new(
+        key: &Key,
+        initial_block: &Block,
+        counter_size: usize,
+        byte_order: ByteOrder,
+    ) -> Self {
+        let counter = Counter::new(initial_block, counter_size, byte_order);
+        Ctr {
+            cipher: Gift128::new(key),
+            stream: Stream::new(&counter),
+            counter,
+        }
+    }
+
+    pub fn apply_keystream(&mut self, buffer: &mut [u8]) {
+        if self.try_apply_keystream(buffer).is_err() {
+            panic!("keystream is exhausted");
+        }
+    }
+
+    // Like `apply_keystream`, but returns an error instead of panicking if the buffer reaches past
+    // the end of the keystream. Full batches of keystream blocks are encrypted `LANES` at a time.
+    pub fn try_apply_keystream(&mut self, buffer: &mut [u8]) -> Result<(), KeystreamExhausted> {
+        let (cipher, counter) = (&self.cipher, &self.counter);
+        let mut lanes_round_keys: Option<RoundKeys<Lanes<LANES>>> = None;
+        let result = self.stream.apply(buffer, |index, keystream| {
+            let (batches, remainder) = keystream.as_chunks_mut::<LANES>();
+            if !batches.is_empty() {
+                let lanes_round_keys = lanes_round_keys
+                    .get_or_insert_with(|| cipher.round_keys.map(Lanes::<LANES>::splat));
+                for (i, batch) in batches.iter_mut().enumerate() {
+                    let first = index + (i * LANES) as u64;
+                    let counters = core::array::from_fn(|j| counter.block(first + j as u64));
+                    *batch = unpack_lanes(rounds(pack_lanes(&counters), lanes_round_keys));
+                }
+            }
+
+            let first = index + (batches.len() * LANES) as u64;
+            for (i, block) in remainder.iter_mut().enumerate() {
+                *block = cipher.encrypt_block(&counter.block(first + i as u64));
+            }
+        });
+
+        if let Some(lanes_round_keys) = lanes_round_keys.as_mut() {
+            zeroize(lanes_round_keys);
+        }
+        result
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.stream.position
+    }
+
+    // Moves to a byte position in the keystream, so that e.g. a single packet of a stream can be
+    // decrypted without the ones before it.
+    #[inline]
+    pub fn seek(&mut self, position: u64) {
+        if self.try_seek(position).is_err() {
+            panic!("position is past the end of the keystream");
+        }
+    }
+
+    #[inline]
+    pub fn try_seek(&mut self, position: u64) -> Result<(), KeystreamExhausted> {
+        self.stream.seek(position)
+    }
+}
+
+// Like `Ctr`, but the key and the round keys stay masked. The counter blocks are public and only
+// masked with zeros, and the keystream is unmasked as it is XORed into the buffer.
+#[derive(Clone)]
+pub struct MaskedCtr {
+    cipher: MaskedGift128<BinaryMask<u8>>,
+    counter: Counter,
+    stream: Stream,
+}
+
+impl MaskedCtr {
+    #[must_use]
+    pub fn new(
+        key: &MaskedKey,
+        initial_block: &Block,
+        counter_size: usize,
+        byte_order: ByteOrder,
+    ) -> Self {
+        let counter = Counter::new(initial_block, counter_size, byte_order);
+        MaskedCtr {
+            cipher: MaskedGift128::new(key),
+            stream: Stream::new(&counter),
+            counter,
+        }
+    }
+
+    // The fresh randomness of the masked rounds is drawn from `rng`.
+    pub fn apply_keystream<R: Rng>(&mut self, buffer: &mut [u8], rng: &mut R) {
+        if self.try_apply_keystream(buffer, rng).is_err() {
+            panic!("keystream is exhausted");
+        }
+    }
+
+    pub fn try_apply_keystream<R: Rng>(
+        &mut self,
+        buffer: &mut [u8],
+        rng: &mut R,
+    ) -> Result<(), KeystreamExhausted> {
+        let (cipher, counter) = (&self.cipher, &self.counter);
+        self.stream.apply(buffer, |index, keystream| {
+            for (i, block) in keystream.iter_mut().enumerate() {
+                let counter_block = mask_block(&counter.block(index + i as u64), &[0; BLOCK_SIZE]);
+                *block = unmask_block(&cipher.encrypt_block(&counter_block, rng));
+            }
+        })
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.stream.position
+    }
+
+    #[inline]
+    pub fn seek(&mut self, position: u64) {
+        if self.try_seek(position).is_err() {
+            panic!("position is past the end of the keystream");
+        }
+    }
+
+    #[inline]
+    pub fn try_seek(&mut self, position: u64) -> Result<(), KeystreamExhausted> {
+        self.stream.seek(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ctr::{ByteOrder, Counter, Ctr, KeystreamExhausted, MaskedCtr};
+    use crate::gift128::key_schedule::mask_key;
+    use crate::gift128::tests::KEY;
+    use crate::gift128::{encrypt, Block};
+    use crate::random::XorShiftRng;
+
+    const INITIAL_BLOCK: Block = [
+        0xe3, 0x9c, 0x14, 0x1f, 0xa5, 0x7d, 0xba, 0x43, 0xf0, 0x8a, 0x85, 0xb6, 0xff, 0xff, 0xff,
+        0xfe,
+    ];
+
+    // Encrypts the counter blocks one by one with `encrypt`.
+    fn expected(message: &[u8], counter: &Counter) -> [u8; 300] {
+        let mut output = [0; 300];
+        for (i, (chunk, output)) in message.chunks(16).zip(output.chunks_mut(16)).enumerate() {
+            let mut keystream = [0; 16];
+            encrypt(&counter.block(i as u64), &KEY, &mut keystream);
+            for ((output, byte), key) in output.iter_mut().zip(chunk).zip(keystream) {
+                *output = byte ^ key;
+            }
+        }
+        output
+    }
+
+    fn message() -> [u8; 300] {
+        let mut message = [0; 300];
+        for (i, byte) in message.iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        message
+    }
+
+    #[test]
+    fn test_counter() {
+        let counter = Counter::new(&INITIAL_BLOCK, 4, ByteOrder::BigEndian);
+        assert_eq!(counter.block(0), INITIAL_BLOCK);
+        assert_eq!(counter.block(1)[12..], [0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(counter.block(3)[12..], [0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(counter.block(3)[..12], INITIAL_BLOCK[..12]);
+
+        let counter = Counter::new(&INITIAL_BLOCK, 2, ByteOrder::LittleEndian);
+        assert_eq!(counter.block(1)[..2], [0xe4, 0x9c]);
+        assert_eq!(counter.block(0x6400)[..2], [0xe3, 0x00]);
+        assert_eq!(counter.block(0x6400)[2..], INITIAL_BLOCK[2..]);
+
+        let counter = Counter::new(&[0xff; 16], 16, ByteOrder::BigEndian);
+        assert_eq!(counter.block(1), [0; 16]);
+
+        assert_eq!(
+            Counter::new(&INITIAL_BLOCK, 1, ByteOrder::BigEndian).end(),
+            256 * 16
+        );
+        assert_eq!(
+            Counter::new(&INITIAL_BLOCK, 7, ByteOrder::BigEndian).end(),
+            1 << 60
+        );
+        assert_eq!(
+            Counter::new(&INITIAL_BLOCK, 8, ByteOrder::BigEndian).end(),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_counter_size() {
+        let _ = Counter::new(&INITIAL_BLOCK, 17, ByteOrder::BigEndian);
+    }
+
+    #[test]
+    fn test_apply_keystream() {
+        let message = message();
+        for (size, byte_order) in [(4, ByteOrder::BigEndian), (8, ByteOrder::LittleEndian)] {
+            let counter = Counter::new(&INITIAL_BLOCK, size, byte_order);
+            let expected = expected(&message, &counter);
+
+            let mut buffer = message;
+            let mut ctr = Ctr::new(&KEY, &INITIAL_BLOCK, size, byte_order);
+            ctr.apply_keystream(&mut buffer);
+            assert_eq!(buffer, expected);
+            assert_eq!(ctr.position(), 300);
+
+            ctr.seek(0);
+            ctr.apply_keystream(&mut buffer);
+            assert_eq!(buffer, message);
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        let message = message();
+        let counter = Counter::new(&INITIAL_BLOCK, 4, ByteOrder::BigEndian);
+        let expected = expected(&message, &counter);
+
+        for piece in [1, 5, 16, 17, 70, 129] {
+            let mut buffer = message;
+            let mut ctr = Ctr::new(&KEY, &INITIAL_BLOCK, 4, ByteOrder::BigEndian);
+            for chunk in buffer.chunks_mut(piece) {
+                ctr.apply_keystream(chunk);
+            }
+            assert_eq!(buffer, expected);
+        }
+    }
+
+    #[test]
+    fn test_seek() {
+        let message = message();
+        let counter = Counter::new(&INITIAL_BLOCK, 4, ByteOrder::BigEndian);
+        let expected = expected(&message, &counter);
+
+        let mut ctr = Ctr::new(&KEY, &INITIAL_BLOCK, 4, ByteOrder::BigEndian);
+        for position in [0, 7, 16, 100, 250, 299] {
+            let mut buffer = message;
+            ctr.seek(position as u64);
+            ctr.apply_keystream(&mut buffer[position..]);
+            assert_eq!(buffer[position..], expected[position..]);
+        }
+    }
+
+    #[test]
+    fn test_exhausted() {
+        // A one-byte counter starting at 0xfe wraps around after two blocks and runs out after 256.
+        let mut buffer = [0; 256 * 16];
+        let mut ctr = Ctr::new(&KEY, &INITIAL_BLOCK, 1, ByteOrder::BigEndian);
+        ctr.apply_keystream(&mut buffer[..100]);
+        ctr.apply_keystream(&mut buffer[100..]);
+        assert_eq!(
+            ctr.try_apply_keystream(&mut [0; 1]),
+            Err(KeystreamExhausted)
+        );
+        assert_eq!(ctr.try_apply_keystream(&mut []), Ok(()));
+        assert_eq!(ctr.position(), 256 * 16);
+
+        assert_eq!(ctr.try_seek(256 * 16 + 1), Err(KeystreamExhausted));
+        ctr.seek(256 * 16 - 1);
+        let mut last = [0; 2];
+        assert_eq!(ctr.try_apply_keystream(&mut last), Err(KeystreamExhausted));
+        assert_eq!(last, [0; 2]);
+        ctr.apply_keystream(&mut last[..1]);
+        assert_eq!(last[0], buffer[256 * 16 - 1]);
+
+        // The position cannot overflow.
+        let mut ctr = Ctr::new(&KEY, &INITIAL_BLOCK, 16, ByteOrder::BigEndian);
+        ctr.seek(u64::MAX - 3);
+        assert_eq!(
+            ctr.try_apply_keystream(&mut [0; 8]),
+            Err(KeystreamExhausted)
+        );
+        assert_eq!(ctr.position(), u64::MAX - 3);
+
+        let masked_key = mask_key(&KEY, &[0x5a; 16]);
+        let mut ctr = MaskedCtr::new(&masked_key, &INITIAL_BLOCK, 1, ByteOrder::BigEndian);
+        let mut rng = XorShiftRng::new(1);
+        ctr.seek(255 * 16);
+        let mut tail = [0; 17];
+        assert_eq!(
+            ctr.try_apply_keystream(&mut tail, &mut rng),
+            Err(KeystreamExhausted)
+        );
+        ctr.apply_keystream(&mut tail[..16], &mut rng);
+        assert_eq!(tail[..16], buffer[255 * 16..]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apply_past_end() {
+        let mut ctr = Ctr::new(&KEY, &INITIAL_BLOCK, 1, ByteOrder::BigEndian);
+        ctr.seek(256 * 16 - 8);
+        ctr.apply_keystream(&mut [0; 9]);
+    }
+
+    #[test]
+    fn test_masked_apply_keystream() {
+        let message = message();
+        let counter = Counter::new(&INITIAL_BLOCK, 4, ByteOrder::BigEndian);
+        let expected = expected(&message, &counter);
+
+        let masked_key = mask_key(&KEY, &[0x5a; 16]);
+        let mut buffer = message;
+        let mut ctr = MaskedCtr::new(&masked_key, &INITIAL_BLOCK, 4, ByteOrder::BigEndian);
+        let mut rng = XorShiftRng::new(1);
+        for chunk in buffer.chunks_mut(23) {
+            ctr.apply_keystream(chunk, &mut rng);
+        }
+        assert_eq!(buffer, expected);
+
+        ctr.seek(40);
+        ctr.apply_keystream(&mut buffer[40..], &mut rng);
+        assert_eq!(buffer[40..], message[40..]);
+    }
+}
@@ -0,0 +1,70 @@
+use cipher::consts::U16;
+use cipher::{impl_simple_block_encdec, BlockCipher, KeyInit, KeySizeUser};
+
+use crate::gift128::{Block, Gift128};
+
+impl KeySizeUser for Gift128 {
+    type KeySize = U16;
+}
+
+impl KeyInit for Gift128 {
+    #[inline]
+    fn new(key: &cipher::Key<Self>) -> Self {
+        Gift128::new(&(*key).into())
+    }
+}
+
+impl BlockCipher for Gift128 {}
+
+impl_simple_block_encdec!(
+    Gift128, U16, cipher, block,
+    encrypt: {
+        let plaintext: Block = (*block.get_in()).into();
+        block.get_out().copy_from_slice(&cipher.encrypt_block(&plaintext));
+    }
+    decrypt: {
+        let ciphertext: Block = (*block.get_in()).into();
+        block.get_out().copy_from_slice(&cipher.decrypt_block(&ciphertext));
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+
+    use crate::gift128::tests::{CIPHERTEXT, KEY, PLAINTEXT};
+    use crate::gift128::{encrypt, Gift128, BLOCK_SIZE};
+
+    #[test]
+    fn test_encrypt_decrypt_block() {
+        let cipher = <Gift128 as KeyInit>::new(&KEY.into());
+
+        let mut block = PLAINTEXT.into();
+        BlockEncrypt::encrypt_block(&cipher, &mut block);
+        assert_eq!(block.as_slice(), CIPHERTEXT);
+
+        BlockDecrypt::decrypt_block(&cipher, &mut block);
+        assert_eq!(block.as_slice(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_encrypt_blocks() {
+        let cipher = <Gift128 as KeyInit>::new(&KEY.into());
+
+        let mut plaintext = [0; 3 * BLOCK_SIZE];
+        for (i, byte) in plaintext.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let mut expected = [0; 3 * BLOCK_SIZE];
+        encrypt(&plaintext, &KEY, &mut expected);
+
+        let mut blocks = [Default::default(); 3];
+        for (block, chunk) in blocks.iter_mut().zip(plaintext.chunks(BLOCK_SIZE)) {
+            *block = *cipher::Block::<Gift128>::from_slice(chunk);
+        }
+        cipher.encrypt_blocks(&mut blocks);
+        for (block, chunk) in blocks.iter().zip(expected.chunks(BLOCK_SIZE)) {
+            assert_eq!(block.as_slice(), chunk);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use core::array;
+use core::ops::{BitAnd, BitXor, BitXorAssign, Not, Shl, Shr};
+
+use crate::gift128::sbox::Gates;
+use crate::gift128::shares::Shares;
+use crate::gift128::traits::{MaskedByte, Refresh, Rotate, SwapBytes, Unmask};
+use crate::random::{Random, Rng};
+
+// Boolean masking with N shares like `Shares`, but multiplied with the domain-oriented masking of
+// Groß, Mangard and Korak ("Domain-Oriented Masking: Compact Masked Hardware Implementations with
+// Arbitrary Protection Order", 2016) in its independent variant. Everything but the
+// multiplication is shared with `Shares`.
+#[derive(Copy, Clone, Debug)]
+pub struct DomShares<T, const N: usize>(pub [T; N]);
+
+impl<T: BitXor<Output = T> + Copy, const N: usize> DomShares<T, N> {
+    pub fn make_shares(value: T, masks: &[T]) -> Self {
+        Shares::make_shares(value, masks).into()
+    }
+
+    #[inline]
+    pub fn recover_shares(self) -> T {
+        Shares::from(self).recover_shares()
+    }
+}
+
+impl<T, const N: usize> From<Shares<T, N>> for DomShares<T, N> {
+    #[inline]
+    fn from(x: Shares<T, N>) -> Self {
+        DomShares(x.0)
+    }
+}
+
+impl<T, const N: usize> From<DomShares<T, N>> for Shares<T, N> {
+    #[inline]
+    fn from(x: DomShares<T, N>) -> Self {
+        Shares(x.0)
+    }
+}
+
+// DOM-independent multiplication, which uses N(N - 1)/2 random words. Every product of shares
+// from two different domains is blinded before it is added to a domain, which in hardware is
+// done in a register stage of its own.
+impl<T, const N: usize> Gates for DomShares<T, N>
+where
+    T: BitAnd<Output = T> + BitXor<Output = T> + Not<Output = T> + Copy + Random,
+{
+    #[inline]
+    fn and<R: Rng>(self, rhs: Self, rng: &mut R) -> Self {
+        let (a, b) = (self.0, rhs.0);
+        let mut c = array::from_fn(|i| a[i] & b[i]);
+        for i in 0..N {
+            for j in i + 1..N {
+                let z = T::random(rng);
+                c[i] = c[i] ^ ((a[i] & b[j]) ^ z);
+                c[j] = c[j] ^ ((a[j] & b[i]) ^ z);
+            }
+        }
+        DomShares(c)
+    }
+
+    #[inline]
+    fn or<R: Rng>(self, rhs: Self, rng: &mut R) -> Self {
+        !(!self).and(!rhs, rng)
+    }
+}
+
+impl<T: BitAnd<Output = T> + Copy, const N: usize> BitAnd<T> for DomShares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: T) -> Self::Output {
+        (Shares::from(self) & rhs).into()
+    }
+}
+
+impl<T: Not<Output = T> + Copy, const N: usize> Not for DomShares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn not(self) -> Self::Output {
+        (!Shares::from(self)).into()
+    }
+}
+
+impl<T: Shl<R, Output = T> + Copy, R: Copy, const N: usize> Shl<R> for DomShares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn shl(self, rhs: R) -> Self::Output {
+        (Shares::from(self) << rhs).into()
+    }
+}
+
+impl<T: Shr<R, Output = T> + Copy, R: Copy, const N: usize> Shr<R> for DomShares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn shr(self, rhs: R) -> Self::Output {
+        (Shares::from(self) >> rhs).into()
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy, const N: usize> BitXor for DomShares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        (Shares::from(self) ^ Shares::from(rhs)).into()
+    }
+}
+
+impl<T: BitXorAssign + Copy, const N: usize> BitXorAssign for DomShares<T, N> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        for (share, other) in self.0.iter_mut().zip(rhs.0) {
+            *share ^= other;
+        }
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy, const N: usize> BitXor<T> for DomShares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: T) -> Self::Output {
+        (Shares::from(self) ^ rhs).into()
+    }
+}
+
+impl<T: BitXorAssign, const N: usize> BitXorAssign<T> for DomShares<T, N> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: T) {
+        self.0[0] ^= rhs;
+    }
+}
+
+impl<T: Rotate + Copy, const N: usize> Rotate for DomShares<T, N> {
+    #[inline]
+    fn rotate_right(self, rhs: u32) -> Self {
+        Shares::from(self).rotate_right(rhs).into()
+    }
+}
+
+impl<T: SwapBytes + Copy, const N: usize> SwapBytes for DomShares<T, N> {
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        Shares::from(self).swap_bytes().into()
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy + Random, const N: usize> Refresh for DomShares<T, N> {
+    #[inline]
+    fn refresh<R: Rng>(self, rng: &mut R) -> Self {
+        Shares::from(self).refresh(rng).into()
+    }
+}
+
+impl<const N: usize> From<DomShares<u32, N>> for DomShares<u8, N> {
+    #[inline]
+    fn from(x: DomShares<u32, N>) -> Self {
+        DomShares(x.0.map(|share| share as u8))
+    }
+}
+
+impl<const N: usize> From<DomShares<u8, N>> for DomShares<u32, N> {
+    #[inline]
+    fn from(x: DomShares<u8, N>) -> Self {
+        DomShares(x.0.map(|share| share as u32))
+    }
+}
+
+impl<T: Default + Copy, const N: usize> Default for DomShares<T, N> {
+    #[inline]
+    fn default() -> Self {
+        DomShares([Default::default(); N])
+    }
+}
+
+impl<const N: usize> Unmask for DomShares<u32, N> {
+    #[inline]
+    fn unmask(self) -> u32 {
+        self.recover_shares()
+    }
+}
+
+impl<const N: usize> MaskedByte for DomShares<u8, N> {
+    type Word = DomShares<u32, N>;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::dom::DomShares;
+    use crate::gift128::sbox::{inv_sbox, sbox, Gates};
+    use crate::gift128::State;
+    use crate::random::{NoRng, XorShiftRng};
+
+    const VALUE1: u32 = 0xD576370D;
+    const MASKS1: [u32; 3] = [0xB751F5EF, 0x4a6e09c3, 0x93d1a27f];
+    const VALUE2: u32 = 0x6CC92B7B;
+    const MASKS2: [u32; 3] = [0xC2E993A4, 0x17b3e5d8, 0x5f0c84a6];
+
+    fn and_or<const N: usize>() {
+        let a = DomShares::<u32, N>::make_shares(VALUE1, &MASKS1[..N - 1]);
+        let b = DomShares::<u32, N>::make_shares(VALUE2, &MASKS2[..N - 1]);
+        let mut rng = XorShiftRng::new(1);
+        assert_eq!(a.and(b, &mut rng).recover_shares(), VALUE1 & VALUE2);
+        assert_eq!(a.or(b, &mut rng).recover_shares(), VALUE1 | VALUE2);
+    }
+
+    #[test]
+    fn test_and_or() {
+        and_or::<2>();
+        and_or::<3>();
+        and_or::<4>();
+    }
+
+    #[test]
+    fn test_sbox() {
+        let initial_state = State(0xd576370du32, 0x8b52c000u32, 0x2bafc0ccu32, 0xa3487987u32);
+        let state = initial_state.map(|x| DomShares::<u32, 3>::make_shares(x, &MASKS1[..2]));
+        let mut rng = XorShiftRng::new(1);
+        assert_eq!(
+            sbox(state, &mut rng).map(DomShares::recover_shares),
+            sbox(initial_state, &mut NoRng)
+        );
+        assert_eq!(
+            inv_sbox(state, &mut rng).map(DomShares::recover_shares),
+            inv_sbox(initial_state, &mut NoRng)
+        );
+    }
+}
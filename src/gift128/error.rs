@@ -0,0 +1,77 @@
+use crate::gift128::fault::FaultDetected;
+use crate::gift128::BLOCK_SIZE;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gift128Error {
+    // The input is not a whole number of blocks.
+    InvalidLength,
+    // The output is not as long as the input.
+    LengthMismatch,
+}
+
+// The error of the `_protected` functions, which check the lengths before any block is processed
+// and the result of every block afterwards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProtectedError {
+    Length(Gift128Error),
+    Fault(FaultDetected),
+}
+
+impl From<Gift128Error> for ProtectedError {
+    fn from(error: Gift128Error) -> Self {
+        ProtectedError::Length(error)
+    }
+}
+
+impl From<FaultDetected> for ProtectedError {
+    fn from(error: FaultDetected) -> Self {
+        ProtectedError::Fault(error)
+    }
+}
+
+// Checks the lengths of an input and an output buffer before any block is processed.
+#[inline]
+pub(crate) fn check_lengths<B>(input: &[B], output: &[B]) -> Result<(), Gift128Error> {
+    if !input.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(Gift128Error::InvalidLength);
+    }
+    if input.len() != output.len() {
+        return Err(Gift128Error::LengthMismatch);
+    }
+    Ok(())
+}
+
+// The block loop of `try_encrypt`, `try_decrypt` and their variants, which checks the lengths and
+// writes `f` of every input block to the output. The output is left untouched on an error.
+#[inline]
+pub(crate) fn try_map_blocks<B: Copy>(
+    input: &[B],
+    output: &mut [B],
+    mut f: impl FnMut(&[B; BLOCK_SIZE]) -> [B; BLOCK_SIZE],
+) -> Result<(), Gift128Error> {
+    check_lengths(input, output)?;
+    let ((blocks, []), (outputs, [])) = (input.as_chunks(), output.as_chunks_mut()) else {
+        return Err(Gift128Error::InvalidLength);
+    };
+    for (block, output) in blocks.iter().zip(outputs) {
+        *output = f(block);
+    }
+    Ok(())
+}
+
+// Panics on a length error of `try_map_blocks`, naming the buffers as `encrypt` and `decrypt` do.
+pub(crate) fn unwrap_encrypt(result: Result<(), Gift128Error>) {
+    match result {
+        Ok(()) => {}
+        Err(Gift128Error::InvalidLength) => panic!("plaintext size is not a multiple of 16"),
+        Err(Gift128Error::LengthMismatch) => panic!("ciphertext size differs from plaintext size"),
+    }
+}
+
+pub(crate) fn unwrap_decrypt(result: Result<(), Gift128Error>) {
+    match result {
+        Ok(()) => {}
+        Err(Gift128Error::InvalidLength) => panic!("ciphertext size is not a multiple of 16"),
+        Err(Gift128Error::LengthMismatch) => panic!("plaintext size differs from ciphertext size"),
+    }
+}
@@ -0,0 +1,209 @@
+use crate::gift128::hooks::RoundHook;
+use crate::gift128::key_schedule::RoundKeys;
+use crate::gift128::rounds::{inv_rounds_with_hook, rounds_with_hook, RoundTraits};
+use crate::gift128::traits::Unmask;
+use crate::gift128::State;
+use crate::random::Rng;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FaultDetected;
+
+// The OR of the differences of all the slices, which is only zero if the states are equal. For
+// masked states, only the difference is unmasked.
+#[must_use]
+#[inline]
+pub(crate) fn difference<T: RoundTraits + Unmask>(a: State<T>, b: State<T>) -> u32 {
+    let (State(mut a0, mut a1, mut a2, mut a3), State(b0, b1, b2, b3)) = (a, b);
+    a0 ^= b0;
+    a1 ^= b1;
+    a2 ^= b2;
+    a3 ^= b3;
+    a0.unmask() | a1.unmask() | a2.unmask() | a3.unmask()
+}
+
+// Runs the rounds and then inverts them again, so that a fault in either direction changes the
+// difference to the input. The round keys are shared by both, so faults in the key schedule are
+// not detected.
+#[must_use]
+#[inline]
+pub(crate) fn verified_rounds_with_hook<T, H, I, R>(
+    state: State<T>,
+    round_keys: &RoundKeys<T>,
+    hook: &mut H,
+    inv_hook: &mut I,
+    rng: &mut R,
+) -> (State<T>, u32)
+where
+    T: RoundTraits + Unmask,
+    H: RoundHook<T>,
+    I: RoundHook<T>,
+    R: Rng,
+{
+    let result = rounds_with_hook(state, round_keys, hook, rng);
+    let check = inv_rounds_with_hook(result, round_keys, inv_hook, rng);
+    (result, difference(state, check))
+}
+
+// Like `verified_rounds_with_hook`, but `hook` is called by the inverse rounds and `inv_hook` by
+// the rounds that check them.
+#[must_use]
+#[inline]
+pub(crate) fn inv_verified_rounds_with_hook<T, H, I, R>(
+    state: State<T>,
+    round_keys: &RoundKeys<T>,
+    hook: &mut H,
+    inv_hook: &mut I,
+    rng: &mut R,
+) -> (State<T>, u32)
+where
+    T: RoundTraits + Unmask,
+    H: RoundHook<T>,
+    I: RoundHook<T>,
+    R: Rng,
+{
+    let result = inv_rounds_with_hook(state, round_keys, hook, rng);
+    let check = rounds_with_hook(result, round_keys, inv_hook, rng);
+    (result, difference(state, check))
+}
+
+// Only branches once on the accumulated difference, so the comparison itself takes the same time
+// for all inputs. The output is zeroed so that no faulty ciphertext is released.
+#[inline]
+pub(crate) fn check_difference<B: Copy>(
+    difference: u32,
+    output: &mut [B],
+    zero: B,
+) -> Result<(), FaultDetected> {
+    if difference != 0 {
+        output.fill(zero);
+        return Err(FaultDetected);
+    }
+    Ok(())
+}
+
+// Flips a bit of the third slice after the S-box layer of a round, like a single bit fault.
+#[cfg(test)]
+pub(crate) struct BitFlip {
+    pub(crate) round: usize,
+}
+
+#[cfg(test)]
+impl<T: core::ops::BitXorAssign<u32>> RoundHook<T> for BitFlip {
+    fn after_sbox<R: Rng>(&mut self, round: usize, slices: &mut [T; 4], _rng: &mut R) {
+        if round == self.round {
+            slices[2] ^= 1 << 17;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::fault::{
+        check_difference, inv_verified_rounds_with_hook, verified_rounds_with_hook, BitFlip,
+        FaultDetected,
+    };
+    use crate::gift128::hooks::NoHook;
+    use crate::gift128::key_schedule::{
+        mask_key_with_rng, precompute_masked_round_keys, precompute_round_keys,
+    };
+    use crate::gift128::mask_block_with_rng;
+    use crate::gift128::packing::{masked_pack, pack};
+    use crate::gift128::rounds::{inv_rounds, rounds};
+    use crate::gift128::tests::{KEY, PLAINTEXT};
+    use crate::random::{NoRng, XorShiftRng};
+
+    #[test]
+    fn test_verified_rounds() {
+        let round_keys = precompute_round_keys(&KEY);
+        let state = pack(&PLAINTEXT);
+        let (result, difference) =
+            verified_rounds_with_hook(state, &round_keys, &mut NoHook, &mut NoHook, &mut NoRng);
+        assert_eq!(result, rounds(state, &round_keys));
+        assert_eq!(difference, 0);
+
+        for round in [0, 17, 39] {
+            let mut hook = BitFlip { round };
+            let (_, difference) =
+                verified_rounds_with_hook(state, &round_keys, &mut hook, &mut NoHook, &mut NoRng);
+            assert_ne!(difference, 0);
+            let mut inv_hook = BitFlip { round };
+            let (_, difference) = verified_rounds_with_hook(
+                state,
+                &round_keys,
+                &mut NoHook,
+                &mut inv_hook,
+                &mut NoRng,
+            );
+            assert_ne!(difference, 0);
+        }
+    }
+
+    #[test]
+    fn test_inv_verified_rounds() {
+        let round_keys = precompute_round_keys(&KEY);
+        let state = pack(&PLAINTEXT);
+        let (result, difference) =
+            inv_verified_rounds_with_hook(state, &round_keys, &mut NoHook, &mut NoHook, &mut NoRng);
+        assert_eq!(result, inv_rounds(state, &round_keys));
+        assert_eq!(difference, 0);
+
+        for round in [0, 17, 39] {
+            let mut hook = BitFlip { round };
+            let (_, difference) = inv_verified_rounds_with_hook(
+                state,
+                &round_keys,
+                &mut hook,
+                &mut NoHook,
+                &mut NoRng,
+            );
+            assert_ne!(difference, 0);
+            let mut inv_hook = BitFlip { round };
+            let (_, difference) = inv_verified_rounds_with_hook(
+                state,
+                &round_keys,
+                &mut NoHook,
+                &mut inv_hook,
+                &mut NoRng,
+            );
+            assert_ne!(difference, 0);
+        }
+    }
+
+    #[test]
+    fn test_masked_verified_rounds() {
+        let mut rng = XorShiftRng::new(0x85ebca6b);
+        let round_keys = precompute_masked_round_keys(&mask_key_with_rng(&KEY, &mut rng));
+        let state = masked_pack(&mask_block_with_rng(&PLAINTEXT, &mut rng));
+        let (_, difference) =
+            verified_rounds_with_hook(state, &round_keys, &mut NoHook, &mut NoHook, &mut rng);
+        assert_eq!(difference, 0);
+        let (_, difference) =
+            inv_verified_rounds_with_hook(state, &round_keys, &mut NoHook, &mut NoHook, &mut rng);
+        assert_eq!(difference, 0);
+
+        for round in [0, 17, 39] {
+            let mut hook = BitFlip { round };
+            let (_, difference) =
+                verified_rounds_with_hook(state, &round_keys, &mut hook, &mut NoHook, &mut rng);
+            assert_ne!(difference, 0);
+            let mut inv_hook = BitFlip { round };
+            let (_, difference) = inv_verified_rounds_with_hook(
+                state,
+                &round_keys,
+                &mut NoHook,
+                &mut inv_hook,
+                &mut rng,
+            );
+            assert_ne!(difference, 0);
+        }
+    }
+
+    #[test]
+    fn test_check_difference() {
+        let mut output = [0xa5; 32];
+        assert_eq!(check_difference(0, &mut output, 0), Ok(()));
+        assert_eq!(output, [0xa5; 32]);
+        assert_eq!(check_difference(0x100, &mut output, 0), Err(FaultDetected));
+        assert_eq!(output, [0; 32]);
+    }
+}
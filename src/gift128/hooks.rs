@@ -0,0 +1,129 @@
+use crate::gift128::traits::Refresh;
+use crate::random::Rng;
+
+// Callbacks into the round function, e.g. to re-randomize the shares of a masked state with
+// randomness from the generator of the rounds. The slices are passed in the order 0 to 3 in which
+// the S-box consumes them. Rounds are numbered from 0, also when decrypting.
+pub trait RoundHook<T> {
+    // Called right before the S-box layer of a round, so that its operations can be told apart
+    // from the ones of the linear layer.
+    #[inline(always)]
+    fn before_sbox(&mut self, _round: usize) {}
+
+    #[inline(always)]
+    fn after_sbox<R: Rng>(&mut self, _round: usize, _slices: &mut [T; 4], _rng: &mut R) {}
+
+    // Called whenever the state is back in its classical representation, i.e. after rounds 4, 9,
+    // .. when encrypting and before them when decrypting.
+    #[inline(always)]
+    fn after_quintuple_round<R: Rng>(&mut self, _round: usize, _slices: &mut [T; 4], _rng: &mut R) {
+    }
+}
+
+pub struct NoHook;
+
+impl<T> RoundHook<T> for NoHook {}
+
+pub struct RefreshPerSbox;
+
+impl<T: Refresh + Copy> RoundHook<T> for RefreshPerSbox {
+    #[inline(always)]
+    fn after_sbox<R: Rng>(&mut self, _round: usize, slices: &mut [T; 4], rng: &mut R) {
+        *slices = slices.map(|slice| slice.refresh(rng));
+    }
+}
+
+pub struct RefreshPerQuintupleRound;
+
+impl<T: Refresh + Copy> RoundHook<T> for RefreshPerQuintupleRound {
+    #[inline(always)]
+    fn after_quintuple_round<R: Rng>(&mut self, _round: usize, slices: &mut [T; 4], rng: &mut R) {
+        *slices = slices.map(|slice| slice.refresh(rng));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::hooks::{NoHook, RoundHook};
+    use crate::gift128::key_schedule::{mask_key, precompute_masked_round_keys};
+    use crate::gift128::masking::BinaryMask;
+    use crate::gift128::rounds::{inv_rounds_with_hook, rounds_with_hook, ROUNDS};
+    use crate::gift128::tests::KEY;
+    use crate::gift128::State;
+    use crate::random::{Rng, XorShiftRng};
+
+    struct Counter {
+        sbox_rounds: [usize; ROUNDS],
+        quintuple_rounds: [usize; ROUNDS / 5],
+    }
+
+    impl Counter {
+        fn new() -> Self {
+            Counter {
+                sbox_rounds: [0; ROUNDS],
+                quintuple_rounds: [0; ROUNDS / 5],
+            }
+        }
+    }
+
+    impl<T> RoundHook<T> for Counter {
+        fn after_sbox<R: Rng>(&mut self, round: usize, _slices: &mut [T; 4], _rng: &mut R) {
+            self.sbox_rounds[round] += 1;
+        }
+
+        fn after_quintuple_round<R: Rng>(
+            &mut self,
+            round: usize,
+            _slices: &mut [T; 4],
+            _rng: &mut R,
+        ) {
+            assert_eq!(round % 5, 4);
+            self.quintuple_rounds[round / 5] += 1;
+        }
+    }
+
+    #[test]
+    fn test_hook_calls() {
+        let round_keys = precompute_masked_round_keys(&mask_key(&KEY, &[0x5a; 16]));
+        let state = State(0, 0, 0, 0u32).make_shares((1, 2, 3, 4));
+
+        let mut rng = XorShiftRng::new(1);
+        let mut counter = Counter::new();
+        let state = rounds_with_hook(state, &round_keys, &mut counter, &mut rng);
+        assert_eq!(counter.sbox_rounds, [1; ROUNDS]);
+        assert_eq!(counter.quintuple_rounds, [1; ROUNDS / 5]);
+
+        let mut counter = Counter::new();
+        let state = inv_rounds_with_hook(state, &round_keys, &mut counter, &mut rng);
+        assert_eq!(counter.sbox_rounds, [1; ROUNDS]);
+        assert_eq!(counter.quintuple_rounds, [1; ROUNDS / 5]);
+        assert_eq!(state.recover_shares(), State(0, 0, 0, 0));
+    }
+
+    struct FlipBit;
+
+    impl RoundHook<BinaryMask<u32>> for FlipBit {
+        fn after_sbox<R: Rng>(
+            &mut self,
+            round: usize,
+            slices: &mut [BinaryMask<u32>; 4],
+            _rng: &mut R,
+        ) {
+            if round == 20 {
+                slices[2].0 ^= 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_hook_modifies_state() {
+        let round_keys = precompute_masked_round_keys(&mask_key(&KEY, &[0x5a; 16]));
+        let state =
+            State(0x01234567, 0x89abcdef, 0xdeadbeef, 0x0badf00du32).make_shares((1, 2, 3, 4));
+
+        let mut rng = XorShiftRng::new(1);
+        let faulty = rounds_with_hook(state, &round_keys, &mut FlipBit, &mut rng);
+        let correct = rounds_with_hook(state, &round_keys, &mut NoHook, &mut rng);
+        assert_ne!(faulty.recover_shares(), correct.recover_shares());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::gift128::fault::difference;
+use crate::gift128::hooks::{NoHook, RoundHook};
+use crate::gift128::key_schedule::RoundKeys;
+use crate::gift128::rounds::{inv_quintuple_round, quintuple_round, ROUNDS, ROUND_CONSTANTS};
+use crate::gift128::State;
+use crate::random::{NoRng, Rng};
+
+const QUINTUPLE_ROUNDS: usize = ROUNDS / 5;
+const DUMMY_QUINTUPLE_ROUNDS: usize = 8;
+
+#[must_use]
+#[inline(always)]
+fn step<H: RoundHook<u32>>(
+    state: State<u32>,
+    round_keys: &RoundKeys<u32>,
+    quintuple: usize,
+    inverse: bool,
+    hook: &mut H,
+) -> State<u32> {
+    let i = if inverse {
+        5 * (QUINTUPLE_ROUNDS - 1 - quintuple)
+    } else {
+        5 * quintuple
+    };
+    let (round_keys, round_constants) =
+        (&round_keys[i * 2..i * 2 + 10], &ROUND_CONSTANTS[i..i + 5]);
+    if inverse {
+        inv_quintuple_round(state, round_keys, round_constants, i, hook, &mut NoRng)
+    } else {
+        quintuple_round(state, round_keys, round_constants, i, hook, &mut NoRng)
+    }
+}
+
+// Randomizes the real state unless it equals the redundant state, without branching on either.
+#[must_use]
+#[inline]
+fn infect<R: Rng>(real: State<u32>, redundant: State<u32>, rng: &mut R) -> State<u32> {
+    let difference = difference(real, redundant);
+    let mask = ((difference | difference.wrapping_neg()) >> 31).wrapping_neg();
+    let State(s0, s1, s2, s3) = real;
+    State(
+        s0 ^ (mask & rng.next_u32()),
+        s1 ^ (mask & rng.next_u32()),
+        s2 ^ (mask & rng.next_u32()),
+        s3 ^ (mask & rng.next_u32()),
+    )
+}
+
+// Computes every quintuple round on a real and a redundant state in random order, with dummy
+// quintuple rounds on a random state at random positions in between. The real state is infected
+// after every pair, so a fault in either computation randomizes the output instead of making it
+// usable for differential fault analysis. The hook is only called for the real computation.
+#[must_use]
+pub(crate) fn infective_rounds_with_hook<R: Rng, H: RoundHook<u32>>(
+    state: State<u32>,
+    round_keys: &RoundKeys<u32>,
+    inverse: bool,
+    rng: &mut R,
+    hook: &mut H,
+) -> State<u32> {
+    let (mut real, mut redundant) = (state, state);
+    let mut dummy = State(
+        rng.next_u32(),
+        rng.next_u32(),
+        rng.next_u32(),
+        rng.next_u32(),
+    );
+    let (mut real_done, mut redundant_done) = (0, 0);
+    let mut dummies = DUMMY_QUINTUPLE_ROUNDS;
+
+    loop {
+        let remaining = 2 * QUINTUPLE_ROUNDS - real_done - redundant_done + dummies;
+        if remaining == 0 {
+            break;
+        }
+
+        if (rng.next_u32() as usize) % remaining < dummies {
+            let quintuple = real_done.min(redundant_done).min(QUINTUPLE_ROUNDS - 1);
+            dummy = step(dummy, round_keys, quintuple, inverse, &mut NoHook);
+            dummies -= 1;
+            continue;
+        }
+
+        let real_turn = if real_done == redundant_done {
+            rng.next_u32() & 1 == 1
+        } else {
+            real_done < redundant_done
+        };
+        if real_turn {
+            real = step(real, round_keys, real_done, inverse, hook);
+            real_done += 1;
+        } else {
+            redundant = step(redundant, round_keys, redundant_done, inverse, &mut NoHook);
+            redundant_done += 1;
+        }
+
+        if real_done == redundant_done {
+            real = infect(real, redundant, rng);
+        }
+    }
+
+    real
+}
+
+#[must_use]
+pub(crate) fn infective_rounds<R: Rng>(
+    state: State<u32>,
+    round_keys: &RoundKeys<u32>,
+    inverse: bool,
+    rng: &mut R,
+) -> State<u32> {
+    infective_rounds_with_hook(state, round_keys, inverse, rng, &mut NoHook)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::hooks::{NoHook, RoundHook};
+    use crate::gift128::infective::{infective_rounds, infective_rounds_with_hook};
+    use crate::gift128::key_schedule::precompute_round_keys;
+    use crate::gift128::packing::pack;
+    use crate::gift128::rounds::{inv_rounds, rounds, rounds_with_hook};
+    use crate::gift128::tests::{KEY, PLAINTEXT};
+    use crate::random::{NoRng, Rng, XorShiftRng};
+
+    struct BitFlip {
+        round: usize,
+    }
+
+    impl RoundHook<u32> for BitFlip {
+        fn after_sbox<R: Rng>(&mut self, round: usize, slices: &mut [u32; 4], _rng: &mut R) {
+            if round == self.round {
+                slices[0] ^= 1 << 9;
+            }
+        }
+    }
+
+    #[test]
+    fn test_infective_rounds() {
+        let round_keys = precompute_round_keys(&KEY);
+        let state = pack(&PLAINTEXT);
+        for seed in 1..20 {
+            let mut rng = XorShiftRng::new(seed);
+            let ciphertext = infective_rounds(state, &round_keys, false, &mut rng);
+            assert_eq!(ciphertext, rounds(state, &round_keys));
+            let plaintext = infective_rounds(ciphertext, &round_keys, true, &mut rng);
+            assert_eq!(plaintext, state);
+            assert_eq!(plaintext, inv_rounds(ciphertext, &round_keys));
+        }
+    }
+
+    // A fault in the last round would give an attacker a single-bit difference before the final
+    // linear layer, but the infected output is unrelated to it.
+    #[test]
+    fn test_fault_infects() {
+        let round_keys = precompute_round_keys(&KEY);
+        let state = pack(&PLAINTEXT);
+        let correct = rounds(state, &round_keys);
+        let faulty = rounds_with_hook(state, &round_keys, &mut BitFlip { round: 39 }, &mut NoRng);
+
+        let mut rng = XorShiftRng::new(1);
+        let infected = infective_rounds_with_hook(
+            state,
+            &round_keys,
+            false,
+            &mut rng,
+            &mut BitFlip { round: 39 },
+        );
+        let other_infected = infective_rounds_with_hook(
+            state,
+            &round_keys,
+            false,
+            &mut rng,
+            &mut BitFlip { round: 39 },
+        );
+        assert_ne!(infected, correct);
+        assert_ne!(infected, faulty);
+        assert_ne!(infected, other_infected);
+
+        let uninfected =
+            infective_rounds_with_hook(state, &round_keys, false, &mut rng, &mut NoHook);
+        assert_eq!(uninfected, correct);
+    }
+}
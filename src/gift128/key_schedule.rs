@@ -1,9 +1,13 @@
-use core::ops::{BitAnd, BitOr, Shl, Shr};
+use core::ops::{BitAnd, BitXor, Shl, Shr};
 
+use crate::gift128::dom::DomShares;
 use crate::gift128::masking::BinaryMask;
 use crate::gift128::rounds::ROUNDS;
-use crate::gift128::traits::{Rotate, SwapBytes};
-use crate::gift128::{Key, KEY_SIZE};
+use crate::gift128::shares::{share_bytes, share_bytes_with_rng};
+use crate::gift128::threshold::{ti_share_bytes, ti_share_bytes_with_rng};
+use crate::gift128::traits::{MaskedByte, Rotate, SwapBytes};
+use crate::gift128::{DomKey, Key, SharedKey, TiKey, KEY_SIZE};
+use crate::random::Rng;
 use crate::swapmove::{swap_move_single, SwapMoveTraits};
 
 // TODO: use tuples
@@ -17,12 +21,12 @@ where
         + Shr<usize, Output = T>
         + Shl<usize, Output = T>
         + BitAnd<u32, Output = T>
-        + BitOr<Output = T>,
+        + BitXor<Output = T>,
 {
     ((round_key >> 12) & 0x0000000f)
-        | ((round_key & 0x00000fff) << 4)
-        | ((round_key >> 2) & 0x3fff0000)
-        | ((round_key & 0x00030000) << 14)
+        ^ ((round_key & 0x00000fff) << 4)
+        ^ ((round_key >> 2) & 0x3fff0000)
+        ^ ((round_key & 0x00030000) << 14)
 }
 
 #[must_use]
@@ -65,9 +69,9 @@ fn rearrange_round_key3<T: SwapMoveTraits>(mut round_key: T) -> T {
 #[inline]
 fn key_triple_update_0<T>(round_key: T) -> T
 where
-    T: Copy + BitAnd<u32, Output = T> + BitOr<Output = T> + Rotate,
+    T: Copy + BitAnd<u32, Output = T> + BitXor<Output = T> + Rotate,
 {
-    (round_key & 0x33333333).rotate_right(24) | (round_key & 0xcccccccc).rotate_right(16)
+    (round_key & 0x33333333).rotate_right(24) ^ (round_key & 0xcccccccc).rotate_right(16)
 }
 
 #[must_use]
@@ -78,12 +82,12 @@ where
         + Shr<usize, Output = T>
         + BitAnd<u32, Output = T>
         + Shl<usize, Output = T>
-        + BitOr<Output = T>,
+        + BitXor<Output = T>,
 {
     ((round_key >> 4) & 0x0f000f00)
-        | ((round_key & 0x0f000f00) << 4)
-        | ((round_key >> 6) & 0x00030003)
-        | ((round_key & 0x003f003f) << 2)
+        ^ ((round_key & 0x0f000f00) << 4)
+        ^ ((round_key >> 6) & 0x00030003)
+        ^ ((round_key & 0x003f003f) << 2)
 }
 
 #[must_use]
@@ -94,30 +98,30 @@ where
         + Shr<usize, Output = T>
         + BitAnd<u32, Output = T>
         + Shl<usize, Output = T>
-        + BitOr<Output = T>,
+        + BitXor<Output = T>,
 {
     ((round_key >> 6) & 0x03000300)
-        | ((round_key & 0x3f003f00) << 2)
-        | ((round_key >> 5) & 0x00070007)
-        | ((round_key & 0x001f001f) << 3)
+        ^ ((round_key & 0x3f003f00) << 2)
+        ^ ((round_key >> 5) & 0x00070007)
+        ^ ((round_key & 0x001f001f) << 3)
 }
 
 #[must_use]
 #[inline]
 fn key_double_update_2<T>(round_key: T) -> T
 where
-    T: Copy + BitAnd<u32, Output = T> + BitOr<Output = T> + Rotate,
+    T: Copy + BitAnd<u32, Output = T> + BitXor<Output = T> + Rotate,
 {
-    (round_key & 0xaaaaaaaa).rotate_right(24) | (round_key & 0x55555555).rotate_right(16)
+    (round_key & 0xaaaaaaaa).rotate_right(24) ^ (round_key & 0x55555555).rotate_right(16)
 }
 
 #[must_use]
 #[inline]
 fn key_triple_update_2<T>(round_key: T) -> T
 where
-    T: Copy + BitAnd<u32, Output = T> + BitOr<Output = T> + Rotate,
+    T: Copy + BitAnd<u32, Output = T> + BitXor<Output = T> + Rotate,
 {
-    (round_key & 0x55555555).rotate_right(24) | (round_key & 0xaaaaaaaa).rotate_right(20)
+    (round_key & 0x55555555).rotate_right(24) ^ (round_key & 0xaaaaaaaa).rotate_right(20)
 }
 
 #[must_use]
@@ -128,12 +132,12 @@ where
         + Shr<usize, Output = T>
         + BitAnd<u32, Output = T>
         + Shl<usize, Output = T>
-        + BitOr<Output = T>,
+        + BitXor<Output = T>,
 {
     ((round_key >> 2) & 0x03030303)
-        | ((round_key & 0x03030303) << 2)
-        | ((round_key >> 1) & 0x70707070)
-        | ((round_key & 0x10101010) << 3)
+        ^ ((round_key & 0x03030303) << 2)
+        ^ ((round_key >> 1) & 0x70707070)
+        ^ ((round_key & 0x10101010) << 3)
 }
 
 #[must_use]
@@ -144,14 +148,14 @@ where
         + Shr<usize, Output = T>
         + BitAnd<u32, Output = T>
         + Shl<usize, Output = T>
-        + BitOr<Output = T>,
+        + BitXor<Output = T>,
 {
     ((round_key >> 18) & 0x00003030)
-        | ((round_key & 0x01010101) << 3)
-        | ((round_key >> 14) & 0x0000c0c0)
-        | ((round_key & 0x0000e0e0) << 15)
-        | ((round_key >> 1) & 0x07070707)
-        | ((round_key & 0x00001010) << 19)
+        ^ ((round_key & 0x01010101) << 3)
+        ^ ((round_key >> 14) & 0x0000c0c0)
+        ^ ((round_key & 0x0000e0e0) << 15)
+        ^ ((round_key >> 1) & 0x07070707)
+        ^ ((round_key & 0x00001010) << 19)
 }
 
 #[must_use]
@@ -162,12 +166,12 @@ where
         + Shr<usize, Output = T>
         + BitAnd<u32, Output = T>
         + Shl<usize, Output = T>
-        + BitOr<Output = T>,
+        + BitXor<Output = T>,
 {
     ((round_key >> 4) & 0x0fff0000)
-        | ((round_key & 0x000f0000) << 12)
-        | ((round_key >> 8) & 0x000000ff)
-        | ((round_key & 0x000000ff) << 8)
+        ^ ((round_key & 0x000f0000) << 12)
+        ^ ((round_key >> 8) & 0x000000ff)
+        ^ ((round_key & 0x000000ff) << 8)
 }
 
 #[must_use]
@@ -178,17 +182,17 @@ where
         + Shr<usize, Output = T>
         + BitAnd<u32, Output = T>
         + Shl<usize, Output = T>
-        + BitOr<Output = T>,
+        + BitXor<Output = T>,
 {
     ((round_key >> 6) & 0x03ff0000)
-        | ((round_key & 0x003f0000) << 10)
-        | ((round_key >> 4) & 0x00000fff)
-        | ((round_key & 0x0000000f) << 12)
+        ^ ((round_key & 0x003f0000) << 10)
+        ^ ((round_key >> 4) & 0x00000fff)
+        ^ ((round_key & 0x0000000f) << 12)
 }
 
-fn fill_round_keys<T>(round_keys: &mut [T; ROUNDS * 2])
+pub(crate) fn fill_round_keys<T>(round_keys: &mut [T; ROUNDS * 2])
 where
-    T: SwapMoveTraits + BitOr<Output = T> + Rotate,
+    T: SwapMoveTraits + BitXor<Output = T> + Rotate,
 {
     for i in (0..16).step_by(2) {
         round_keys[i + 4] = round_keys[i + 1];
@@ -246,30 +250,61 @@ pub fn mask_key(key: &[u8; KEY_SIZE], masks: &[u8; KEY_SIZE]) -> [BinaryMask<u8>
 }
 
 #[must_use]
-pub fn precompute_masked_round_keys(
-    key: &[BinaryMask<u8>; KEY_SIZE],
-) -> RoundKeys<BinaryMask<u32>> {
-    let mut round_keys = [BinaryMask::make_shares(0, 0); ROUNDS * 2];
-    round_keys[0] = BinaryMask(
-        u32::from_le_bytes([key[12].0, key[13].0, key[14].0, key[15].0]),
-        u32::from_le_bytes([key[12].1, key[13].1, key[14].1, key[15].1]),
-    )
-    .swap_bytes();
-    round_keys[1] = BinaryMask(
-        u32::from_le_bytes([key[4].0, key[5].0, key[6].0, key[7].0]),
-        u32::from_le_bytes([key[4].1, key[5].1, key[6].1, key[7].1]),
-    )
-    .swap_bytes();
-    round_keys[2] = BinaryMask(
-        u32::from_le_bytes([key[8].0, key[9].0, key[10].0, key[11].0]),
-        u32::from_le_bytes([key[8].1, key[9].1, key[10].1, key[11].1]),
-    )
-    .swap_bytes();
-    round_keys[3] = BinaryMask(
-        u32::from_le_bytes([key[0].0, key[1].0, key[2].0, key[3].0]),
-        u32::from_le_bytes([key[0].1, key[1].1, key[2].1, key[3].1]),
-    )
-    .swap_bytes();
+pub fn mask_key_with_rng<R: Rng>(key: &Key, rng: &mut R) -> [BinaryMask<u8>; KEY_SIZE] {
+    let mut masks = [0; KEY_SIZE];
+    rng.fill_bytes(&mut masks);
+    mask_key(key, &masks)
+}
+
+#[must_use]
+pub fn share_key<const N: usize>(key: &Key, masks: &[Key]) -> SharedKey<N> {
+    share_bytes(key, masks)
+}
+
+#[must_use]
+pub fn share_key_with_rng<const N: usize, R: Rng>(key: &Key, rng: &mut R) -> SharedKey<N> {
+    share_bytes_with_rng(key, rng)
+}
+
+#[must_use]
+pub fn dom_share_key<const N: usize>(key: &Key, masks: &[Key]) -> DomKey<N> {
+    share_bytes(key, masks).map(DomShares::from)
+}
+
+#[must_use]
+pub fn dom_share_key_with_rng<const N: usize, R: Rng>(key: &Key, rng: &mut R) -> DomKey<N> {
+    share_bytes_with_rng(key, rng).map(DomShares::from)
+}
+
+#[must_use]
+pub fn ti_share_key(key: &Key, masks: &[Key; 2]) -> TiKey {
+    ti_share_bytes(key, masks)
+}
+
+#[must_use]
+pub fn ti_share_key_with_rng<R: Rng>(key: &Key, rng: &mut R) -> TiKey {
+    ti_share_bytes_with_rng(key, rng)
+}
+
+// The shares of a masked byte are only ever shifted and XORed while packing, so this works for
+// any masking scheme.
+#[must_use]
+#[inline]
+fn masked_key_word<B: MaskedByte>(bytes: &[B]) -> B::Word {
+    let word = B::Word::from(bytes[0])
+        ^ (B::Word::from(bytes[1]) << 8usize)
+        ^ (B::Word::from(bytes[2]) << 16usize)
+        ^ (B::Word::from(bytes[3]) << 24usize);
+    word.swap_bytes()
+}
+
+#[must_use]
+pub fn precompute_masked_round_keys<B: MaskedByte>(key: &[B; KEY_SIZE]) -> RoundKeys<B::Word> {
+    let mut round_keys = [Default::default(); ROUNDS * 2];
+    round_keys[0] = masked_key_word(&key[12..16]);
+    round_keys[1] = masked_key_word(&key[4..8]);
+    round_keys[2] = masked_key_word(&key[8..12]);
+    round_keys[3] = masked_key_word(&key[0..4]);
 
     fill_round_keys(&mut round_keys);
 
@@ -279,8 +314,9 @@ pub fn precompute_masked_round_keys(
 #[cfg(test)]
 mod tests {
     use crate::gift128::key_schedule::{
-        mask_key, precompute_masked_round_keys, precompute_round_keys,
+        mask_key, precompute_masked_round_keys, precompute_round_keys, share_key,
     };
+    use crate::gift128::SharedKey;
     use crate::gift128::KEY_SIZE;
 
     const KEY: [u8; KEY_SIZE] = [
@@ -312,4 +348,16 @@ mod tests {
         }
         assert_eq!(unmasked_key, KEY);
     }
+
+    #[test]
+    fn test_shared_round_keys() {
+        let round_keys = precompute_round_keys(&KEY);
+        let masks = [KEY_MASKS, [0xa5; KEY_SIZE]];
+        let shared_key: SharedKey<3> = share_key(&KEY, &masks);
+        let shared_round_keys = precompute_masked_round_keys(&shared_key);
+
+        for (shared, expected) in shared_round_keys.into_iter().zip(round_keys) {
+            assert_eq!(shared.recover_shares(), expected);
+        }
+    }
 }
@@ -0,0 +1,293 @@
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::gift128::error::{try_map_blocks, unwrap_decrypt, unwrap_encrypt, Gift128Error};
+use crate::gift128::hooks::NoHook;
+use crate::gift128::key_schedule::{
+    precompute_masked_round_keys, precompute_round_keys, RoundKeys,
+};
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::packing::{masked_pack, masked_unpack, pack, unpack};
+use crate::gift128::rounds::{inv_rounds, inv_rounds_with_hook, rounds, rounds_with_hook};
+use crate::gift128::traits::MaskedByte;
+use crate::gift128::{Block, Key, BLOCK_SIZE, KEY_SIZE};
+use crate::random::Rng;
+
+// Overwrites the round keys with volatile writes, so that the compiler cannot remove them as dead
+// stores before the memory is freed.
+#[inline]
+pub(crate) fn zeroize<T: Default>(round_keys: &mut [T]) {
+    for round_key in round_keys.iter_mut() {
+        // SAFETY: `round_key` is a valid and aligned mutable reference.
+        unsafe { ptr::write_volatile(round_key, T::default()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+// Owns the round keys of a key, so that they are only computed once for any number of calls. The
+// round keys are zeroized on drop.
+#[derive(Clone)]
+pub struct Gift128 {
+    pub(crate) round_keys: RoundKeys<u32>,
+}
+
+impl Gift128 {
+    #[must_use]
+    #[inline]
+    pub fn new(key: &Key) -> Self {
+        Gift128 {
+            round_keys: precompute_round_keys(key),
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn encrypt_block(&self, plaintext: &Block) -> Block {
+        unpack(rounds(pack(plaintext), &self.round_keys))
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn decrypt_block(&self, ciphertext: &Block) -> Block {
+        unpack(inv_rounds(pack(ciphertext), &self.round_keys))
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8], ciphertext: &mut [u8]) {
+        unwrap_encrypt(self.try_encrypt(plaintext, ciphertext));
+    }
+
+    // Like `encrypt`, but returns an error instead of panicking on invalid lengths. The output is
+    // left untouched in that case.
+    #[inline]
+    pub fn try_encrypt(&self, plaintext: &[u8], ciphertext: &mut [u8]) -> Result<(), Gift128Error> {
+        try_map_blocks(plaintext, ciphertext, |block| self.encrypt_block(block))
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8], plaintext: &mut [u8]) {
+        unwrap_decrypt(self.try_decrypt(ciphertext, plaintext));
+    }
+
+    #[inline]
+    pub fn try_decrypt(&self, ciphertext: &[u8], plaintext: &mut [u8]) -> Result<(), Gift128Error> {
+        try_map_blocks(ciphertext, plaintext, |block| self.decrypt_block(block))
+    }
+
+    // Overwrites every block with its encryption, so that no second buffer is needed.
+    #[inline]
+    pub fn encrypt_blocks_in_place(&self, blocks: &mut [Block]) {
+        for block in blocks.iter_mut() {
+            *block = self.encrypt_block(block);
+        }
+    }
+
+    #[inline]
+    pub fn decrypt_blocks_in_place(&self, blocks: &mut [Block]) {
+        for block in blocks.iter_mut() {
+            *block = self.decrypt_block(block);
+        }
+    }
+
+    pub fn encrypt_in_place(&self, buffer: &mut [u8]) {
+        let (blocks, []) = buffer.as_chunks_mut() else {
+            panic!("buffer size is not a multiple of 16");
+        };
+        self.encrypt_blocks_in_place(blocks);
+    }
+
+    pub fn decrypt_in_place(&self, buffer: &mut [u8]) {
+        let (blocks, []) = buffer.as_chunks_mut() else {
+            panic!("buffer size is not a multiple of 16");
+        };
+        self.decrypt_blocks_in_place(blocks);
+    }
+}
+
+impl Drop for Gift128 {
+    fn drop(&mut self) {
+        zeroize(&mut self.round_keys);
+    }
+}
+
+// Like `Gift128`, but for a masked key. The round keys stay masked.
+#[derive(Clone)]
+pub struct MaskedGift128<B: MaskedByte = BinaryMask<u8>> {
+    round_keys: RoundKeys<B::Word>,
+}
+
+impl<B: MaskedByte> MaskedGift128<B> {
+    #[must_use]
+    #[inline]
+    pub fn new(key: &[B; KEY_SIZE]) -> Self {
+        MaskedGift128 {
+            round_keys: precompute_masked_round_keys(key),
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn encrypt_block<R: Rng>(
+        &self,
+        plaintext: &[B; BLOCK_SIZE],
+        rng: &mut R,
+    ) -> [B; BLOCK_SIZE] {
+        let state = rounds_with_hook(masked_pack(plaintext), &self.round_keys, &mut NoHook, rng);
+        masked_unpack(state)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn decrypt_block<R: Rng>(
+        &self,
+        ciphertext: &[B; BLOCK_SIZE],
+        rng: &mut R,
+    ) -> [B; BLOCK_SIZE] {
+        let state =
+            inv_rounds_with_hook(masked_pack(ciphertext), &self.round_keys, &mut NoHook, rng);
+        masked_unpack(state)
+    }
+
+    pub fn encrypt<R: Rng>(&self, plaintext: &[B], ciphertext: &mut [B], rng: &mut R) {
+        unwrap_encrypt(self.try_encrypt(plaintext, ciphertext, rng));
+    }
+
+    #[inline]
+    pub fn try_encrypt<R: Rng>(
+        &self,
+        plaintext: &[B],
+        ciphertext: &mut [B],
+        rng: &mut R,
+    ) -> Result<(), Gift128Error> {
+        try_map_blocks(plaintext, ciphertext, |block| {
+            self.encrypt_block(block, rng)
+        })
+    }
+
+    pub fn decrypt<R: Rng>(&self, ciphertext: &[B], plaintext: &mut [B], rng: &mut R) {
+        unwrap_decrypt(self.try_decrypt(ciphertext, plaintext, rng));
+    }
+
+    #[inline]
+    pub fn try_decrypt<R: Rng>(
+        &self,
+        ciphertext: &[B],
+        plaintext: &mut [B],
+        rng: &mut R,
+    ) -> Result<(), Gift128Error> {
+        try_map_blocks(ciphertext, plaintext, |block| {
+            self.decrypt_block(block, rng)
+        })
+    }
+
+    #[inline]
+    pub fn encrypt_blocks_in_place<R: Rng>(&self, blocks: &mut [[B; BLOCK_SIZE]], rng: &mut R) {
+        for block in blocks.iter_mut() {
+            *block = self.encrypt_block(block, rng);
+        }
+    }
+
+    #[inline]
+    pub fn decrypt_blocks_in_place<R: Rng>(&self, blocks: &mut [[B; BLOCK_SIZE]], rng: &mut R) {
+        for block in blocks.iter_mut() {
+            *block = self.decrypt_block(block, rng);
+        }
+    }
+
+    pub fn encrypt_in_place<R: Rng>(&self, buffer: &mut [B], rng: &mut R) {
+        let (blocks, []) = buffer.as_chunks_mut() else {
+            panic!("buffer size is not a multiple of 16");
+        };
+        self.encrypt_blocks_in_place(blocks, rng);
+    }
+
+    pub fn decrypt_in_place<R: Rng>(&self, buffer: &mut [B], rng: &mut R) {
+        let (blocks, []) = buffer.as_chunks_mut() else {
+            panic!("buffer size is not a multiple of 16");
+        };
+        self.decrypt_blocks_in_place(blocks, rng);
+    }
+}
+
+impl<B: MaskedByte> Drop for MaskedGift128<B> {
+    fn drop(&mut self) {
+        zeroize(&mut self.round_keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::MaybeUninit;
+    use core::ptr;
+
+    use crate::gift128::key_schedule::{mask_key, share_key};
+    use crate::gift128::masking::BinaryMask;
+    use crate::gift128::shares::Shares;
+    use crate::gift128::tests::{CIPHERTEXT, KEY, PLAINTEXT};
+    use crate::gift128::{
+        encrypt, encrypt_masked, mask_block, share_block, unmask_block, unshare_block, Gift128,
+        MaskedGift128, BLOCK_SIZE,
+    };
+    use crate::random::XorShiftRng;
+
+    #[test]
+    fn test_gift128() {
+        let cipher = Gift128::new(&KEY);
+        assert_eq!(cipher.encrypt_block(&PLAINTEXT), CIPHERTEXT);
+        assert_eq!(cipher.decrypt_block(&CIPHERTEXT), PLAINTEXT);
+
+        let mut plaintext = [0; 3 * BLOCK_SIZE];
+        for (i, byte) in plaintext.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let mut expected = [0; 3 * BLOCK_SIZE];
+        encrypt(&plaintext, &KEY, &mut expected);
+
+        let mut ciphertext = [0; 3 * BLOCK_SIZE];
+        cipher.encrypt(&plaintext, &mut ciphertext);
+        assert_eq!(ciphertext, expected);
+        let mut decrypted = [0; 3 * BLOCK_SIZE];
+        cipher.decrypt(&ciphertext, &mut decrypted);
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn test_masked_gift128() {
+        let masked_key = mask_key(&KEY, &[0x5a; 16]);
+        let cipher = MaskedGift128::new(&masked_key);
+        let masked_plaintext = mask_block(&PLAINTEXT, &[0xa5; 16]);
+        let mut rng = XorShiftRng::new(1);
+        let masked_ciphertext = cipher.encrypt_block(&masked_plaintext, &mut rng);
+        assert_eq!(unmask_block(&masked_ciphertext), CIPHERTEXT);
+        assert_eq!(
+            unmask_block(&cipher.decrypt_block(&masked_ciphertext, &mut rng)),
+            PLAINTEXT
+        );
+
+        let mut expected = [BinaryMask::default(); BLOCK_SIZE];
+        encrypt_masked(&masked_plaintext, &masked_key, &mut expected, &mut rng);
+        let mut ciphertext = [BinaryMask::default(); BLOCK_SIZE];
+        cipher.encrypt(&masked_plaintext, &mut ciphertext, &mut rng);
+        assert_eq!(unmask_block(&ciphertext), unmask_block(&expected));
+
+        let shared_key = share_key::<3>(&KEY, &[[0x3c; 16], [0xc3; 16]]);
+        let cipher = MaskedGift128::new(&shared_key);
+        let shared_plaintext = share_block(&PLAINTEXT, &[[0x12; 16], [0x34; 16]]);
+        let shared_ciphertext: [Shares<u8, 3>; BLOCK_SIZE] =
+            cipher.encrypt_block(&shared_plaintext, &mut rng);
+        assert_eq!(unshare_block(&shared_ciphertext), CIPHERTEXT);
+    }
+
+    #[test]
+    fn test_zeroize_on_drop() {
+        // The memory stays allocated after dropping in place, so the round keys can still be read.
+        let mut cipher = MaybeUninit::new(Gift128::new(&KEY));
+        unsafe { ptr::drop_in_place(cipher.as_mut_ptr()) };
+        let round_keys = unsafe { ptr::addr_of!((*cipher.as_ptr()).round_keys).read() };
+        assert!(round_keys.iter().all(|&round_key| round_key == 0));
+
+        let masked_key = mask_key(&KEY, &[0x5a; 16]);
+        let mut cipher = MaybeUninit::new(MaskedGift128::new(&masked_key));
+        unsafe { ptr::drop_in_place(cipher.as_mut_ptr()) };
+        let round_keys = unsafe { ptr::addr_of!((*cipher.as_ptr()).round_keys).read() };
+        assert!(round_keys.iter().all(|&BinaryMask(a, b)| a == 0 && b == 0));
+    }
+}
@@ -0,0 +1,372 @@
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not, Shl, Shr};
+
+use crate::gift128::packing::{pack, unpack};
+use crate::gift128::traits::{Rotate, SwapBytes};
+use crate::gift128::{Block, State};
+
+#[inline]
+const fn splat(x: u32) -> u64 {
+    (x as u64) | ((x as u64) << 32)
+}
+
+// Two words of independent states side by side in the low and high half of a `u64`, so that the
+// rounds process two blocks at once on 64-bit hosts. Shifts and rotations are masked to stay
+// within their lane.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Lanes2(pub u64);
+
+impl Lanes2 {
+    #[must_use]
+    #[inline]
+    pub fn new(lanes: [u32; 2]) -> Self {
+        Lanes2(u64::from(lanes[0]) | (u64::from(lanes[1]) << 32))
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn splat(x: u32) -> Self {
+        Lanes2(splat(x))
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn lanes(self) -> [u32; 2] {
+        [self.0 as u32, (self.0 >> 32) as u32]
+    }
+}
+
+impl BitAnd for Lanes2 {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Lanes2(self.0 & rhs.0)
+    }
+}
+
+impl BitAnd<u32> for Lanes2 {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        Lanes2(self.0 & splat(rhs))
+    }
+}
+
+impl BitOr for Lanes2 {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Lanes2(self.0 | rhs.0)
+    }
+}
+
+impl BitXor for Lanes2 {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Lanes2(self.0 ^ rhs.0)
+    }
+}
+
+impl BitXorAssign for Lanes2 {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        self.0 ^= rhs.0;
+    }
+}
+
+impl BitXorAssign<u32> for Lanes2 {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        self.0 ^= splat(rhs);
+    }
+}
+
+impl Not for Lanes2 {
+    type Output = Self;
+
+    #[inline]
+    fn not(self) -> Self::Output {
+        Lanes2(!self.0)
+    }
+}
+
+impl<R: Copy> Shl<R> for Lanes2
+where
+    u32: Shl<R, Output = u32>,
+    u64: Shl<R, Output = u64>,
+{
+    type Output = Self;
+
+    #[inline]
+    fn shl(self, rhs: R) -> Self::Output {
+        Lanes2((self.0 << rhs) & splat(u32::MAX << rhs))
+    }
+}
+
+impl<R: Copy> Shr<R> for Lanes2
+where
+    u32: Shr<R, Output = u32>,
+    u64: Shr<R, Output = u64>,
+{
+    type Output = Self;
+
+    #[inline]
+    fn shr(self, rhs: R) -> Self::Output {
+        Lanes2((self.0 >> rhs) & splat(u32::MAX >> rhs))
+    }
+}
+
+impl Rotate for Lanes2 {
+    #[inline]
+    fn rotate_right(self, rhs: u32) -> Self {
+        let rhs = rhs % 32;
+        if rhs == 0 {
+            return self;
+        }
+        self >> rhs | self << (32 - rhs)
+    }
+}
+
+impl SwapBytes for Lanes2 {
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        Lanes2(self.0.swap_bytes().rotate_left(32))
+    }
+}
+
+// One word of N independent states per lane. The operations work on all lanes alike, which the
+// compiler turns into SIMD instructions where the target has them and into a loop elsewhere.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Lanes<const N: usize>(pub [u32; N]);
+
+impl<const N: usize> Lanes<N> {
+    #[must_use]
+    #[inline]
+    pub fn splat(x: u32) -> Self {
+        Lanes([x; N])
+    }
+
+    #[must_use]
+    #[inline(always)]
+    fn map(mut self, f: impl Fn(u32) -> u32) -> Self {
+        for lane in self.0.iter_mut() {
+            *lane = f(*lane);
+        }
+        self
+    }
+
+    #[must_use]
+    #[inline(always)]
+    fn zip(mut self, rhs: Self, f: impl Fn(u32, u32) -> u32) -> Self {
+        for (lane, rhs) in self.0.iter_mut().zip(rhs.0) {
+            *lane = f(*lane, rhs);
+        }
+        self
+    }
+}
+
+impl<const N: usize> Default for Lanes<N> {
+    #[inline]
+    fn default() -> Self {
+        Lanes([0; N])
+    }
+}
+
+impl<const N: usize> BitAnd for Lanes<N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.zip(rhs, |a, b| a & b)
+    }
+}
+
+impl<const N: usize> BitAnd<u32> for Lanes<N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: u32) -> Self::Output {
+        self.map(|lane| lane & rhs)
+    }
+}
+
+impl<const N: usize> BitOr for Lanes<N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.zip(rhs, |a, b| a | b)
+    }
+}
+
+impl<const N: usize> BitXor for Lanes<N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.zip(rhs, |a, b| a ^ b)
+    }
+}
+
+impl<const N: usize> BitXorAssign for Lanes<N> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = *self ^ rhs;
+    }
+}
+
+impl<const N: usize> BitXorAssign<u32> for Lanes<N> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: u32) {
+        *self = self.map(|lane| lane ^ rhs);
+    }
+}
+
+impl<const N: usize> Not for Lanes<N> {
+    type Output = Self;
+
+    #[inline]
+    fn not(self) -> Self::Output {
+        self.map(|lane| !lane)
+    }
+}
+
+impl<R: Copy, const N: usize> Shl<R> for Lanes<N>
+where
+    u32: Shl<R, Output = u32>,
+{
+    type Output = Self;
+
+    #[inline]
+    fn shl(self, rhs: R) -> Self::Output {
+        self.map(|lane| lane << rhs)
+    }
+}
+
+impl<R: Copy, const N: usize> Shr<R> for Lanes<N>
+where
+    u32: Shr<R, Output = u32>,
+{
+    type Output = Self;
+
+    #[inline]
+    fn shr(self, rhs: R) -> Self::Output {
+        self.map(|lane| lane >> rhs)
+    }
+}
+
+impl<const N: usize> Rotate for Lanes<N> {
+    #[inline]
+    fn rotate_right(self, rhs: u32) -> Self {
+        self.map(|lane| lane.rotate_right(rhs))
+    }
+}
+
+impl<const N: usize> SwapBytes for Lanes<N> {
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        self.map(u32::swap_bytes)
+    }
+}
+
+#[must_use]
+#[inline]
+pub(crate) fn join(a: State<u32>, b: State<u32>) -> State<Lanes2> {
+    State(
+        Lanes2::new([a.0, b.0]),
+        Lanes2::new([a.1, b.1]),
+        Lanes2::new([a.2, b.2]),
+        Lanes2::new([a.3, b.3]),
+    )
+}
+
+#[must_use]
+#[inline]
+pub(crate) fn split(state: State<Lanes2>) -> (State<u32>, State<u32>) {
+    let State([a0, b0], [a1, b1], [a2, b2], [a3, b3]) = state.map(Lanes2::lanes);
+    (State(a0, a1, a2, a3), State(b0, b1, b2, b3))
+}
+
+// Packs N blocks into the lanes of one state, i.e. transposes them from one state per block to
+// one lane per block.
+#[must_use]
+#[inline]
+pub(crate) fn pack_lanes<const N: usize>(blocks: &[Block; N]) -> State<Lanes<N>> {
+    let states = blocks.map(|block| pack(&block));
+    State(
+        Lanes(states.map(|state| state.0)),
+        Lanes(states.map(|state| state.1)),
+        Lanes(states.map(|state| state.2)),
+        Lanes(states.map(|state| state.3)),
+    )
+}
+
+#[must_use]
+#[inline]
+pub(crate) fn unpack_lanes<const N: usize>(state: State<Lanes<N>>) -> [Block; N] {
+    core::array::from_fn(|i| unpack(state.map(|word| word.0[i])))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::key_schedule::precompute_round_keys;
+    use crate::gift128::lanes::{join, pack_lanes, split, unpack_lanes, Lanes, Lanes2};
+    use crate::gift128::packing::{pack, unpack};
+    use crate::gift128::rounds::{inv_rounds, rounds};
+    use crate::gift128::traits::{Rotate, SwapBytes};
+    use crate::gift128::State;
+
+    const A: u32 = 0xd576370d;
+    const B: u32 = 0x8b52c001;
+
+    #[test]
+    fn test_operations() {
+        let x = Lanes2::new([A, B]);
+        assert_eq!(x.lanes(), [A, B]);
+        for n in 0..32u32 {
+            assert_eq!((x << n).lanes(), [A << n, B << n]);
+            assert_eq!((x >> n as usize).lanes(), [A >> n, B >> n]);
+            assert_eq!(
+                x.rotate_right(n).lanes(),
+                [A.rotate_right(n), B.rotate_right(n)]
+            );
+        }
+        assert_eq!(x.swap_bytes().lanes(), [A.swap_bytes(), B.swap_bytes()]);
+        assert_eq!((x & 0x0f0f00ff).lanes(), [A & 0x0f0f00ff, B & 0x0f0f00ff]);
+        let mut y = x;
+        y ^= 0x80000001;
+        assert_eq!(y.lanes(), [A ^ 0x80000001, B ^ 0x80000001]);
+    }
+
+    #[test]
+    fn test_rounds() {
+        let key = [0x42; 16];
+        let round_keys = precompute_round_keys(&key);
+        let lanes_round_keys = round_keys.map(Lanes2::splat);
+        let a = State(A, B, 0x2bafc0cc, 0xa3487987);
+        let b = State(0x01234567, 0x89abcdef, 0xfedcba98, 0x76543210);
+
+        let state = rounds(join(a, b), &lanes_round_keys);
+        assert_eq!(
+            split(state),
+            (rounds(a, &round_keys), rounds(b, &round_keys))
+        );
+        assert_eq!(split(inv_rounds(state, &lanes_round_keys)), (a, b));
+    }
+
+    #[test]
+    fn test_lanes_rounds() {
+        let round_keys = precompute_round_keys(&[0x42; 16]);
+        let lanes_round_keys = round_keys.map(Lanes::<8>::splat);
+        let blocks: [[u8; 16]; 8] = core::array::from_fn(|i| [i as u8 * 31; 16]);
+
+        let state = rounds(pack_lanes(&blocks), &lanes_round_keys);
+        let expected = blocks.map(|block| unpack(rounds(pack(&block), &round_keys)));
+        assert_eq!(unpack_lanes(state), expected);
+        assert_eq!(unpack_lanes(inv_rounds(state, &lanes_round_keys)), blocks);
+    }
+}
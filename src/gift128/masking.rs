@@ -1,10 +1,9 @@
-use core::ops::{
-    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Shl, ShlAssign, Shr,
-    ShrAssign,
-};
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not, Shl, ShlAssign, Shr, ShrAssign};
 
-use crate::gift128::traits::{Rotate, SwapBytes};
+use crate::gift128::sbox::Gates;
+use crate::gift128::traits::{MaskedByte, Refresh, Rotate, SwapBytes, Unmask};
 use crate::gift128::State;
+use crate::random::{Random, Rng};
 
 #[derive(Copy, Clone, Debug)]
 pub struct BinaryMask<T>(pub T, pub T);
@@ -21,20 +20,6 @@ impl<T: BitXor<Output = T> + Copy> BinaryMask<T> {
     }
 }
 
-impl<T> BitAnd for BinaryMask<T>
-where
-    T: BitAnd<Output = T> + Not<Output = T> + BitOr<Output = T> + BitXor<Output = T> + Copy,
-{
-    type Output = Self;
-
-    #[inline]
-    fn bitand(self, rhs: Self) -> Self::Output {
-        let z1 = (self.0 & rhs.0) ^ (self.0 | !rhs.1);
-        let z2 = (self.1 & rhs.0) ^ (self.1 | !rhs.1);
-        BinaryMask(z1, z2)
-    }
-}
-
 impl<T> BitAnd<T> for BinaryMask<T>
 where
     T: BitAnd<Output = T> + Not<Output = T> + Copy,
@@ -49,30 +34,6 @@ where
     }
 }
 
-impl<T> BitAndAssign for BinaryMask<T>
-where
-    T: BitAnd<Output = T> + Not<Output = T> + BitOr<Output = T> + BitXor<Output = T> + Copy,
-{
-    #[inline]
-    fn bitand_assign(&mut self, rhs: Self) {
-        *self = *self & rhs;
-    }
-}
-
-impl<T> BitOr for BinaryMask<T>
-where
-    T: BitAnd<Output = T> + Not<Output = T> + BitOr<Output = T> + BitXor<Output = T> + Copy,
-{
-    type Output = Self;
-
-    #[inline]
-    fn bitor(self, rhs: Self) -> Self::Output {
-        let z1 = (self.0 & rhs.0) ^ (self.0 | rhs.1);
-        let z2 = (self.1 | rhs.0) ^ (self.1 & rhs.1);
-        BinaryMask(z1, z2)
-    }
-}
-
 impl<T> BitOr<T> for BinaryMask<T>
 where
     T: BitAnd<Output = T> + Not<Output = T> + BitOr<Output = T> + BitXor<Output = T> + Copy,
@@ -87,13 +48,25 @@ where
     }
 }
 
-impl<T> BitOrAssign for BinaryMask<T>
+// The multiplication of Ishai, Sahai and Wagner for two shares. The fresh mask blinds the cross
+// products before they are added to the second share.
+impl<T> Gates for BinaryMask<T>
 where
-    T: BitAnd<Output = T> + Not<Output = T> + BitOr<Output = T> + BitXor<Output = T> + Copy,
+    T: BitAnd<Output = T> + BitXor<Output = T> + Not<Output = T> + Copy + Random,
 {
     #[inline]
-    fn bitor_assign(&mut self, rhs: Self) {
-        *self = *self | rhs;
+    fn and<R: Rng>(self, rhs: Self, rng: &mut R) -> Self {
+        let r = T::random(rng);
+        let z0 = (self.0 & rhs.0) ^ r;
+        let z1 = (self.1 & rhs.1) ^ ((r ^ (self.0 & rhs.1)) ^ (self.1 & rhs.0));
+        BinaryMask(z0, z1)
+    }
+
+    // a | b = !(!a & !b), where complementing the first share complements the value.
+    #[inline]
+    fn or<R: Rng>(self, rhs: Self, rng: &mut R) -> Self {
+        let BinaryMask(z0, z1) = BinaryMask(!self.0, self.1).and(BinaryMask(!rhs.0, rhs.1), rng);
+        BinaryMask(!z0, z1)
     }
 }
 
@@ -186,12 +159,15 @@ impl<T: BitXor<Output = T> + Copy> State<T> {
 impl<T: BitXor<Output = T> + Copy> State<BinaryMask<T>> {
     #[inline]
     pub fn recover_shares(self) -> State<T> {
-        State(
-            self.0.recover_shares(),
-            self.1.recover_shares(),
-            self.2.recover_shares(),
-            self.3.recover_shares(),
-        )
+        self.map(BinaryMask::recover_shares)
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy + Random> Refresh for BinaryMask<T> {
+    #[inline]
+    fn refresh<R: Rng>(self, rng: &mut R) -> Self {
+        let r = T::random(rng);
+        BinaryMask(self.0 ^ r, self.1 ^ r)
     }
 }
 
@@ -223,11 +199,24 @@ impl<T: Default> Default for BinaryMask<T> {
     }
 }
 
+impl Unmask for BinaryMask<u32> {
+    #[inline]
+    fn unmask(self) -> u32 {
+        self.recover_shares()
+    }
+}
+
+impl MaskedByte for BinaryMask<u8> {
+    type Word = BinaryMask<u32>;
+}
+
 #[cfg(test)]
 mod tests {
     use crate::gift128::masking::BinaryMask;
     use crate::gift128::rounds::StateOperations;
-    use crate::gift128::traits::{Rotate, SwapBytes};
+    use crate::gift128::sbox::Gates;
+    use crate::gift128::traits::{Refresh, Rotate, SwapBytes};
+    use crate::random::XorShiftRng;
 
     #[test]
     fn test_and() {
@@ -239,10 +228,15 @@ mod tests {
         let masked_value1 = BinaryMask::make_shares(value1, mask1);
         let masked_value2 = BinaryMask::make_shares(value2, mask2);
 
-        let masked_result = masked_value1 & masked_value2;
+        let mut rng = XorShiftRng::new(1);
+        let masked_result = masked_value1.and(masked_value2, &mut rng);
         let result = masked_result.recover_shares();
-
         assert_eq!(result, value1 & value2);
+
+        // The shares depend on the fresh mask, not only on the inputs.
+        let other = masked_value1.and(masked_value2, &mut rng);
+        assert_eq!(other.recover_shares(), value1 & value2);
+        assert_ne!(other.1, masked_result.1);
     }
 
     #[test]
@@ -269,10 +263,14 @@ mod tests {
         let masked_value1 = BinaryMask::make_shares(value1, mask1);
         let masked_value2 = BinaryMask::make_shares(value2, mask2);
 
-        let masked_result = masked_value1 | masked_value2;
+        let mut rng = XorShiftRng::new(1);
+        let masked_result = masked_value1.or(masked_value2, &mut rng);
         let result = masked_result.recover_shares();
-
         assert_eq!(result, value1 | value2);
+
+        let other = masked_value1.or(masked_value2, &mut rng);
+        assert_eq!(other.recover_shares(), value1 | value2);
+        assert_ne!(other.1, masked_result.1);
     }
 
     #[test]
@@ -407,4 +405,15 @@ mod tests {
             value.nibble_ror_3()
         );
     }
+
+    #[test]
+    fn test_refresh() {
+        let value = 0xD576370Du32;
+        let mask = 0xB751F5EFu32;
+        let masked_value = BinaryMask::make_shares(value, mask);
+
+        let refreshed = masked_value.refresh(&mut XorShiftRng::new(1));
+        assert_eq!(refreshed.recover_shares(), value);
+        assert_ne!(refreshed.1, mask);
+    }
 }
@@ -1,69 +1,273 @@
+use crate::gift128::dom::DomShares;
+use crate::gift128::error::{check_lengths, try_map_blocks, unwrap_decrypt, unwrap_encrypt};
+use crate::gift128::fault::{
+    check_difference, inv_verified_rounds_with_hook, verified_rounds_with_hook,
+};
+use crate::gift128::hooks::{NoHook, RoundHook};
+use crate::gift128::infective::infective_rounds;
 use crate::gift128::key_schedule::{
     precompute_masked_round_keys, precompute_round_keys, RoundKeys,
 };
+use crate::gift128::lanes::{join, pack_lanes, split, unpack_lanes, Lanes, Lanes2};
 use crate::gift128::masking::BinaryMask;
 use crate::gift128::packing::{
-    bitsliced_pack, bitsliced_unpack, masked_pack, masked_unpack, pack, unpack,
+    bitsliced_masked_pack, bitsliced_masked_unpack, bitsliced_pack, bitsliced_unpack, masked_pack,
+    masked_unpack, pack, unpack,
 };
-use crate::gift128::rounds::{inv_rounds, rounds};
+use crate::gift128::rounds::{inv_rounds, inv_rounds_with_hook, rounds, rounds_with_hook};
+use crate::gift128::shares::{share_bytes, share_bytes_with_rng, unshare_bytes, Shares};
+use crate::gift128::shuffling::shuffled_rounds;
+use crate::gift128::threshold::{ti_share_bytes, ti_share_bytes_with_rng, TiShares};
+use crate::gift128::traits::{MaskedByte, Unmask};
+use crate::random::{NoRng, Rng};
+
+pub use crate::gift128::error::{Gift128Error, ProtectedError};
+pub use crate::gift128::fault::FaultDetected;
+pub use crate::gift128::keyed::{Gift128, MaskedGift128};
 
+#[cfg(feature = "cipher")]
+mod block_cipher;
+pub(crate) mod dom;
+pub(crate) mod error;
+pub(crate) mod fault;
+pub mod hooks;
+pub(crate) mod infective;
 pub mod key_schedule;
-mod masking;
-mod packing;
-mod rounds;
-mod sbox;
-mod traits;
+pub(crate) mod keyed;
+pub(crate) mod lanes;
+pub(crate) mod masking;
+pub(crate) mod packing;
+pub(crate) mod rounds;
+pub(crate) mod sbox;
+pub(crate) mod shares;
+pub(crate) mod shuffling;
+pub(crate) mod threshold;
+pub(crate) mod traits;
 
 const KEY_SIZE: usize = 16;
-const BLOCK_SIZE: usize = 16;
+pub(crate) const BLOCK_SIZE: usize = 16;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
-struct State<T>(T, T, T, T);
+pub(crate) struct State<T>(pub(crate) T, pub(crate) T, pub(crate) T, pub(crate) T);
+
+impl<T> State<T> {
+    #[inline]
+    pub(crate) fn map<U>(self, mut f: impl FnMut(T) -> U) -> State<U> {
+        State(f(self.0), f(self.1), f(self.2), f(self.3))
+    }
+}
 
 pub type Block = [u8; BLOCK_SIZE];
 pub type MaskedBlock = [BinaryMask<u8>; BLOCK_SIZE];
 pub type Key = [u8; KEY_SIZE];
 pub type MaskedKey = [BinaryMask<u8>; KEY_SIZE];
+pub type SharedBlock<const N: usize> = [Shares<u8, N>; BLOCK_SIZE];
+pub type SharedKey<const N: usize> = [Shares<u8, N>; KEY_SIZE];
+pub type DomBlock<const N: usize> = [DomShares<u8, N>; BLOCK_SIZE];
+pub type DomKey<const N: usize> = [DomShares<u8, N>; KEY_SIZE];
+pub type TiBlock = [TiShares<u8>; BLOCK_SIZE];
+pub type TiKey = [TiShares<u8>; KEY_SIZE];
 
 pub fn encrypt(plaintext: &[u8], key: &Key, ciphertext: &mut [u8]) {
-    if !plaintext.len().is_multiple_of(BLOCK_SIZE) {
-        panic!("plaintext size is not a multiple of 16");
-    }
+    Gift128::new(key).encrypt(plaintext, ciphertext);
+}
+
+pub fn decrypt(ciphertext: &[u8], key: &Key, plaintext: &mut [u8]) {
+    Gift128::new(key).decrypt(ciphertext, plaintext);
+}
+
+// Like `encrypt`, but returns an error instead of panicking on invalid lengths. This and the
+// other `try_` functions are free of panics, which `examples/no_panic.rs` checks at link time.
+#[inline]
+pub fn try_encrypt(plaintext: &[u8], key: &Key, ciphertext: &mut [u8]) -> Result<(), Gift128Error> {
+    Gift128::new(key).try_encrypt(plaintext, ciphertext)
+}
+
+#[inline]
+pub fn try_decrypt(ciphertext: &[u8], key: &Key, plaintext: &mut [u8]) -> Result<(), Gift128Error> {
+    Gift128::new(key).try_decrypt(ciphertext, plaintext)
+}
+
+// Like `encrypt`, but overwrites the buffer, which halves the memory needed for large buffers.
+pub fn encrypt_in_place(buffer: &mut [u8], key: &Key) {
+    Gift128::new(key).encrypt_in_place(buffer);
+}
+
+pub fn decrypt_in_place(buffer: &mut [u8], key: &Key) {
+    Gift128::new(key).decrypt_in_place(buffer);
+}
+
+pub fn encrypt_blocks_in_place(blocks: &mut [Block], key: &Key) {
+    Gift128::new(key).encrypt_blocks_in_place(blocks);
+}
 
-    if plaintext.len() != ciphertext.len() {
-        panic!("ciphertext size differs from plaintext size");
+pub fn decrypt_blocks_in_place(blocks: &mut [Block], key: &Key) {
+    Gift128::new(key).decrypt_blocks_in_place(blocks);
+}
+
+// Like `try_map_blocks`, but `f_lanes` maps N blocks at once. The remaining blocks are mapped
+// one by one with `f`.
+fn try_map_lanes<const N: usize>(
+    input: &[u8],
+    output: &mut [u8],
+    mut f_lanes: impl FnMut(&[Block; N]) -> [Block; N],
+    mut f: impl FnMut(&Block) -> Block,
+) -> Result<(), Gift128Error> {
+    check_lengths(input, output)?;
+    let ((blocks, []), (outputs, [])) = (input.as_chunks(), output.as_chunks_mut()) else {
+        return Err(Gift128Error::InvalidLength);
+    };
+    let (groups, blocks) = blocks.as_chunks();
+    let (output_groups, outputs) = outputs.as_chunks_mut();
+    for (group, output) in groups.iter().zip(output_groups) {
+        *output = f_lanes(group);
+    }
+    for (block, output) in blocks.iter().zip(outputs) {
+        *output = f(block);
     }
+    Ok(())
+}
 
+// Like `encrypt`, but two blocks are processed at once in the two halves of a `u64`, which is
+// faster on 64-bit hosts. A last odd block is processed on its own.
+pub fn encrypt_parallel(plaintext: &[u8], key: &Key, ciphertext: &mut [u8]) {
     let round_keys = precompute_round_keys(key);
-    for (i, chunk) in plaintext.chunks(BLOCK_SIZE).enumerate() {
-        // TODO: annoying runtime check
-        let plaintext_block = chunk.try_into().expect("invalid chunk length");
+    let lanes_round_keys = round_keys.map(Lanes2::splat);
+    unwrap_encrypt(try_map_lanes(
+        plaintext,
+        ciphertext,
+        |[a, b]| {
+            let (a, b) = split(rounds(join(pack(a), pack(b)), &lanes_round_keys));
+            [unpack(a), unpack(b)]
+        },
+        |block| unpack(rounds(pack(block), &round_keys)),
+    ));
+}
 
-        let state = rounds(pack(plaintext_block), &round_keys);
+pub fn decrypt_parallel(ciphertext: &[u8], key: &Key, plaintext: &mut [u8]) {
+    let round_keys = precompute_round_keys(key);
+    let lanes_round_keys = round_keys.map(Lanes2::splat);
+    unwrap_decrypt(try_map_lanes(
+        ciphertext,
+        plaintext,
+        |[a, b]| {
+            let (a, b) = split(inv_rounds(join(pack(a), pack(b)), &lanes_round_keys));
+            [unpack(a), unpack(b)]
+        },
+        |block| unpack(inv_rounds(pack(block), &round_keys)),
+    ));
+}
 
-        ciphertext[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE].copy_from_slice(&unpack(state));
-    }
+// Like `encrypt`, but N blocks are processed at once in the lanes of `[u32; N]` words, which the
+// compiler vectorizes for SIMD units such as SSE2 or AVX2 (N = 4 or 8). Remaining blocks are
+// processed on their own.
+pub fn encrypt_lanes<const N: usize>(plaintext: &[u8], key: &Key, ciphertext: &mut [u8]) {
+    const { assert!(N > 0, "number of lanes must be positive") };
+
+    let round_keys = precompute_round_keys(key);
+    let lanes_round_keys = round_keys.map(Lanes::<N>::splat);
+    unwrap_encrypt(try_map_lanes(
+        plaintext,
+        ciphertext,
+        |blocks| unpack_lanes(rounds(pack_lanes(blocks), &lanes_round_keys)),
+        |block| unpack(rounds(pack(block), &round_keys)),
+    ));
 }
 
-pub fn decrypt(ciphertext: &[u8], key: &Key, plaintext: &mut [u8]) {
-    if !ciphertext.len().is_multiple_of(16) {
-        panic!("ciphertext size is not a multiple of 16");
-    }
+pub fn decrypt_lanes<const N: usize>(ciphertext: &[u8], key: &Key, plaintext: &mut [u8]) {
+    const { assert!(N > 0, "number of lanes must be positive") };
 
-    if ciphertext.len() != plaintext.len() {
-        panic!("plaintext size differs from ciphertext size");
-    }
+    let round_keys = precompute_round_keys(key);
+    let lanes_round_keys = round_keys.map(Lanes::<N>::splat);
+    unwrap_decrypt(try_map_lanes(
+        ciphertext,
+        plaintext,
+        |blocks| unpack_lanes(inv_rounds(pack_lanes(blocks), &lanes_round_keys)),
+        |block| unpack(inv_rounds(pack(block), &round_keys)),
+    ));
+}
 
+// Like `try_encrypt`, but every block is decrypted again and compared with the plaintext. If a
+// fault is detected in any block, the whole ciphertext is zeroed.
+#[inline]
+pub fn encrypt_protected(
+    plaintext: &[u8],
+    key: &Key,
+    ciphertext: &mut [u8],
+) -> Result<(), ProtectedError> {
+    encrypt_protected_with_hook(plaintext, key, ciphertext, &mut NoHook)
+}
+
+// The hook is called by the rounds whose result is released, e.g. to inject a fault in tests.
+fn encrypt_protected_with_hook<H: RoundHook<u32>>(
+    plaintext: &[u8],
+    key: &Key,
+    ciphertext: &mut [u8],
+    hook: &mut H,
+) -> Result<(), ProtectedError> {
     let round_keys = precompute_round_keys(key);
+    let mut difference = 0;
+    try_map_blocks(plaintext, ciphertext, |block| {
+        let (state, block_difference) =
+            verified_rounds_with_hook(pack(block), &round_keys, hook, &mut NoHook, &mut NoRng);
+        difference |= block_difference;
+        unpack(state)
+    })?;
+    check_difference(difference, ciphertext, 0)?;
+    Ok(())
+}
 
-    for (i, chunk) in ciphertext.chunks(BLOCK_SIZE).enumerate() {
-        // TODO: annoying runtime check
-        let ciphertext_block = chunk.try_into().expect("invalid chunk length");
+#[inline]
+pub fn decrypt_protected(
+    ciphertext: &[u8],
+    key: &Key,
+    plaintext: &mut [u8],
+) -> Result<(), ProtectedError> {
+    decrypt_protected_with_hook(ciphertext, key, plaintext, &mut NoHook)
+}
 
-        let state = inv_rounds(pack(ciphertext_block), &round_keys);
+fn decrypt_protected_with_hook<H: RoundHook<u32>>(
+    ciphertext: &[u8],
+    key: &Key,
+    plaintext: &mut [u8],
+    hook: &mut H,
+) -> Result<(), ProtectedError> {
+    let round_keys = precompute_round_keys(key);
+    let mut difference = 0;
+    try_map_blocks(ciphertext, plaintext, |block| {
+        let (state, block_difference) =
+            inv_verified_rounds_with_hook(pack(block), &round_keys, hook, &mut NoHook, &mut NoRng);
+        difference |= block_difference;
+        unpack(state)
+    })?;
+    check_difference(difference, plaintext, 0)?;
+    Ok(())
+}
 
-        plaintext[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE].copy_from_slice(&unpack(state));
-    }
+// Like `encrypt`, but with an infective countermeasure: the rounds are computed twice and
+// interleaved with dummy rounds in an order drawn from `rng`, and a fault randomizes the
+// ciphertext instead of being reported.
+pub fn encrypt_infective<R: Rng>(plaintext: &[u8], key: &Key, ciphertext: &mut [u8], rng: &mut R) {
+    let round_keys = precompute_round_keys(key);
+    unwrap_encrypt(try_map_blocks(plaintext, ciphertext, |block| {
+        unpack(infective_rounds(pack(block), &round_keys, false, rng))
+    }));
+}
+
+pub fn decrypt_infective<R: Rng>(ciphertext: &[u8], key: &Key, plaintext: &mut [u8], rng: &mut R) {
+    let round_keys = precompute_round_keys(key);
+    unwrap_decrypt(try_map_blocks(ciphertext, plaintext, |block| {
+        unpack(infective_rounds(pack(block), &round_keys, true, rng))
+    }));
+}
+
+// Like `encrypt`, but hidden by shuffling the order of the words in every linear layer and
+// inserting dummy operations into every S-box and linear layer, as drawn from `rng`.
+pub fn encrypt_shuffled<R: Rng>(plaintext: &[u8], key: &Key, ciphertext: &mut [u8], rng: &mut R) {
+    let round_keys = precompute_round_keys(key);
+    unwrap_encrypt(try_map_blocks(plaintext, ciphertext, |block| {
+        unpack(shuffled_rounds(pack(block), &round_keys, rng))
+    }));
 }
 
 #[must_use]
@@ -84,50 +288,249 @@ pub fn unmask_block(masked_block: &MaskedBlock) -> Block {
     block
 }
 
-pub fn encrypt_masked(
-    plaintext: &[BinaryMask<u8>],
-    key: &MaskedKey,
-    ciphertext: &mut [BinaryMask<u8>],
+#[must_use]
+pub fn mask_block_with_rng<R: Rng>(block: &Block, rng: &mut R) -> MaskedBlock {
+    let mut masks = [0; BLOCK_SIZE];
+    rng.fill_bytes(&mut masks);
+    mask_block(block, &masks)
+}
+
+// Splits every byte into N shares using N - 1 mask blocks.
+#[must_use]
+pub fn share_block<const N: usize>(block: &Block, masks: &[Block]) -> SharedBlock<N> {
+    share_bytes(block, masks)
+}
+
+#[must_use]
+pub fn unshare_block<const N: usize>(shared_block: &SharedBlock<N>) -> Block {
+    unshare_bytes(shared_block)
+}
+
+#[must_use]
+pub fn share_block_with_rng<const N: usize, R: Rng>(block: &Block, rng: &mut R) -> SharedBlock<N> {
+    share_bytes_with_rng(block, rng)
+}
+
+// Like `share_block`, but the shares are multiplied with domain-oriented masking.
+#[must_use]
+pub fn dom_share_block<const N: usize>(block: &Block, masks: &[Block]) -> DomBlock<N> {
+    share_bytes(block, masks).map(DomShares::from)
+}
+
+#[must_use]
+pub fn dom_unshare_block<const N: usize>(dom_block: &DomBlock<N>) -> Block {
+    dom_block.map(DomShares::recover_shares)
+}
+
+#[must_use]
+pub fn dom_share_block_with_rng<const N: usize, R: Rng>(block: &Block, rng: &mut R) -> DomBlock<N> {
+    share_bytes_with_rng(block, rng).map(DomShares::from)
+}
+
+// Splits every byte into three shares for the threshold implementation.
+#[must_use]
+pub fn ti_share_block(block: &Block, masks: &[Block; 2]) -> TiBlock {
+    ti_share_bytes(block, masks)
+}
+
+#[must_use]
+pub fn ti_unshare_block(ti_block: &TiBlock) -> Block {
+    ti_block.map(TiShares::recover_shares)
+}
+
+#[must_use]
+pub fn ti_share_block_with_rng<R: Rng>(block: &Block, rng: &mut R) -> TiBlock {
+    ti_share_bytes_with_rng(block, rng)
+}
+
+// Works with any masking scheme, e.g. `MaskedBlock`, `SharedBlock<N>` or `DomBlock<N>` of any
+// order or `TiBlock`. The fresh randomness of the non-linear gadgets is drawn from `rng`.
+pub fn encrypt_masked<B: MaskedByte, R: Rng>(
+    plaintext: &[B],
+    key: &[B; KEY_SIZE],
+    ciphertext: &mut [B],
+    rng: &mut R,
 ) {
-    if !plaintext.len().is_multiple_of(BLOCK_SIZE) {
-        panic!("plaintext size is not a multiple of 16");
-    }
+    MaskedGift128::new(key).encrypt(plaintext, ciphertext, rng);
+}
 
-    if plaintext.len() != ciphertext.len() {
-        panic!("ciphertext size differs from plaintext size");
-    }
+pub fn decrypt_masked<B: MaskedByte, R: Rng>(
+    ciphertext: &[B],
+    key: &[B; KEY_SIZE],
+    plaintext: &mut [B],
+    rng: &mut R,
+) {
+    MaskedGift128::new(key).decrypt(ciphertext, plaintext, rng);
+}
 
-    let round_keys = precompute_masked_round_keys(key);
+#[inline]
+pub fn try_encrypt_masked<B: MaskedByte, R: Rng>(
+    plaintext: &[B],
+    key: &[B; KEY_SIZE],
+    ciphertext: &mut [B],
+    rng: &mut R,
+) -> Result<(), Gift128Error> {
+    MaskedGift128::new(key).try_encrypt(plaintext, ciphertext, rng)
+}
 
-    for (i, chunk) in plaintext.chunks(BLOCK_SIZE).enumerate() {
-        // TODO: annoying runtime check
-        let plaintext_block = chunk.try_into().expect("invalid chunk length");
-        let state = rounds(masked_pack(plaintext_block), &round_keys);
-        ciphertext[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE].copy_from_slice(&masked_unpack(state));
-    }
+#[inline]
+pub fn try_decrypt_masked<B: MaskedByte, R: Rng>(
+    ciphertext: &[B],
+    key: &[B; KEY_SIZE],
+    plaintext: &mut [B],
+    rng: &mut R,
+) -> Result<(), Gift128Error> {
+    MaskedGift128::new(key).try_decrypt(ciphertext, plaintext, rng)
 }
 
-pub fn decrypt_masked(
-    ciphertext: &[BinaryMask<u8>],
-    key: &MaskedKey,
-    plaintext: &mut [BinaryMask<u8>],
+pub fn encrypt_masked_in_place<B: MaskedByte, R: Rng>(
+    buffer: &mut [B],
+    key: &[B; KEY_SIZE],
+    rng: &mut R,
 ) {
-    if !ciphertext.len().is_multiple_of(16) {
-        panic!("ciphertext size is not a multiple of 16");
-    }
+    MaskedGift128::new(key).encrypt_in_place(buffer, rng);
+}
 
-    if ciphertext.len() != plaintext.len() {
-        panic!("plaintext size differs from ciphertext size");
-    }
+pub fn decrypt_masked_in_place<B: MaskedByte, R: Rng>(
+    buffer: &mut [B],
+    key: &[B; KEY_SIZE],
+    rng: &mut R,
+) {
+    MaskedGift128::new(key).decrypt_in_place(buffer, rng);
+}
+
+pub fn encrypt_masked_blocks_in_place<B: MaskedByte, R: Rng>(
+    blocks: &mut [[B; BLOCK_SIZE]],
+    key: &[B; KEY_SIZE],
+    rng: &mut R,
+) {
+    MaskedGift128::new(key).encrypt_blocks_in_place(blocks, rng);
+}
+
+pub fn decrypt_masked_blocks_in_place<B: MaskedByte, R: Rng>(
+    blocks: &mut [[B; BLOCK_SIZE]],
+    key: &[B; KEY_SIZE],
+    rng: &mut R,
+) {
+    MaskedGift128::new(key).decrypt_blocks_in_place(blocks, rng);
+}
 
+// Like `encrypt_masked`, but calls the hook during the rounds, e.g. to refresh the masks with
+// `hooks::RefreshPerSbox` or `hooks::RefreshPerQuintupleRound`.
+pub fn encrypt_masked_with_hook<B: MaskedByte, H: RoundHook<B::Word>, R: Rng>(
+    plaintext: &[B],
+    key: &[B; KEY_SIZE],
+    ciphertext: &mut [B],
+    hook: &mut H,
+    rng: &mut R,
+) {
     let round_keys = precompute_masked_round_keys(key);
+    unwrap_encrypt(try_map_blocks(plaintext, ciphertext, |block| {
+        masked_unpack(rounds_with_hook(masked_pack(block), &round_keys, hook, rng))
+    }));
+}
 
-    for (i, chunk) in ciphertext.chunks(BLOCK_SIZE).enumerate() {
-        // TODO: annoying runtime check
-        let ciphertext_block = chunk.try_into().expect("invalid chunk length");
-        let state = inv_rounds(masked_pack(ciphertext_block), &round_keys);
-        plaintext[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE].copy_from_slice(&masked_unpack(state));
-    }
+pub fn decrypt_masked_with_hook<B: MaskedByte, H: RoundHook<B::Word>, R: Rng>(
+    ciphertext: &[B],
+    key: &[B; KEY_SIZE],
+    plaintext: &mut [B],
+    hook: &mut H,
+    rng: &mut R,
+) {
+    let round_keys = precompute_masked_round_keys(key);
+    unwrap_decrypt(try_map_blocks(ciphertext, plaintext, |block| {
+        masked_unpack(inv_rounds_with_hook(
+            masked_pack(block),
+            &round_keys,
+            hook,
+            rng,
+        ))
+    }));
+}
+
+// Like `encrypt_protected`. Only the difference to the decrypted sharing is unmasked, and a faulty
+// ciphertext is replaced by a sharing of zero.
+#[inline]
+pub fn encrypt_masked_protected<B: MaskedByte, R: Rng>(
+    plaintext: &[B],
+    key: &[B; KEY_SIZE],
+    ciphertext: &mut [B],
+    rng: &mut R,
+) -> Result<(), ProtectedError>
+where
+    B::Word: Unmask,
+{
+    encrypt_masked_protected_with_hook(plaintext, key, ciphertext, &mut NoHook, rng)
+}
+
+fn encrypt_masked_protected_with_hook<B: MaskedByte, H: RoundHook<B::Word>, R: Rng>(
+    plaintext: &[B],
+    key: &[B; KEY_SIZE],
+    ciphertext: &mut [B],
+    hook: &mut H,
+    rng: &mut R,
+) -> Result<(), ProtectedError>
+where
+    B::Word: Unmask,
+{
+    let round_keys = precompute_masked_round_keys(key);
+    let mut difference = 0;
+    try_map_blocks(plaintext, ciphertext, |block| {
+        let (state, block_difference) =
+            verified_rounds_with_hook(masked_pack(block), &round_keys, hook, &mut NoHook, rng);
+        difference |= block_difference;
+        masked_unpack(state)
+    })?;
+    check_difference(difference, ciphertext, B::Word::default().into())?;
+    Ok(())
+}
+
+#[inline]
+pub fn decrypt_masked_protected<B: MaskedByte, R: Rng>(
+    ciphertext: &[B],
+    key: &[B; KEY_SIZE],
+    plaintext: &mut [B],
+    rng: &mut R,
+) -> Result<(), ProtectedError>
+where
+    B::Word: Unmask,
+{
+    decrypt_masked_protected_with_hook(ciphertext, key, plaintext, &mut NoHook, rng)
+}
+
+fn decrypt_masked_protected_with_hook<B: MaskedByte, H: RoundHook<B::Word>, R: Rng>(
+    ciphertext: &[B],
+    key: &[B; KEY_SIZE],
+    plaintext: &mut [B],
+    hook: &mut H,
+    rng: &mut R,
+) -> Result<(), ProtectedError>
+where
+    B::Word: Unmask,
+{
+    let round_keys = precompute_masked_round_keys(key);
+    let mut difference = 0;
+    try_map_blocks(ciphertext, plaintext, |block| {
+        let (state, block_difference) =
+            inv_verified_rounds_with_hook(masked_pack(block), &round_keys, hook, &mut NoHook, rng);
+        difference |= block_difference;
+        masked_unpack(state)
+    })?;
+    check_difference(difference, plaintext, B::Word::default().into())?;
+    Ok(())
+}
+
+// Combines masking with the shuffling of `encrypt_shuffled`.
+pub fn encrypt_masked_shuffled<B: MaskedByte, R: Rng>(
+    plaintext: &[B],
+    key: &[B; KEY_SIZE],
+    ciphertext: &mut [B],
+    rng: &mut R,
+) {
+    let round_keys = precompute_masked_round_keys(key);
+    unwrap_encrypt(try_map_blocks(plaintext, ciphertext, |block| {
+        masked_unpack(shuffled_rounds(masked_pack(block), &round_keys, rng))
+    }));
 }
 
 #[must_use]
@@ -144,36 +547,113 @@ pub fn bitsliced_decrypt_block(ciphertext: &Block, round_keys: &RoundKeys<u32>)
     bitsliced_unpack(final_state)
 }
 
+pub fn bitsliced_encrypt_blocks_in_place(blocks: &mut [Block], round_keys: &RoundKeys<u32>) {
+    for block in blocks.iter_mut() {
+        *block = bitsliced_encrypt_block(block, round_keys);
+    }
+}
+
+pub fn bitsliced_decrypt_blocks_in_place(blocks: &mut [Block], round_keys: &RoundKeys<u32>) {
+    for block in blocks.iter_mut() {
+        *block = bitsliced_decrypt_block(block, round_keys);
+    }
+}
+
+// Masks for the state words of `bitsliced_masked_encrypt_block` and
+// `bitsliced_masked_decrypt_block`.
 #[must_use]
-pub fn bitsliced_masked_encrypt_block(
+pub fn random_state_mask<R: Rng>(rng: &mut R) -> (u32, u32, u32, u32) {
+    (
+        rng.next_u32(),
+        rng.next_u32(),
+        rng.next_u32(),
+        rng.next_u32(),
+    )
+}
+
+#[must_use]
+pub fn bitsliced_masked_encrypt_block<R: Rng>(
     plaintext: &Block,
     mask: (u32, u32, u32, u32),
     round_keys: &RoundKeys<BinaryMask<u32>>,
+    rng: &mut R,
 ) -> Block {
     let initial_state = bitsliced_pack(plaintext);
     let initial_masked_state = initial_state.make_shares(mask);
-    let final_masked_state = rounds(initial_masked_state, round_keys);
+    let final_masked_state = rounds_with_hook(initial_masked_state, round_keys, &mut NoHook, rng);
     let final_state = final_masked_state.recover_shares();
     bitsliced_unpack(final_state)
 }
 
 #[must_use]
-pub fn bitsliced_masked_decrypt_block(
+pub fn bitsliced_masked_decrypt_block<R: Rng>(
     ciphertext: &Block,
     mask: (u32, u32, u32, u32),
     round_keys: &RoundKeys<BinaryMask<u32>>,
+    rng: &mut R,
 ) -> Block {
     let initial_state = bitsliced_pack(ciphertext);
     let initial_masked_state = initial_state.make_shares(mask);
-    let final_masked_state = inv_rounds(initial_masked_state, round_keys);
+    let final_masked_state =
+        inv_rounds_with_hook(initial_masked_state, round_keys, &mut NoHook, rng);
     let final_state = final_masked_state.recover_shares();
     bitsliced_unpack(final_state)
 }
 
+// Unlike the functions above, these keep the output masked so that it can be chained through a
+// mode of operation.
+#[must_use]
+pub fn bitsliced_encrypt_shared_block<R: Rng>(
+    plaintext: &MaskedBlock,
+    round_keys: &RoundKeys<BinaryMask<u32>>,
+    rng: &mut R,
+) -> MaskedBlock {
+    let initial_state = bitsliced_masked_pack(plaintext);
+    let final_state = rounds_with_hook(initial_state, round_keys, &mut NoHook, rng);
+    bitsliced_masked_unpack(final_state)
+}
+
+#[must_use]
+pub fn bitsliced_decrypt_shared_block<R: Rng>(
+    ciphertext: &MaskedBlock,
+    round_keys: &RoundKeys<BinaryMask<u32>>,
+    rng: &mut R,
+) -> MaskedBlock {
+    let initial_state = bitsliced_masked_pack(ciphertext);
+    let final_state = inv_rounds_with_hook(initial_state, round_keys, &mut NoHook, rng);
+    bitsliced_masked_unpack(final_state)
+}
+
+pub fn bitsliced_encrypt_shared_blocks_in_place<R: Rng>(
+    blocks: &mut [MaskedBlock],
+    round_keys: &RoundKeys<BinaryMask<u32>>,
+    rng: &mut R,
+) {
+    for block in blocks.iter_mut() {
+        *block = bitsliced_encrypt_shared_block(block, round_keys, rng);
+    }
+}
+
+pub fn bitsliced_decrypt_shared_blocks_in_place<R: Rng>(
+    blocks: &mut [MaskedBlock],
+    round_keys: &RoundKeys<BinaryMask<u32>>,
+    rng: &mut R,
+) {
+    for block in blocks.iter_mut() {
+        *block = bitsliced_decrypt_shared_block(block, round_keys, rng);
+    }
+}
+
 #[cfg(test)]
-mod tests {
-    use crate::gift128::key_schedule::mask_key;
+pub(crate) mod tests {
+    use crate::gift128::fault::BitFlip;
+    use crate::gift128::hooks::{RefreshPerQuintupleRound, RefreshPerSbox};
+    use crate::gift128::key_schedule::{
+        dom_share_key, dom_share_key_with_rng, mask_key, mask_key_with_rng, share_key,
+        share_key_with_rng, ti_share_key, ti_share_key_with_rng,
+    };
     use crate::gift128::*;
+    use crate::random::XorShiftRng;
 
     struct TestTriple {
         key: Key,
@@ -226,6 +706,11 @@ mod tests {
         },
     ];
 
+    // The vector shared by the other test modules.
+    pub(crate) const KEY: Key = CASES[2].key;
+    pub(crate) const PLAINTEXT: Block = CASES[2].plaintext;
+    pub(crate) const CIPHERTEXT: Block = CASES[2].ciphertext;
+
     #[test]
     fn test_encrypt() {
         for case in &CASES {
@@ -246,6 +731,7 @@ mod tests {
 
     #[test]
     fn test_masked_encrypt() {
+        let mut rng = XorShiftRng::new(1);
         let plaintext_masks = [
             0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf,
             0xa1, 0xb4,
@@ -258,7 +744,12 @@ mod tests {
             let masked_key = mask_key(&case.key, &key_masks);
             let mut masked_ciphertext = [Default::default(); BLOCK_SIZE];
             let masked_plaintext = mask_block(&case.plaintext, &plaintext_masks);
-            encrypt_masked(&masked_plaintext, &masked_key, &mut masked_ciphertext);
+            encrypt_masked(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            );
             let ciphertext = unmask_block(&masked_ciphertext);
             assert_eq!(ciphertext, case.ciphertext);
         }
@@ -266,6 +757,7 @@ mod tests {
 
     #[test]
     fn test_masked_decrypt() {
+        let mut rng = XorShiftRng::new(1);
         let ciphertext_masks = [
             0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf,
             0xa1, 0xb4,
@@ -278,12 +770,597 @@ mod tests {
             let masked_key = mask_key(&case.key, &key_masks);
             let masked_ciphertext = mask_block(&case.ciphertext, &ciphertext_masks);
             let mut masked_plaintext = [Default::default(); BLOCK_SIZE];
-            decrypt_masked(&masked_ciphertext, &masked_key, &mut masked_plaintext);
+            decrypt_masked(
+                &masked_ciphertext,
+                &masked_key,
+                &mut masked_plaintext,
+                &mut rng,
+            );
             let plaintext = unmask_block(&masked_plaintext);
             assert_eq!(plaintext, case.plaintext);
         }
     }
 
+    fn shared_encrypt_decrypt<const N: usize>() {
+        let mut rng = XorShiftRng::new(1);
+        let masks = [
+            [
+                0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf,
+                0xa1, 0xb4,
+            ],
+            [
+                0x1d, 0x54, 0xf0, 0xae, 0x54, 0x0a, 0xaf, 0x8c, 0xb3, 0xd7, 0x7d, 0x46, 0x4a, 0xac,
+                0xa1, 0xb4,
+            ],
+            [
+                0xa2, 0x4e, 0x71, 0x0c, 0x93, 0x6b, 0xde, 0x07, 0x38, 0xc5, 0x19, 0xf4, 0x60, 0x8d,
+                0x2b, 0xe9,
+            ],
+        ];
+        for case in &CASES {
+            let shared_key: SharedKey<N> = share_key(&case.key, &masks[..N - 1]);
+            let shared_plaintext: SharedBlock<N> = share_block(&case.plaintext, &masks[4 - N..]);
+            let mut shared_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked(
+                &shared_plaintext,
+                &shared_key,
+                &mut shared_ciphertext,
+                &mut rng,
+            );
+            assert_eq!(unshare_block(&shared_ciphertext), case.ciphertext);
+
+            let mut shared_plaintext = [Default::default(); BLOCK_SIZE];
+            decrypt_masked(
+                &shared_ciphertext,
+                &shared_key,
+                &mut shared_plaintext,
+                &mut rng,
+            );
+            assert_eq!(unshare_block(&shared_plaintext), case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_shared_encrypt_decrypt() {
+        shared_encrypt_decrypt::<2>();
+        shared_encrypt_decrypt::<3>();
+        shared_encrypt_decrypt::<4>();
+    }
+
+    #[test]
+    fn test_try_encrypt_decrypt() {
+        let mut rng = XorShiftRng::new(1);
+        for case in CASES {
+            let mut ciphertext = [0; BLOCK_SIZE];
+            assert_eq!(
+                try_encrypt(&case.plaintext, &case.key, &mut ciphertext),
+                Ok(())
+            );
+            assert_eq!(ciphertext, case.ciphertext);
+            let mut plaintext = [0; BLOCK_SIZE];
+            assert_eq!(
+                try_decrypt(&case.ciphertext, &case.key, &mut plaintext),
+                Ok(())
+            );
+            assert_eq!(plaintext, case.plaintext);
+        }
+
+        let key = CASES[0].key;
+        let mut output = [0xa5; 2 * BLOCK_SIZE];
+        assert_eq!(
+            try_encrypt(&[0; BLOCK_SIZE + 1], &key, &mut output[..BLOCK_SIZE + 1]),
+            Err(Gift128Error::InvalidLength)
+        );
+        assert_eq!(
+            try_decrypt(&[0; BLOCK_SIZE], &key, &mut output),
+            Err(Gift128Error::LengthMismatch)
+        );
+        assert_eq!(output, [0xa5; 2 * BLOCK_SIZE]);
+
+        let masked_key = mask_key(&key, &[0x5a; 16]);
+        let mut masked_output: MaskedBlock = Default::default();
+        assert_eq!(
+            try_encrypt_masked(
+                &[Default::default(); 8],
+                &masked_key,
+                &mut masked_output[..8],
+                &mut rng
+            ),
+            Err(Gift128Error::InvalidLength)
+        );
+        let masked_plaintext = mask_block(&CASES[0].plaintext, &[0xa5; 16]);
+        assert_eq!(
+            try_encrypt_masked(&masked_plaintext, &masked_key, &mut masked_output, &mut rng),
+            Ok(())
+        );
+        assert_eq!(unmask_block(&masked_output), CASES[0].ciphertext);
+        let mut masked_decrypted: MaskedBlock = Default::default();
+        assert_eq!(
+            try_decrypt_masked(
+                &masked_output,
+                &masked_key,
+                &mut masked_decrypted[..8],
+                &mut rng
+            ),
+            Err(Gift128Error::LengthMismatch)
+        );
+        assert_eq!(
+            try_decrypt_masked(&masked_output, &masked_key, &mut masked_decrypted, &mut rng),
+            Ok(())
+        );
+        assert_eq!(unmask_block(&masked_decrypted), CASES[0].plaintext);
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_in_place() {
+        let mut plaintext = [0; 3 * BLOCK_SIZE];
+        for (i, byte) in plaintext.iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        let key = CASES[2].key;
+        let mut expected = [0; 3 * BLOCK_SIZE];
+        encrypt(&plaintext, &key, &mut expected);
+
+        let mut buffer = plaintext;
+        encrypt_in_place(&mut buffer, &key);
+        assert_eq!(buffer, expected);
+        decrypt_in_place(&mut buffer, &key);
+        assert_eq!(buffer, plaintext);
+
+        let mut blocks = CASES.map(|case| case.plaintext);
+        for (block, case) in blocks.iter_mut().zip(&CASES) {
+            encrypt_blocks_in_place(core::slice::from_mut(block), &case.key);
+            assert_eq!(*block, case.ciphertext);
+            decrypt_blocks_in_place(core::slice::from_mut(block), &case.key);
+            assert_eq!(*block, case.plaintext);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_encrypt_in_place_invalid_length() {
+        encrypt_in_place(&mut [0; BLOCK_SIZE + 1], &CASES[0].key);
+    }
+
+    #[test]
+    fn test_masked_encrypt_decrypt_in_place() {
+        let mut rng = XorShiftRng::new(1);
+        let key_masks = [0x5a; 16];
+        for case in &CASES {
+            let masked_key = mask_key(&case.key, &key_masks);
+            let mut buffer = mask_block(&case.plaintext, &[0xa5; 16]);
+            encrypt_masked_in_place(&mut buffer, &masked_key, &mut rng);
+            assert_eq!(unmask_block(&buffer), case.ciphertext);
+            decrypt_masked_in_place(&mut buffer, &masked_key, &mut rng);
+            assert_eq!(unmask_block(&buffer), case.plaintext);
+
+            let mut blocks = [mask_block(&case.plaintext, &[0x3c; 16]); 2];
+            encrypt_masked_blocks_in_place(&mut blocks, &masked_key, &mut rng);
+            assert!(blocks
+                .iter()
+                .all(|block| unmask_block(block) == case.ciphertext));
+            decrypt_masked_blocks_in_place(&mut blocks, &masked_key, &mut rng);
+            assert!(blocks
+                .iter()
+                .all(|block| unmask_block(block) == case.plaintext));
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_parallel() {
+        let key = CASES[2].key;
+        let mut plaintext = [0; 5 * BLOCK_SIZE];
+        for (i, byte) in plaintext.iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        for blocks in 1..=5 {
+            let plaintext = &plaintext[..blocks * BLOCK_SIZE];
+            let mut expected = [0; 5 * BLOCK_SIZE];
+            let expected = &mut expected[..blocks * BLOCK_SIZE];
+            encrypt(plaintext, &key, expected);
+
+            let mut ciphertext = [0; 5 * BLOCK_SIZE];
+            let ciphertext = &mut ciphertext[..blocks * BLOCK_SIZE];
+            encrypt_parallel(plaintext, &key, ciphertext);
+            assert_eq!(ciphertext, expected);
+
+            let mut decrypted = [0; 5 * BLOCK_SIZE];
+            let decrypted = &mut decrypted[..blocks * BLOCK_SIZE];
+            decrypt_parallel(ciphertext, &key, decrypted);
+            assert_eq!(decrypted, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_lanes() {
+        let key = CASES[2].key;
+        let mut plaintext = [0; 11 * BLOCK_SIZE];
+        for (i, byte) in plaintext.iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        for blocks in 1..=11 {
+            let plaintext = &plaintext[..blocks * BLOCK_SIZE];
+            let mut expected = [0; 11 * BLOCK_SIZE];
+            let expected = &mut expected[..blocks * BLOCK_SIZE];
+            encrypt(plaintext, &key, expected);
+
+            let mut ciphertext = [0; 11 * BLOCK_SIZE];
+            let ciphertext = &mut ciphertext[..blocks * BLOCK_SIZE];
+            encrypt_lanes::<4>(plaintext, &key, ciphertext);
+            assert_eq!(ciphertext, expected);
+            encrypt_lanes::<8>(plaintext, &key, ciphertext);
+            assert_eq!(ciphertext, expected);
+
+            let mut decrypted = [0; 11 * BLOCK_SIZE];
+            let decrypted = &mut decrypted[..blocks * BLOCK_SIZE];
+            decrypt_lanes::<4>(ciphertext, &key, decrypted);
+            assert_eq!(decrypted, plaintext);
+            decrypt_lanes::<8>(ciphertext, &key, decrypted);
+            assert_eq!(decrypted, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_protected() {
+        for case in &CASES {
+            let mut ciphertext = [0; BLOCK_SIZE];
+            assert_eq!(
+                encrypt_protected(&case.plaintext, &case.key, &mut ciphertext),
+                Ok(())
+            );
+            assert_eq!(ciphertext, case.ciphertext);
+
+            let mut plaintext = [0; BLOCK_SIZE];
+            assert_eq!(
+                decrypt_protected(&case.ciphertext, &case.key, &mut plaintext),
+                Ok(())
+            );
+            assert_eq!(plaintext, case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_infective() {
+        let mut rng = XorShiftRng::new(0x165667b1);
+        for case in &CASES {
+            let mut ciphertext = [0; BLOCK_SIZE];
+            encrypt_infective(&case.plaintext, &case.key, &mut ciphertext, &mut rng);
+            assert_eq!(ciphertext, case.ciphertext);
+
+            let mut plaintext = [0; BLOCK_SIZE];
+            decrypt_infective(&case.ciphertext, &case.key, &mut plaintext, &mut rng);
+            assert_eq!(plaintext, case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_shuffled() {
+        let mut rng = XorShiftRng::new(0x9e3779b9);
+        for case in &CASES {
+            let mut ciphertext = [0; BLOCK_SIZE];
+            encrypt_shuffled(&case.plaintext, &case.key, &mut ciphertext, &mut rng);
+            assert_eq!(ciphertext, case.ciphertext);
+
+            let masked_key = mask_key_with_rng(&case.key, &mut rng);
+            let masked_plaintext = mask_block_with_rng(&case.plaintext, &mut rng);
+            let mut masked_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked_shuffled(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            );
+            assert_eq!(unmask_block(&masked_ciphertext), case.ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_masked_encrypt_decrypt_protected() {
+        let mut rng = XorShiftRng::new(0x27d4eb2f);
+        for case in &CASES {
+            let masked_key = mask_key_with_rng(&case.key, &mut rng);
+            let masked_plaintext = mask_block_with_rng(&case.plaintext, &mut rng);
+            let mut masked_ciphertext = [Default::default(); BLOCK_SIZE];
+            assert_eq!(
+                encrypt_masked_protected(
+                    &masked_plaintext,
+                    &masked_key,
+                    &mut masked_ciphertext,
+                    &mut rng
+                ),
+                Ok(())
+            );
+            assert_eq!(unmask_block(&masked_ciphertext), case.ciphertext);
+
+            let shared_key: SharedKey<3> = share_key_with_rng(&case.key, &mut rng);
+            let shared_ciphertext: SharedBlock<3> =
+                share_block_with_rng(&case.ciphertext, &mut rng);
+            let mut shared_plaintext = [Default::default(); BLOCK_SIZE];
+            assert_eq!(
+                decrypt_masked_protected(
+                    &shared_ciphertext,
+                    &shared_key,
+                    &mut shared_plaintext,
+                    &mut rng
+                ),
+                Ok(())
+            );
+            assert_eq!(unshare_block(&shared_plaintext), case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_protected_detects_faults() {
+        for case in &CASES {
+            let plaintext = [case.plaintext; 2];
+            let mut ciphertext = [0xa5; 2 * BLOCK_SIZE];
+            assert_eq!(
+                encrypt_protected_with_hook(
+                    plaintext.as_flattened(),
+                    &case.key,
+                    &mut ciphertext,
+                    &mut BitFlip { round: 20 }
+                ),
+                Err(ProtectedError::Fault(FaultDetected))
+            );
+            assert_eq!(ciphertext, [0; 2 * BLOCK_SIZE]);
+
+            let ciphertext = [case.ciphertext; 2];
+            let mut plaintext = [0xa5; 2 * BLOCK_SIZE];
+            assert_eq!(
+                decrypt_protected_with_hook(
+                    ciphertext.as_flattened(),
+                    &case.key,
+                    &mut plaintext,
+                    &mut BitFlip { round: 20 }
+                ),
+                Err(ProtectedError::Fault(FaultDetected))
+            );
+            assert_eq!(plaintext, [0; 2 * BLOCK_SIZE]);
+        }
+    }
+
+    #[test]
+    fn test_masked_protected_detects_faults() {
+        let mut rng = XorShiftRng::new(0x165667b1);
+        for case in &CASES {
+            let masked_key = mask_key_with_rng(&case.key, &mut rng);
+            let masked_plaintext = mask_block_with_rng(&case.plaintext, &mut rng);
+            let mut masked_ciphertext = mask_block_with_rng(&case.ciphertext, &mut rng);
+            assert_eq!(
+                encrypt_masked_protected_with_hook(
+                    &masked_plaintext,
+                    &masked_key,
+                    &mut masked_ciphertext,
+                    &mut BitFlip { round: 0 },
+                    &mut rng
+                ),
+                Err(ProtectedError::Fault(FaultDetected))
+            );
+            assert_eq!(unmask_block(&masked_ciphertext), [0; BLOCK_SIZE]);
+
+            let shared_key: SharedKey<3> = share_key_with_rng(&case.key, &mut rng);
+            let shared_ciphertext: SharedBlock<3> =
+                share_block_with_rng(&case.ciphertext, &mut rng);
+            let mut shared_plaintext = share_block_with_rng(&case.plaintext, &mut rng);
+            assert_eq!(
+                decrypt_masked_protected_with_hook(
+                    &shared_ciphertext,
+                    &shared_key,
+                    &mut shared_plaintext,
+                    &mut BitFlip { round: 39 },
+                    &mut rng
+                ),
+                Err(ProtectedError::Fault(FaultDetected))
+            );
+            assert_eq!(unshare_block(&shared_plaintext), [0; BLOCK_SIZE]);
+        }
+    }
+
+    #[test]
+    fn test_protected_lengths() {
+        let key = CASES[0].key;
+        let mut output = [0xa5; 2 * BLOCK_SIZE];
+        assert_eq!(
+            encrypt_protected(&[0; BLOCK_SIZE + 1], &key, &mut output[..BLOCK_SIZE + 1]),
+            Err(ProtectedError::Length(Gift128Error::InvalidLength))
+        );
+        assert_eq!(
+            decrypt_protected(&[0; BLOCK_SIZE], &key, &mut output),
+            Err(ProtectedError::Length(Gift128Error::LengthMismatch))
+        );
+        assert_eq!(output, [0xa5; 2 * BLOCK_SIZE]);
+
+        let mut rng = XorShiftRng::new(0x85ebca77);
+        let masked_key = mask_key(&key, &[0x5a; 16]);
+        let masked_block = mask_block(&CASES[0].plaintext, &[0xa5; 16]);
+        let mut masked_output = mask_block(&[0xa5; BLOCK_SIZE], &[0x3c; 16]);
+        assert_eq!(
+            encrypt_masked_protected(
+                &masked_block[..8],
+                &masked_key,
+                &mut masked_output[..8],
+                &mut rng
+            ),
+            Err(ProtectedError::Length(Gift128Error::InvalidLength))
+        );
+        assert_eq!(
+            decrypt_masked_protected(
+                &masked_block,
+                &masked_key,
+                &mut masked_output[..8],
+                &mut rng
+            ),
+            Err(ProtectedError::Length(Gift128Error::LengthMismatch))
+        );
+        assert_eq!(unmask_block(&masked_output), [0xa5; BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn test_dom_encrypt_decrypt() {
+        let mut rng = XorShiftRng::new(0xc2b2ae35);
+        for case in &CASES {
+            let dom_key: DomKey<3> = dom_share_key_with_rng(&case.key, &mut rng);
+            let dom_plaintext: DomBlock<3> = dom_share_block_with_rng(&case.plaintext, &mut rng);
+            let mut dom_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked(&dom_plaintext, &dom_key, &mut dom_ciphertext, &mut rng);
+            assert_eq!(dom_unshare_block(&dom_ciphertext), case.ciphertext);
+
+            let masks = [case.key, case.plaintext];
+            let dom_key: DomKey<3> = dom_share_key(&case.key, &masks);
+            let dom_ciphertext: DomBlock<3> = dom_share_block(&case.ciphertext, &masks);
+            let mut dom_plaintext = [Default::default(); BLOCK_SIZE];
+            decrypt_masked(&dom_ciphertext, &dom_key, &mut dom_plaintext, &mut rng);
+            assert_eq!(dom_unshare_block(&dom_plaintext), case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_ti_encrypt_decrypt() {
+        let masks = [
+            [
+                0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf,
+                0xa1, 0xb4,
+            ],
+            [
+                0xa2, 0x4e, 0x71, 0x0c, 0x93, 0x6b, 0xde, 0x07, 0x38, 0xc5, 0x19, 0xf4, 0x60, 0x8d,
+                0x2b, 0xe9,
+            ],
+        ];
+        let mut rng = XorShiftRng::new(0x85ebca6b);
+        for case in &CASES {
+            let ti_key = ti_share_key(&case.key, &masks);
+            let ti_plaintext = ti_share_block(&case.plaintext, &masks);
+            let mut ti_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked(&ti_plaintext, &ti_key, &mut ti_ciphertext, &mut rng);
+            assert_eq!(ti_unshare_block(&ti_ciphertext), case.ciphertext);
+
+            let ti_key = ti_share_key_with_rng(&case.key, &mut rng);
+            let ti_ciphertext = ti_share_block_with_rng(&case.ciphertext, &mut rng);
+            let mut ti_plaintext = [Default::default(); BLOCK_SIZE];
+            decrypt_masked(&ti_ciphertext, &ti_key, &mut ti_plaintext, &mut rng);
+            assert_eq!(ti_unshare_block(&ti_plaintext), case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_masked_encrypt_decrypt_with_refresh() {
+        let mut rng = XorShiftRng::new(0x2545f491);
+        for case in &CASES {
+            let masked_key = mask_key_with_rng(&case.key, &mut rng);
+            let masked_plaintext = mask_block_with_rng(&case.plaintext, &mut rng);
+
+            let mut unrefreshed = [Default::default(); BLOCK_SIZE];
+            encrypt_masked(&masked_plaintext, &masked_key, &mut unrefreshed, &mut rng);
+            let mut per_sbox = [Default::default(); BLOCK_SIZE];
+            encrypt_masked_with_hook(
+                &masked_plaintext,
+                &masked_key,
+                &mut per_sbox,
+                &mut RefreshPerSbox,
+                &mut rng,
+            );
+            let mut per_quintuple_round = [Default::default(); BLOCK_SIZE];
+            encrypt_masked_with_hook(
+                &masked_plaintext,
+                &masked_key,
+                &mut per_quintuple_round,
+                &mut RefreshPerQuintupleRound,
+                &mut rng,
+            );
+
+            assert_eq!(unmask_block(&per_sbox), case.ciphertext);
+            assert_eq!(unmask_block(&per_quintuple_round), case.ciphertext);
+            // The refreshed ciphertexts are shared differently.
+            assert!(per_sbox
+                .iter()
+                .zip(&unrefreshed)
+                .any(|(refreshed, unrefreshed)| refreshed.1 != unrefreshed.1));
+
+            let mut plaintext = [Default::default(); BLOCK_SIZE];
+            decrypt_masked_with_hook(
+                &per_sbox,
+                &masked_key,
+                &mut plaintext,
+                &mut RefreshPerSbox,
+                &mut rng,
+            );
+            assert_eq!(unmask_block(&plaintext), case.plaintext);
+            decrypt_masked_with_hook(
+                &per_sbox,
+                &masked_key,
+                &mut plaintext,
+                &mut RefreshPerQuintupleRound,
+                &mut rng,
+            );
+            assert_eq!(unmask_block(&plaintext), case.plaintext);
+
+            let shared_key: SharedKey<3> = share_key_with_rng(&case.key, &mut rng);
+            let shared_plaintext: SharedBlock<3> = share_block_with_rng(&case.plaintext, &mut rng);
+            let mut shared_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked_with_hook(
+                &shared_plaintext,
+                &shared_key,
+                &mut shared_ciphertext,
+                &mut RefreshPerSbox,
+                &mut rng,
+            );
+            assert_eq!(unshare_block(&shared_ciphertext), case.ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_masked_encrypt_with_rng() {
+        let mut rng = XorShiftRng::new(0x9e3779b9);
+        for case in &CASES {
+            let masked_key = mask_key_with_rng(&case.key, &mut rng);
+            let masked_plaintext = mask_block_with_rng(&case.plaintext, &mut rng);
+            let mut masked_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            );
+            assert_eq!(unmask_block(&masked_ciphertext), case.ciphertext);
+
+            let shared_key: SharedKey<3> = share_key_with_rng(&case.key, &mut rng);
+            let shared_plaintext: SharedBlock<3> = share_block_with_rng(&case.plaintext, &mut rng);
+            let mut shared_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked(
+                &shared_plaintext,
+                &shared_key,
+                &mut shared_ciphertext,
+                &mut rng,
+            );
+            assert_eq!(unshare_block(&shared_ciphertext), case.ciphertext);
+
+            let round_keys = precompute_masked_round_keys(&masked_key);
+            let ciphertext = bitsliced_masked_encrypt_block(
+                &case.plaintext,
+                random_state_mask(&mut rng),
+                &round_keys,
+                &mut rng,
+            );
+            let plaintext = bitsliced_masked_decrypt_block(
+                &ciphertext,
+                random_state_mask(&mut rng),
+                &round_keys,
+                &mut rng,
+            );
+            assert_eq!(plaintext, case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_masks_with_rng_are_reproducible() {
+        let masked_block1 = mask_block_with_rng(&CASES[0].plaintext, &mut XorShiftRng::new(7));
+        let masked_block2 = mask_block_with_rng(&CASES[0].plaintext, &mut XorShiftRng::new(7));
+        for (share1, share2) in masked_block1.iter().zip(&masked_block2) {
+            assert_eq!((share1.0, share1.1), (share2.0, share2.1));
+        }
+    }
+
     #[test]
     fn test_bitsliced_block_encrypt_decrypt() {
         for case in &CASES {
@@ -295,8 +1372,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bitsliced_blocks_in_place() {
+        let mut rng = XorShiftRng::new(1);
+        let key_masks = [0x5a; 16];
+        for case in &CASES {
+            let round_keys = precompute_round_keys(&case.key);
+            let mut blocks = [case.plaintext; 2];
+            bitsliced_encrypt_blocks_in_place(&mut blocks, &round_keys);
+            let expected = bitsliced_encrypt_block(&case.plaintext, &round_keys);
+            assert_eq!(blocks, [expected; 2]);
+            bitsliced_decrypt_blocks_in_place(&mut blocks, &round_keys);
+            assert_eq!(blocks, [case.plaintext; 2]);
+
+            let masked_key = mask_key(&case.key, &key_masks);
+            let masked_round_keys = precompute_masked_round_keys(&masked_key);
+            let mut masked_blocks = [mask_block(&case.plaintext, &[0xa5; 16]); 2];
+            bitsliced_encrypt_shared_blocks_in_place(
+                &mut masked_blocks,
+                &masked_round_keys,
+                &mut rng,
+            );
+            assert!(masked_blocks
+                .iter()
+                .all(|block| unmask_block(block) == expected));
+            bitsliced_decrypt_shared_blocks_in_place(
+                &mut masked_blocks,
+                &masked_round_keys,
+                &mut rng,
+            );
+            assert!(masked_blocks
+                .iter()
+                .all(|block| unmask_block(block) == case.plaintext));
+        }
+    }
+
     #[test]
     fn test_masked_bitsliced_block_encrypt_decrypt() {
+        let mut rng = XorShiftRng::new(1);
         let encrypt_masks = (0x1d54f08eu32, 0x550aaf8cu32, 0xb3d27d46u32, 0x4aafa1b4u32);
         let decrypt_masks = (0x1d56608eu32, 0x550aaf8cu32, 0xb3d27d98u32, 0x4fffa1b4u32);
         let key_masks = [
@@ -306,14 +1419,47 @@ mod tests {
         for case in &CASES {
             let masked_key = mask_key(&case.key, &key_masks);
             let round_keys = precompute_masked_round_keys(&masked_key);
-            let ciphertext =
-                bitsliced_masked_encrypt_block(&case.plaintext, encrypt_masks, &round_keys);
-            let plaintext = bitsliced_masked_decrypt_block(&ciphertext, decrypt_masks, &round_keys);
+            let ciphertext = bitsliced_masked_encrypt_block(
+                &case.plaintext,
+                encrypt_masks,
+                &round_keys,
+                &mut rng,
+            );
+            let plaintext =
+                bitsliced_masked_decrypt_block(&ciphertext, decrypt_masks, &round_keys, &mut rng);
 
             assert_eq!(case.plaintext, plaintext);
         }
     }
 
+    #[test]
+    fn test_bitsliced_masked_block_chaining() {
+        let mut rng = XorShiftRng::new(1);
+        let block_masks = [
+            0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf,
+            0xa1, 0xb4,
+        ];
+        let key_masks = [
+            0x1d, 0x54, 0xf0, 0xae, 0x54, 0x0a, 0xaf, 0x8c, 0xb3, 0xd7, 0x7d, 0x46, 0x4a, 0xac,
+            0xa1, 0xb4,
+        ];
+        for case in &CASES {
+            let round_keys = precompute_round_keys(&case.key);
+            let masked_key = mask_key(&case.key, &key_masks);
+            let masked_round_keys = precompute_masked_round_keys(&masked_key);
+
+            let masked_plaintext = mask_block(&case.plaintext, &block_masks);
+            let masked_ciphertext =
+                bitsliced_encrypt_shared_block(&masked_plaintext, &masked_round_keys, &mut rng);
+            let ciphertext = bitsliced_encrypt_block(&case.plaintext, &round_keys);
+            assert_eq!(unmask_block(&masked_ciphertext), ciphertext);
+
+            let masked_plaintext =
+                bitsliced_decrypt_shared_block(&masked_ciphertext, &masked_round_keys, &mut rng);
+            assert_eq!(unmask_block(&masked_plaintext), case.plaintext);
+        }
+    }
+
     #[test]
     fn test_mask_block() {
         let block_masks = [
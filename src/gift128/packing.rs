@@ -1,9 +1,10 @@
 use crate::gift128::masking::BinaryMask;
+use crate::gift128::traits::MaskedByte;
 use crate::gift128::{Block, MaskedBlock, State, BLOCK_SIZE};
 use crate::{swap_move, swap_move_single};
 
 #[must_use]
-pub(super) fn pack(input: &Block) -> State<u32> {
+pub(crate) fn pack(input: &Block) -> State<u32> {
     let mut s0 = ((input[6] as u32) << 24)
         | ((input[7] as u32) << 16)
         | ((input[14] as u32) << 8)
@@ -41,7 +42,7 @@ pub(super) fn pack(input: &Block) -> State<u32> {
 }
 
 #[must_use]
-pub(super) fn unpack(state: State<u32>) -> Block {
+pub(crate) fn unpack(state: State<u32>) -> Block {
     let State(mut s0, mut s1, mut s2, mut s3) = state;
 
     // TODO: use macro for swap_move
@@ -81,23 +82,23 @@ pub(super) fn unpack(state: State<u32>) -> Block {
 }
 
 #[must_use]
-pub(super) fn masked_pack(input: &MaskedBlock) -> State<BinaryMask<u32>> {
-    let mut s0 = ((BinaryMask::<u32>::from(input[6])) << 24)
-        | ((BinaryMask::<u32>::from(input[7])) << 16)
-        | ((BinaryMask::<u32>::from(input[14])) << 8)
-        | BinaryMask::<u32>::from(input[15]);
-    let mut s1 = ((BinaryMask::<u32>::from(input[4])) << 24)
-        | ((BinaryMask::<u32>::from(input[5])) << 16)
-        | ((BinaryMask::<u32>::from(input[12])) << 8)
-        | (BinaryMask::<u32>::from(input[13]));
-    let mut s2 = ((BinaryMask::<u32>::from(input[2])) << 24)
-        | ((BinaryMask::<u32>::from(input[3])) << 16)
-        | ((BinaryMask::<u32>::from(input[10])) << 8)
-        | (BinaryMask::<u32>::from(input[11]));
-    let mut s3 = ((BinaryMask::<u32>::from(input[0])) << 24)
-        | ((BinaryMask::<u32>::from(input[1])) << 16)
-        | ((BinaryMask::<u32>::from(input[8])) << 8)
-        | (BinaryMask::<u32>::from(input[9]));
+pub(crate) fn masked_pack<B: MaskedByte>(input: &[B; BLOCK_SIZE]) -> State<B::Word> {
+    let mut s0 = ((B::Word::from(input[6])) << 24)
+        ^ ((B::Word::from(input[7])) << 16)
+        ^ ((B::Word::from(input[14])) << 8)
+        ^ B::Word::from(input[15]);
+    let mut s1 = ((B::Word::from(input[4])) << 24)
+        ^ ((B::Word::from(input[5])) << 16)
+        ^ ((B::Word::from(input[12])) << 8)
+        ^ (B::Word::from(input[13]));
+    let mut s2 = ((B::Word::from(input[2])) << 24)
+        ^ ((B::Word::from(input[3])) << 16)
+        ^ ((B::Word::from(input[10])) << 8)
+        ^ (B::Word::from(input[11]));
+    let mut s3 = ((B::Word::from(input[0])) << 24)
+        ^ ((B::Word::from(input[1])) << 16)
+        ^ ((B::Word::from(input[8])) << 8)
+        ^ (B::Word::from(input[9]));
 
     s0 = swap_move_single(s0, 0x0a0a0a0a, 3);
     s0 = swap_move_single(s0, 0x00cc00cc, 6);
@@ -119,7 +120,7 @@ pub(super) fn masked_pack(input: &MaskedBlock) -> State<BinaryMask<u32>> {
 }
 
 #[must_use]
-pub(super) fn masked_unpack(state: State<BinaryMask<u32>>) -> MaskedBlock {
+pub(crate) fn masked_unpack<B: MaskedByte>(state: State<B::Word>) -> [B; BLOCK_SIZE] {
     let State(mut s0, mut s1, mut s2, mut s3) = state;
 
     // TODO: use macro for swap_move
@@ -159,7 +160,7 @@ pub(super) fn masked_unpack(state: State<BinaryMask<u32>>) -> MaskedBlock {
 }
 
 #[must_use]
-pub(super) fn bitsliced_pack(input: &Block) -> State<u32> {
+pub(crate) fn bitsliced_pack(input: &Block) -> State<u32> {
     let s0 = u32::from_le_bytes([input[0], input[1], input[2], input[3]]).swap_bytes();
     let s1 = u32::from_le_bytes([input[4], input[5], input[6], input[7]]).swap_bytes();
     let s2 = u32::from_le_bytes([input[8], input[9], input[10], input[11]]).swap_bytes();
@@ -169,7 +170,7 @@ pub(super) fn bitsliced_pack(input: &Block) -> State<u32> {
 }
 
 #[must_use]
-pub(super) fn bitsliced_unpack(state: State<u32>) -> Block {
+pub(crate) fn bitsliced_unpack(state: State<u32>) -> Block {
     let State(s0, s1, s2, s3) = state;
     let mut block = [0; BLOCK_SIZE];
     block[..4].copy_from_slice(&s0.to_be_bytes());
@@ -179,6 +180,32 @@ pub(super) fn bitsliced_unpack(state: State<u32>) -> Block {
     block
 }
 
+// The shares are packed separately so that they are never combined.
+#[must_use]
+pub(crate) fn bitsliced_masked_pack(input: &MaskedBlock) -> State<BinaryMask<u32>> {
+    let word = |i: usize| {
+        BinaryMask(
+            u32::from_be_bytes([input[i].0, input[i + 1].0, input[i + 2].0, input[i + 3].0]),
+            u32::from_be_bytes([input[i].1, input[i + 1].1, input[i + 2].1, input[i + 3].1]),
+        )
+    };
+
+    State(word(0), word(4), word(8), word(12))
+}
+
+#[must_use]
+pub(crate) fn bitsliced_masked_unpack(state: State<BinaryMask<u32>>) -> MaskedBlock {
+    let State(s0, s1, s2, s3) = state;
+    let mut block = [Default::default(); BLOCK_SIZE];
+    for (i, word) in [s0, s1, s2, s3].into_iter().enumerate() {
+        let shares = word.0.to_be_bytes().into_iter().zip(word.1.to_be_bytes());
+        for (j, (share0, share1)) in shares.enumerate() {
+            block[i * 4 + j] = BinaryMask(share0, share1);
+        }
+    }
+    block
+}
+
 #[cfg(test)]
 mod tests {
     use crate::gift128::packing::*;
@@ -211,4 +238,22 @@ mod tests {
         let output = unmask_block(&masked_output);
         assert_eq!(input, output);
     }
+
+    #[test]
+    fn test_bitsliced_masked_pack_unpack() {
+        let input = [
+            0xe3, 0x9c, 0x14, 0x1f, 0xa5, 0x7d, 0xba, 0x43, 0xf0, 0x8a, 0x85, 0xb6, 0xa9, 0x1f,
+            0x86, 0xc1,
+        ];
+        let masks = [
+            0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf,
+            0xa1, 0xb4u8,
+        ];
+        let masked_input = mask_block(&input, &masks);
+        let s = bitsliced_masked_pack(&masked_input);
+        assert_eq!(s.recover_shares(), bitsliced_pack(&input));
+        let masked_output = bitsliced_masked_unpack(s);
+        let output = unmask_block(&masked_output);
+        assert_eq!(input, output);
+    }
 }
@@ -1,14 +1,16 @@
-use core::ops::{BitAnd, BitOr, BitXorAssign, Shl, Shr};
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Shl, Shr};
 
+use crate::gift128::hooks::{NoHook, RoundHook};
 use crate::gift128::key_schedule::RoundKeys;
 use crate::gift128::sbox::{inv_sbox, sbox, SboxTraits};
 use crate::gift128::traits::Rotate;
 use crate::gift128::State;
+use crate::random::{NoRng, Rng};
 use crate::swap_move_single;
 use crate::swapmove::SwapMoveTraits;
 
 pub const ROUNDS: usize = 40;
-const ROUND_CONSTANTS: [u32; ROUNDS] = [
+pub(crate) const ROUND_CONSTANTS: [u32; ROUNDS] = [
     0x10000008, 0x80018000, 0x54000002, 0x01010181, 0x8000001f, 0x10888880, 0x6001e000, 0x51500002,
     0x03030180, 0x8000002f, 0x10088880, 0x60016000, 0x41500002, 0x03030080, 0x80000027, 0x10008880,
     0x4001e000, 0x11500002, 0x03020180, 0x8000002b, 0x10080880, 0x60014000, 0x01400002, 0x02020080,
@@ -51,51 +53,51 @@ where
         + Shr<u32, Output = Self>
         + BitAnd<u32, Output = Self>
         + Shl<u32, Output = Self>
-        + BitOr<Output = Self>,
+        + BitXor<Output = Self>,
 {
     #[inline]
     fn byte_ror_2(self) -> Self {
-        ((self >> 2) & 0x3f3f3f3f) | ((self & 0x03030303) << 6)
+        ((self >> 2) & 0x3f3f3f3f) ^ ((self & 0x03030303) << 6)
     }
 
     #[inline]
     fn byte_ror_4(self) -> Self {
-        ((self >> 4) & 0x0f0f0f0f) | ((self & 0x0f0f0f0f) << 4)
+        ((self >> 4) & 0x0f0f0f0f) ^ ((self & 0x0f0f0f0f) << 4)
     }
 
     #[inline]
     fn byte_ror_6(self) -> Self {
-        ((self >> 6) & 0x03030303) | ((self & 0x3f3f3f3f) << 2)
+        ((self >> 6) & 0x03030303) ^ ((self & 0x3f3f3f3f) << 2)
     }
 
     #[inline]
     fn half_ror_4(self) -> Self {
-        ((self >> 4) & 0x0fff0fff) | ((self & 0x000f000f) << 12)
+        ((self >> 4) & 0x0fff0fff) ^ ((self & 0x000f000f) << 12)
     }
 
     #[inline]
     fn half_ror_8(self) -> Self {
-        ((self >> 8) & 0x00ff00ff) | ((self & 0x00ff00ff) << 8)
+        ((self >> 8) & 0x00ff00ff) ^ ((self & 0x00ff00ff) << 8)
     }
 
     #[inline]
     fn half_ror_12(self) -> Self {
-        ((self >> 12) & 0x000f000f) | ((self & 0x0fff0fff) << 4)
+        ((self >> 12) & 0x000f000f) ^ ((self & 0x0fff0fff) << 4)
     }
 
     #[inline]
     fn nibble_ror_1(self) -> Self {
-        ((self >> 1) & 0x77777777) | ((self & 0x11111111) << 3)
+        ((self >> 1) & 0x77777777) ^ ((self & 0x11111111) << 3)
     }
 
     #[inline]
     fn nibble_ror_2(self) -> Self {
-        ((self >> 2) & 0x33333333) | ((self & 0x33333333) << 2)
+        ((self >> 2) & 0x33333333) ^ ((self & 0x33333333) << 2)
     }
 
     #[inline]
     fn nibble_ror_3(self) -> Self {
-        ((self >> 3) & 0x11111111) | ((self & 0x77777777) << 1)
+        ((self >> 3) & 0x11111111) ^ ((self & 0x77777777) << 1)
     }
 }
 
@@ -109,30 +111,51 @@ impl<T> RoundTraits for T where
 {
 }
 
+#[must_use]
+#[inline(always)]
+fn after_sbox<T, H: RoundHook<T>, R: Rng>(
+    hook: &mut H,
+    round: usize,
+    mut slices: [T; 4],
+    rng: &mut R,
+) -> [T; 4] {
+    hook.after_sbox(round, &mut slices, rng);
+    slices
+}
+
 // TODO: possible to fix size on slices?
 #[must_use]
 #[inline(always)]
-fn quintuple_round<T: RoundTraits>(
+pub(crate) fn quintuple_round<T: RoundTraits, H: RoundHook<T>, R: Rng>(
     state: State<T>,
     round_keys: &[T],
     round_constants: &[u32],
+    round: usize,
+    hook: &mut H,
+    rng: &mut R,
 ) -> State<T> {
     let State(mut s0, mut s1, mut s2, mut s3) = state;
-    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3));
+    hook.before_sbox(round);
+    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3), rng);
+    [s3, s1, s2, s0] = after_sbox(hook, round, [s3, s1, s2, s0], rng);
     s3 = s3.nibble_ror_1();
     s1 = s1.nibble_ror_2();
     s2 = s2.nibble_ror_3();
     s1 ^= round_keys[0];
     s2 ^= round_keys[1];
     s0 ^= round_constants[0];
-    State(s3, s1, s2, s0) = sbox(State(s3, s1, s2, s0));
+    hook.before_sbox(round + 1);
+    State(s3, s1, s2, s0) = sbox(State(s3, s1, s2, s0), rng);
+    [s0, s1, s2, s3] = after_sbox(hook, round + 1, [s0, s1, s2, s3], rng);
     s0 = s0.half_ror_4();
     s1 = s1.half_ror_8();
     s2 = s2.half_ror_12();
     s1 ^= round_keys[2];
     s2 ^= round_keys[3];
     s3 ^= round_constants[1];
-    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3));
+    hook.before_sbox(round + 2);
+    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3), rng);
+    [s3, s1, s2, s0] = after_sbox(hook, round + 2, [s3, s1, s2, s0], rng);
     s3 = s3.rotate_right(16);
     s2 = s2.rotate_right(16);
     s1 = swap_move_single(s1, 0x55555555, 1);
@@ -141,14 +164,18 @@ fn quintuple_round<T: RoundTraits>(
     s1 ^= round_keys[4];
     s2 ^= round_keys[5];
     s0 ^= round_constants[2];
-    State(s3, s1, s2, s0) = sbox(State(s3, s1, s2, s0));
+    hook.before_sbox(round + 3);
+    State(s3, s1, s2, s0) = sbox(State(s3, s1, s2, s0), rng);
+    [s0, s1, s2, s3] = after_sbox(hook, round + 3, [s0, s1, s2, s3], rng);
     s0 = s0.byte_ror_6();
     s1 = s1.byte_ror_4();
     s2 = s2.byte_ror_2();
     s1 ^= round_keys[6];
     s2 ^= round_keys[7];
     s3 ^= round_constants[3];
-    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3));
+    hook.before_sbox(round + 4);
+    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3), rng);
+    [s3, s1, s2, s0] = after_sbox(hook, round + 4, [s3, s1, s2, s0], rng);
     s3 = s3.rotate_right(24);
     s1 = s1.rotate_right(16);
     s2 = s2.rotate_right(8);
@@ -157,18 +184,28 @@ fn quintuple_round<T: RoundTraits>(
     s0 ^= round_constants[4];
     core::mem::swap(&mut s0, &mut s3);
 
+    let mut slices = [s0, s1, s2, s3];
+    hook.after_quintuple_round(round + 4, &mut slices, rng);
+    let [s0, s1, s2, s3] = slices;
+
     State(s0, s1, s2, s3)
 }
 
 // TODO: possible to fix size on slices?
 #[must_use]
 #[inline(always)]
-fn inv_quintuple_round<T: RoundTraits>(
+pub(crate) fn inv_quintuple_round<T: RoundTraits, H: RoundHook<T>, R: Rng>(
     state: State<T>,
     round_keys: &[T],
     round_constants: &[u32],
+    round: usize,
+    hook: &mut H,
+    rng: &mut R,
 ) -> State<T> {
-    let State(mut s0, mut s1, mut s2, mut s3) = state;
+    let State(s0, s1, s2, s3) = state;
+    let mut slices = [s0, s1, s2, s3];
+    hook.after_quintuple_round(round + 4, &mut slices, rng);
+    let [mut s0, mut s1, mut s2, mut s3] = slices;
     core::mem::swap(&mut s0, &mut s3);
     s1 ^= round_keys[8];
     s2 ^= round_keys[9];
@@ -176,14 +213,18 @@ fn inv_quintuple_round<T: RoundTraits>(
     s3 = s3.rotate_right(8);
     s1 = s1.rotate_right(16);
     s2 = s2.rotate_right(24);
-    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0));
+    hook.before_sbox(round + 4);
+    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0), rng);
+    [s3, s1, s2, s0] = after_sbox(hook, round + 4, [s3, s1, s2, s0], rng);
     s1 ^= round_keys[6];
     s2 ^= round_keys[7];
     s3 ^= round_constants[3];
     s0 = s0.byte_ror_2();
     s1 = s1.byte_ror_4();
     s2 = s2.byte_ror_6();
-    State(s0, s1, s2, s3) = inv_sbox(State(s0, s1, s2, s3));
+    hook.before_sbox(round + 3);
+    State(s0, s1, s2, s3) = inv_sbox(State(s0, s1, s2, s3), rng);
+    [s0, s1, s2, s3] = after_sbox(hook, round + 3, [s0, s1, s2, s3], rng);
     s1 ^= round_keys[4];
     s2 ^= round_keys[5];
     s0 ^= round_constants[2];
@@ -192,46 +233,81 @@ fn inv_quintuple_round<T: RoundTraits>(
     s2 = swap_move_single(s2, 0x00005555, 1);
     s3 = s3.rotate_right(16);
     s2 = s2.rotate_right(16);
-    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0));
+    hook.before_sbox(round + 2);
+    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0), rng);
+    [s3, s1, s2, s0] = after_sbox(hook, round + 2, [s3, s1, s2, s0], rng);
     s1 ^= round_keys[2];
     s2 ^= round_keys[3];
     s3 ^= round_constants[1];
     s0 = s0.half_ror_12();
     s1 = s1.half_ror_8();
     s2 = s2.half_ror_4();
-    State(s0, s1, s2, s3) = inv_sbox(State(s0, s1, s2, s3));
+    hook.before_sbox(round + 1);
+    State(s0, s1, s2, s3) = inv_sbox(State(s0, s1, s2, s3), rng);
+    [s0, s1, s2, s3] = after_sbox(hook, round + 1, [s0, s1, s2, s3], rng);
     s1 ^= round_keys[0];
     s2 ^= round_keys[1];
     s0 ^= round_constants[0];
     s3 = s3.nibble_ror_3();
     s1 = s1.nibble_ror_2();
     s2 = s2.nibble_ror_1();
-    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0));
+    hook.before_sbox(round);
+    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0), rng);
+    [s3, s1, s2, s0] = after_sbox(hook, round, [s3, s1, s2, s0], rng);
 
     State(s0, s1, s2, s3)
 }
 
-pub(super) fn rounds<T: RoundTraits>(mut state: State<T>, round_keys: &RoundKeys<T>) -> State<T> {
+// Only for words with plain AND and OR operators, whose gates need no fresh randomness. Masked
+// words go through `rounds_with_hook` with a generator.
+pub(crate) fn rounds<T>(state: State<T>, round_keys: &RoundKeys<T>) -> State<T>
+where
+    T: RoundTraits + BitAnd<Output = T> + BitOr<Output = T>,
+{
+    rounds_with_hook(state, round_keys, &mut NoHook, &mut NoRng)
+}
+
+pub(crate) fn inv_rounds<T>(state: State<T>, round_keys: &RoundKeys<T>) -> State<T>
+where
+    T: RoundTraits + BitAnd<Output = T> + BitOr<Output = T>,
+{
+    inv_rounds_with_hook(state, round_keys, &mut NoHook, &mut NoRng)
+}
+
+pub(crate) fn rounds_with_hook<T: RoundTraits, H: RoundHook<T>, R: Rng>(
+    mut state: State<T>,
+    round_keys: &RoundKeys<T>,
+    hook: &mut H,
+    rng: &mut R,
+) -> State<T> {
     for i in (0..ROUNDS).step_by(5) {
         state = quintuple_round(
             state,
             &round_keys[i * 2..i * 2 + 10],
             &ROUND_CONSTANTS[i..i + 5],
+            i,
+            hook,
+            rng,
         );
     }
 
     state
 }
 
-pub(super) fn inv_rounds<T: RoundTraits>(
+pub(crate) fn inv_rounds_with_hook<T: RoundTraits, H: RoundHook<T>, R: Rng>(
     mut state: State<T>,
     round_keys: &RoundKeys<T>,
+    hook: &mut H,
+    rng: &mut R,
 ) -> State<T> {
     for i in (0..ROUNDS).step_by(5).rev() {
         state = inv_quintuple_round(
             state,
             &round_keys[i * 2..i * 2 + 10],
             &ROUND_CONSTANTS[i..i + 5],
+            i,
+            hook,
+            rng,
         );
     }
 
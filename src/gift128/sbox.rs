@@ -1,49 +1,86 @@
 use core::ops::{BitAnd, BitOr, BitXorAssign};
 
 use crate::gift128::State;
+use crate::random::Rng;
 
-pub trait SboxTraits:
-    BitXorAssign + Copy + BitAnd<Output = Self> + BitXorAssign<u32> + BitOr<Output = Self>
-{
+// The non-linear gates of the S-box. Masked words draw the fresh randomness of their gadgets from
+// `rng`, all other words have plain AND and OR operators and ignore it.
+pub trait Gates: Sized {
+    #[must_use]
+    fn and<R: Rng>(self, rhs: Self, rng: &mut R) -> Self;
+
+    #[must_use]
+    fn or<R: Rng>(self, rhs: Self, rng: &mut R) -> Self;
 }
 
-impl<T> SboxTraits for T where
-    T: BitXorAssign + Copy + BitAnd<Output = T> + BitXorAssign<u32> + BitOr<Output = T>
-{
+impl<T: BitAnd<Output = T> + BitOr<Output = T>> Gates for T {
+    #[inline(always)]
+    fn and<R: Rng>(self, rhs: Self, _rng: &mut R) -> Self {
+        self & rhs
+    }
+
+    #[inline(always)]
+    fn or<R: Rng>(self, rhs: Self, _rng: &mut R) -> Self {
+        self | rhs
+    }
 }
 
+pub trait SboxTraits: BitXorAssign + Copy + Gates + BitXorAssign<u32> {}
+
+impl<T> SboxTraits for T where T: BitXorAssign + Copy + Gates + BitXorAssign<u32> {}
+
 #[must_use]
 #[inline]
-pub(super) fn sbox<T: SboxTraits>(state: State<T>) -> State<T> {
+pub(crate) fn sbox<T: SboxTraits, R: Rng>(state: State<T>, rng: &mut R) -> State<T> {
     let State(mut s0, mut s1, mut s2, mut s3) = state;
-    s1 ^= s0 & s2;
-    s0 ^= s1 & s3;
-    s2 ^= s0 | s1;
+    s1 ^= s0.and(s2, rng);
+    s0 ^= s1.and(s3, rng);
+    s2 ^= s0.or(s1, rng);
     s3 ^= s2;
     s1 ^= s3;
     s3 ^= 0xffffffff;
-    s2 ^= s0 & s1;
+    s2 ^= s0.and(s1, rng);
     State(s0, s1, s2, s3)
 }
 
+pub(crate) const SBOX_GATES: usize = 7;
+
+// The gates of `sbox` one at a time, for implementations that interleave them with other
+// operations.
+#[inline]
+pub(crate) fn sbox_gate<T: SboxTraits, R: Rng>(slices: &mut [T; 4], gate: usize, rng: &mut R) {
+    let [s0, s1, s2, s3] = slices;
+    match gate {
+        0 => *s1 ^= s0.and(*s2, rng),
+        1 => *s0 ^= s1.and(*s3, rng),
+        2 => *s2 ^= s0.or(*s1, rng),
+        3 => *s3 ^= *s2,
+        4 => *s1 ^= *s3,
+        5 => *s3 ^= 0xffffffff,
+        6 => *s2 ^= s0.and(*s1, rng),
+        _ => panic!("the S-box has only {SBOX_GATES} gates"),
+    }
+}
+
 #[must_use]
 #[inline]
-pub(super) fn inv_sbox<T: SboxTraits>(state: State<T>) -> State<T> {
+pub(crate) fn inv_sbox<T: SboxTraits, R: Rng>(state: State<T>, rng: &mut R) -> State<T> {
     let State(mut s0, mut s1, mut s2, mut s3) = state;
-    s2 ^= s3 & s1;
+    s2 ^= s3.and(s1, rng);
     s0 ^= 0xffffffff;
     s1 ^= s0;
     s0 ^= s2;
-    s2 ^= s3 | s1;
-    s3 ^= s1 & s0;
-    s1 ^= s3 & s2;
+    s2 ^= s3.or(s1, rng);
+    s3 ^= s1.and(s0, rng);
+    s1 ^= s3.and(s2, rng);
     State(s0, s1, s2, s3)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::gift128::sbox::{inv_sbox, sbox};
+    use crate::gift128::sbox::{inv_sbox, sbox, sbox_gate, SBOX_GATES};
     use crate::gift128::State;
+    use crate::random::{NoRng, XorShiftRng};
 
     #[test]
     fn test_masked_sbox() {
@@ -51,10 +88,10 @@ mod tests {
         let masks = (0x1d54f08eu32, 0x550aaf8cu32, 0xb3d27d46u32, 0x4aafa1b4u32);
 
         let masked_state = initial_state.make_shares(masks);
-        let masked_output = sbox(masked_state);
+        let masked_output = sbox(masked_state, &mut XorShiftRng::new(1));
 
         let output = masked_output.recover_shares();
-        assert_eq!(output, sbox(initial_state));
+        assert_eq!(output, sbox(initial_state, &mut NoRng));
     }
 
     #[test]
@@ -63,9 +100,21 @@ mod tests {
         let masks = (0x1d54f08eu32, 0x550aaf8cu32, 0xb3d27d46u32, 0x4aafa1b4u32);
 
         let masked_state = initial_state.make_shares(masks);
-        let masked_output = inv_sbox(masked_state);
+        let masked_output = inv_sbox(masked_state, &mut XorShiftRng::new(1));
 
         let output = masked_output.recover_shares();
-        assert_eq!(output, inv_sbox(initial_state));
+        assert_eq!(output, inv_sbox(initial_state, &mut NoRng));
+    }
+
+    #[test]
+    fn test_sbox_gates() {
+        let initial_state = State(0xd576370du32, 0x8b52c000u32, 0x2bafc0ccu32, 0xa3487987u32);
+        let State(s0, s1, s2, s3) = initial_state;
+        let mut slices = [s0, s1, s2, s3];
+        for gate in 0..SBOX_GATES {
+            sbox_gate(&mut slices, gate, &mut NoRng);
+        }
+        let [s0, s1, s2, s3] = slices;
+        assert_eq!(State(s0, s1, s2, s3), sbox(initial_state, &mut NoRng));
     }
 }
@@ -0,0 +1,344 @@
+use core::array;
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not, Shl, ShlAssign, Shr, ShrAssign};
+
+use crate::gift128::sbox::Gates;
+use crate::gift128::traits::{MaskedByte, Refresh, Rotate, SwapBytes, Unmask};
+use crate::random::{Random, Rng};
+
+// Boolean masking with N shares, which protects against probing attacks of order N - 1. For N = 2,
+// the non-linear gadgets are the same as those of `BinaryMask`.
+#[derive(Copy, Clone, Debug)]
+pub struct Shares<T, const N: usize>(pub [T; N]);
+
+impl<T: BitXor<Output = T> + Copy, const N: usize> Shares<T, N> {
+    pub fn make_shares(value: T, masks: &[T]) -> Self {
+        if masks.len() + 1 != N {
+            panic!("number of masks is not one less than the number of shares");
+        }
+
+        let mut shares = [value; N];
+        for (i, &mask) in masks.iter().enumerate() {
+            shares[0] = shares[0] ^ mask;
+            shares[i + 1] = mask;
+        }
+        Shares(shares)
+    }
+
+    #[inline]
+    pub fn recover_shares(self) -> T {
+        self.0[1..]
+            .iter()
+            .fold(self.0[0], |value, &share| value ^ share)
+    }
+}
+
+impl<T: BitAnd<Output = T> + Copy, const N: usize> BitAnd<T> for Shares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: T) -> Self::Output {
+        Shares(self.0.map(|share| share & rhs))
+    }
+}
+
+impl<T: Not<Output = T> + Copy, const N: usize> Not for Shares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn not(mut self) -> Self::Output {
+        self.0[0] = !self.0[0];
+        self
+    }
+}
+
+impl<T, const N: usize> BitOr<T> for Shares<T, N>
+where
+    T: BitAnd<Output = T> + BitXor<Output = T> + Not<Output = T> + Copy,
+{
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: T) -> Self::Output {
+        let mut output = self & !rhs;
+        output.0[0] = output.0[0] ^ rhs;
+        output
+    }
+}
+
+// The multiplication of Ishai, Sahai and Wagner, which uses N(N - 1)/2 random words.
+impl<T, const N: usize> Gates for Shares<T, N>
+where
+    T: BitAnd<Output = T> + BitXor<Output = T> + Not<Output = T> + Copy + Random,
+{
+    #[inline]
+    fn and<R: Rng>(self, rhs: Self, rng: &mut R) -> Self {
+        let (a, b) = (self.0, rhs.0);
+        let mut c = array::from_fn(|i| a[i] & b[i]);
+        for i in 0..N {
+            for j in i + 1..N {
+                let r = T::random(rng);
+                c[i] = c[i] ^ r;
+                c[j] = c[j] ^ ((r ^ (a[i] & b[j])) ^ (a[j] & b[i]));
+            }
+        }
+        Shares(c)
+    }
+
+    #[inline]
+    fn or<R: Rng>(self, rhs: Self, rng: &mut R) -> Self {
+        !(!self).and(!rhs, rng)
+    }
+}
+
+impl<T: Shl<R, Output = T> + Copy, R: Copy, const N: usize> Shl<R> for Shares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn shl(self, rhs: R) -> Self::Output {
+        Shares(self.0.map(|share| share << rhs))
+    }
+}
+
+impl<T: ShlAssign<R>, R: Copy, const N: usize> ShlAssign<R> for Shares<T, N> {
+    #[inline]
+    fn shl_assign(&mut self, rhs: R) {
+        for share in &mut self.0 {
+            *share <<= rhs;
+        }
+    }
+}
+
+impl<T: Shr<R, Output = T> + Copy, R: Copy, const N: usize> Shr<R> for Shares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn shr(self, rhs: R) -> Self::Output {
+        Shares(self.0.map(|share| share >> rhs))
+    }
+}
+
+impl<T: ShrAssign<R>, R: Copy, const N: usize> ShrAssign<R> for Shares<T, N> {
+    #[inline]
+    fn shr_assign(&mut self, rhs: R) {
+        for share in &mut self.0 {
+            *share >>= rhs;
+        }
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy, const N: usize> BitXor for Shares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Shares(array::from_fn(|i| self.0[i] ^ rhs.0[i]))
+    }
+}
+
+impl<T: BitXorAssign + Copy, const N: usize> BitXorAssign for Shares<T, N> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        for (share, other) in self.0.iter_mut().zip(rhs.0) {
+            *share ^= other;
+        }
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy, const N: usize> BitXor<T> for Shares<T, N> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(mut self, rhs: T) -> Self::Output {
+        self.0[0] = self.0[0] ^ rhs;
+        self
+    }
+}
+
+impl<T: BitXorAssign, const N: usize> BitXorAssign<T> for Shares<T, N> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: T) {
+        self.0[0] ^= rhs;
+    }
+}
+
+impl<T: Rotate + Copy, const N: usize> Rotate for Shares<T, N> {
+    #[inline]
+    fn rotate_right(self, rhs: u32) -> Self {
+        Shares(self.0.map(|share| share.rotate_right(rhs)))
+    }
+}
+
+// Adds a fresh random word to every share but the first and all of them to the first.
+impl<T: BitXor<Output = T> + Copy + Random, const N: usize> Refresh for Shares<T, N> {
+    #[inline]
+    fn refresh<R: Rng>(mut self, rng: &mut R) -> Self {
+        for i in 1..N {
+            let r = T::random(rng);
+            self.0[0] = self.0[0] ^ r;
+            self.0[i] = self.0[i] ^ r;
+        }
+        self
+    }
+}
+
+impl<T: SwapBytes + Copy, const N: usize> SwapBytes for Shares<T, N> {
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        Shares(self.0.map(|share| share.swap_bytes()))
+    }
+}
+
+impl<const N: usize> From<Shares<u32, N>> for Shares<u8, N> {
+    #[inline]
+    fn from(x: Shares<u32, N>) -> Self {
+        Shares(x.0.map(|share| share as u8))
+    }
+}
+
+impl<const N: usize> From<Shares<u8, N>> for Shares<u32, N> {
+    #[inline]
+    fn from(x: Shares<u8, N>) -> Self {
+        Shares(x.0.map(|share| share as u32))
+    }
+}
+
+impl<T: Default + Copy, const N: usize> Default for Shares<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Shares([Default::default(); N])
+    }
+}
+
+impl<const N: usize> Unmask for Shares<u32, N> {
+    #[inline]
+    fn unmask(self) -> u32 {
+        self.recover_shares()
+    }
+}
+
+impl<const N: usize> MaskedByte for Shares<u8, N> {
+    type Word = Shares<u32, N>;
+}
+
+#[must_use]
+pub(crate) fn share_bytes<const L: usize, const N: usize>(
+    bytes: &[u8; L],
+    masks: &[[u8; L]],
+) -> [Shares<u8, N>; L] {
+    array::from_fn(|i| {
+        let mut byte_masks = [0; N];
+        for (byte_mask, mask) in byte_masks.iter_mut().zip(masks) {
+            *byte_mask = mask[i];
+        }
+        Shares::make_shares(bytes[i], &byte_masks[..masks.len()])
+    })
+}
+
+#[must_use]
+pub(crate) fn share_bytes_with_rng<const L: usize, const N: usize, R: Rng>(
+    bytes: &[u8; L],
+    rng: &mut R,
+) -> [Shares<u8, N>; L] {
+    let mut masks = [[0; L]; N];
+    for mask in &mut masks[..N - 1] {
+        rng.fill_bytes(mask);
+    }
+    share_bytes(bytes, &masks[..N - 1])
+}
+
+#[must_use]
+pub(crate) fn unshare_bytes<const L: usize, const N: usize>(
+    shared_bytes: &[Shares<u8, N>; L],
+) -> [u8; L] {
+    shared_bytes.map(|shares| shares.recover_shares())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::rounds::StateOperations;
+    use crate::gift128::sbox::Gates;
+    use crate::gift128::shares::Shares;
+    use crate::gift128::traits::{Refresh, Rotate, SwapBytes};
+    use crate::random::XorShiftRng;
+
+    const VALUE1: u32 = 0xD576370D;
+    const MASKS1: [u32; 3] = [0xB751F5EF, 0x4a6e09c3, 0x93d1a27f];
+    const VALUE2: u32 = 0x6CC92B7B;
+    const MASKS2: [u32; 3] = [0xC2E993A4, 0x17b3e5d8, 0x5f0c84a6];
+
+    #[test]
+    fn test_make_recover_shares() {
+        let shares = Shares::<u32, 4>::make_shares(VALUE1, &MASKS1);
+        assert_eq!(shares.0[1..], MASKS1);
+        assert_eq!(shares.recover_shares(), VALUE1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_make_shares_wrong_mask_count() {
+        let _ = Shares::<u32, 3>::make_shares(VALUE1, &MASKS1);
+    }
+
+    #[test]
+    fn test_and() {
+        let mut rng = XorShiftRng::new(1);
+        let a = Shares::<u32, 2>::make_shares(VALUE1, &MASKS1[..1]);
+        let b = Shares::<u32, 2>::make_shares(VALUE2, &MASKS2[..1]);
+        assert_eq!(a.and(b, &mut rng).recover_shares(), VALUE1 & VALUE2);
+
+        let a = Shares::<u32, 3>::make_shares(VALUE1, &MASKS1[..2]);
+        let b = Shares::<u32, 3>::make_shares(VALUE2, &MASKS2[..2]);
+        assert_eq!(a.and(b, &mut rng).recover_shares(), VALUE1 & VALUE2);
+
+        let a = Shares::<u32, 4>::make_shares(VALUE1, &MASKS1);
+        let b = Shares::<u32, 4>::make_shares(VALUE2, &MASKS2);
+        assert_eq!(a.and(b, &mut rng).recover_shares(), VALUE1 & VALUE2);
+    }
+
+    #[test]
+    fn test_and_with_constant() {
+        let a = Shares::<u32, 4>::make_shares(VALUE1, &MASKS1);
+        assert_eq!((a & VALUE2).recover_shares(), VALUE1 & VALUE2);
+    }
+
+    #[test]
+    fn test_or() {
+        let a = Shares::<u32, 4>::make_shares(VALUE1, &MASKS1);
+        let b = Shares::<u32, 4>::make_shares(VALUE2, &MASKS2);
+        let mut rng = XorShiftRng::new(1);
+        assert_eq!(a.or(b, &mut rng).recover_shares(), VALUE1 | VALUE2);
+        assert_eq!((a | VALUE2).recover_shares(), VALUE1 | VALUE2);
+    }
+
+    #[test]
+    fn test_xor() {
+        let a = Shares::<u32, 4>::make_shares(VALUE1, &MASKS1);
+        let b = Shares::<u32, 4>::make_shares(VALUE2, &MASKS2);
+        assert_eq!((a ^ b).recover_shares(), VALUE1 ^ VALUE2);
+        assert_eq!((a ^ VALUE2).recover_shares(), VALUE1 ^ VALUE2);
+
+        let mut c = a;
+        c ^= b;
+        c ^= VALUE2;
+        assert_eq!(c.recover_shares(), VALUE1);
+    }
+
+    #[test]
+    fn test_linear_operations() {
+        let a = Shares::<u32, 3>::make_shares(VALUE1, &MASKS1[1..]);
+        assert_eq!((a << 3).recover_shares(), VALUE1 << 3);
+        assert_eq!((a >> 3).recover_shares(), VALUE1 >> 3);
+        assert_eq!(a.rotate_right(5).recover_shares(), VALUE1.rotate_right(5));
+        assert_eq!(a.swap_bytes().recover_shares(), VALUE1.swap_bytes());
+        assert_eq!(a.nibble_ror_1().recover_shares(), VALUE1.nibble_ror_1());
+        assert_eq!(a.half_ror_12().recover_shares(), VALUE1.half_ror_12());
+    }
+
+    #[test]
+    fn test_refresh() {
+        let a = Shares::<u32, 4>::make_shares(VALUE1, &MASKS1);
+        let refreshed = a.refresh(&mut XorShiftRng::new(1));
+        assert_eq!(refreshed.recover_shares(), VALUE1);
+        assert_ne!(refreshed.0[1..], MASKS1);
+    }
+}
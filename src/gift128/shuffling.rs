@@ -0,0 +1,217 @@
+use core::ops::BitXorAssign;
+
+use crate::gift128::key_schedule::RoundKeys;
+use crate::gift128::rounds::{RoundTraits, ROUNDS, ROUND_CONSTANTS};
+use crate::gift128::sbox::{sbox_gate, SboxTraits, SBOX_GATES};
+use crate::gift128::State;
+use crate::random::Rng;
+use crate::swap_move_single;
+
+// The number of dummy operations in every S-box and every linear layer. They are fixed, so that
+// only their positions and not the length of an encryption depend on the generator.
+const DUMMY_SBOX_GATES: usize = 4;
+const DUMMY_LINEAR_OPERATIONS: usize = 2;
+
+// Runs the `steps` real steps in order and the dummy steps at random positions in between.
+#[inline]
+fn interleave<R: Rng>(
+    rng: &mut R,
+    steps: usize,
+    dummies: usize,
+    mut step: impl FnMut(usize, &mut R),
+    mut dummy: impl FnMut(&mut R),
+) {
+    let (mut done, mut dummies) = (0, dummies);
+    while done < steps || dummies > 0 {
+        if (rng.next_u32() as usize) % (steps - done + dummies) < dummies {
+            dummy(rng);
+            dummies -= 1;
+        } else {
+            step(done, rng);
+            done += 1;
+        }
+    }
+}
+
+// The dummy operations work on their own state, which starts out random and never sees the real
+// state or a round key. A dummy gate is an AND or OR like the real ones.
+#[inline]
+fn dummy_gate<T: SboxTraits, R: Rng>(dummy: &mut [T; 4], rng: &mut R) {
+    let r = rng.next_u32() as usize;
+    let (a, b, c) = (r % 4, (r + 1) % 4, (r + 2) % 4);
+    if (r >> 2) & 1 == 1 {
+        dummy[a] ^= dummy[b].and(dummy[c], rng);
+    } else {
+        dummy[a] ^= dummy[b].or(dummy[c], rng);
+    }
+}
+
+#[must_use]
+#[inline]
+fn shuffled_sbox<T: SboxTraits, R: Rng>(
+    state: State<T>,
+    dummy: &mut [T; 4],
+    rng: &mut R,
+) -> State<T> {
+    let State(s0, s1, s2, s3) = state;
+    let mut slices = [s0, s1, s2, s3];
+    interleave(
+        rng,
+        SBOX_GATES,
+        DUMMY_SBOX_GATES,
+        |gate, rng| sbox_gate(&mut slices, gate, rng),
+        |rng| dummy_gate(dummy, rng),
+    );
+    let [s0, s1, s2, s3] = slices;
+    State(s0, s1, s2, s3)
+}
+
+// Applies `f` to every word in a random order, with dummy rotations in between.
+#[must_use]
+#[inline]
+fn shuffled_words<T: RoundTraits, R: Rng>(
+    mut words: [T; 4],
+    dummy: &mut [T; 4],
+    rng: &mut R,
+    f: impl Fn(usize, T) -> T,
+) -> [T; 4] {
+    let mut order = [0, 1, 2, 3];
+    for i in (1..4).rev() {
+        order.swap(i, rng.next_u32() as usize % (i + 1));
+    }
+
+    interleave(
+        rng,
+        4,
+        DUMMY_LINEAR_OPERATIONS,
+        |i, _| words[order[i]] = f(order[i], words[order[i]]),
+        |rng| {
+            let r = rng.next_u32();
+            let word = &mut dummy[r as usize % 4];
+            *word = word.rotate_right(1 + (r >> 2) % 31);
+        },
+    );
+    words
+}
+
+#[must_use]
+#[inline]
+fn xor<T: BitXorAssign<U>, U>(mut a: T, b: U) -> T {
+    a ^= b;
+    a
+}
+
+// `quintuple_round` with every S-box layer and every linear layer shuffled.
+#[must_use]
+fn shuffled_quintuple_round<T: RoundTraits, R: Rng>(
+    state: State<T>,
+    round_keys: &[T],
+    round_constants: &[u32],
+    dummy: &mut [T; 4],
+    rng: &mut R,
+) -> State<T> {
+    let State(s0, s1, s2, s3) = shuffled_sbox(state, dummy, rng);
+    let [s0, s1, s2, s3] = shuffled_words([s0, s1, s2, s3], dummy, rng, |i, w| match i {
+        0 => xor(w, round_constants[0]),
+        1 => xor(w.nibble_ror_2(), round_keys[0]),
+        2 => xor(w.nibble_ror_3(), round_keys[1]),
+        _ => w.nibble_ror_1(),
+    });
+    let State(s3, s1, s2, s0) = shuffled_sbox(State(s3, s1, s2, s0), dummy, rng);
+    let [s0, s1, s2, s3] = shuffled_words([s0, s1, s2, s3], dummy, rng, |i, w| match i {
+        0 => w.half_ror_4(),
+        1 => xor(w.half_ror_8(), round_keys[2]),
+        2 => xor(w.half_ror_12(), round_keys[3]),
+        _ => xor(w, round_constants[1]),
+    });
+    let State(s0, s1, s2, s3) = shuffled_sbox(State(s0, s1, s2, s3), dummy, rng);
+    let [s0, s1, s2, s3] = shuffled_words([s0, s1, s2, s3], dummy, rng, |i, w| match i {
+        0 => xor(w, round_constants[2]),
+        1 => xor(swap_move_single(w, 0x55555555, 1), round_keys[4]),
+        2 => xor(
+            swap_move_single(w.rotate_right(16), 0x00005555, 1),
+            round_keys[5],
+        ),
+        _ => swap_move_single(w.rotate_right(16), 0x55550000, 1),
+    });
+    let State(s3, s1, s2, s0) = shuffled_sbox(State(s3, s1, s2, s0), dummy, rng);
+    let [s0, s1, s2, s3] = shuffled_words([s0, s1, s2, s3], dummy, rng, |i, w| match i {
+        0 => w.byte_ror_6(),
+        1 => xor(w.byte_ror_4(), round_keys[6]),
+        2 => xor(w.byte_ror_2(), round_keys[7]),
+        _ => xor(w, round_constants[3]),
+    });
+    let State(s0, s1, s2, s3) = shuffled_sbox(State(s0, s1, s2, s3), dummy, rng);
+    let [s0, s1, s2, s3] = shuffled_words([s0, s1, s2, s3], dummy, rng, |i, w| match i {
+        0 => xor(w, round_constants[4]),
+        1 => xor(w.rotate_right(16), round_keys[8]),
+        2 => xor(w.rotate_right(8), round_keys[9]),
+        _ => w.rotate_right(24),
+    });
+    State(s3, s1, s2, s0)
+}
+
+// Hides the rounds by processing the words of every linear layer in a random order and inserting
+// dummy operations at random positions of every S-box and linear layer, all drawn from `rng`.
+#[must_use]
+pub(crate) fn shuffled_rounds<T: RoundTraits + Default, R: Rng>(
+    mut state: State<T>,
+    round_keys: &RoundKeys<T>,
+    rng: &mut R,
+) -> State<T> {
+    let mut dummy = [T::default(); 4];
+    for word in &mut dummy {
+        *word ^= rng.next_u32();
+    }
+    for i in (0..ROUNDS).step_by(5) {
+        state = shuffled_quintuple_round(
+            state,
+            &round_keys[i * 2..i * 2 + 10],
+            &ROUND_CONSTANTS[i..i + 5],
+            &mut dummy,
+            rng,
+        );
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::hooks::NoHook;
+    use crate::gift128::key_schedule::{
+        mask_key, precompute_masked_round_keys, precompute_round_keys,
+    };
+    use crate::gift128::masking::BinaryMask;
+    use crate::gift128::packing::{masked_pack, masked_unpack, pack};
+    use crate::gift128::rounds::{rounds, rounds_with_hook};
+    use crate::gift128::shuffling::shuffled_rounds;
+    use crate::gift128::tests::{KEY, PLAINTEXT};
+    use crate::gift128::{mask_block, unmask_block, MaskedBlock};
+    use crate::random::XorShiftRng;
+
+    #[test]
+    fn test_shuffled_rounds() {
+        let round_keys = precompute_round_keys(&KEY);
+        let state = pack(&PLAINTEXT);
+        let expected = rounds(state, &round_keys);
+        for seed in 1..20 {
+            let mut rng = XorShiftRng::new(seed);
+            assert_eq!(shuffled_rounds(state, &round_keys, &mut rng), expected);
+        }
+    }
+
+    #[test]
+    fn test_shuffled_masked_rounds() {
+        let masked_key = mask_key(&KEY, &[0x5a; 16]);
+        let round_keys = precompute_masked_round_keys(&masked_key);
+        let masked_plaintext = mask_block(&PLAINTEXT, &[0xa5; 16]);
+        let mut rng = XorShiftRng::new(7);
+        let state = masked_pack(&masked_plaintext);
+        let expected: MaskedBlock =
+            masked_unpack(rounds_with_hook(state, &round_keys, &mut NoHook, &mut rng));
+
+        let state = shuffled_rounds(masked_pack(&masked_plaintext), &round_keys, &mut rng);
+        let shuffled: [BinaryMask<u8>; 16] = masked_unpack(state);
+        assert_eq!(unmask_block(&shuffled), unmask_block(&expected));
+    }
+}
@@ -0,0 +1,301 @@
+use core::ops::{BitAnd, BitOr, BitXor, BitXorAssign, Not, Shl, Shr};
+
+use crate::gift128::traits::{MaskedByte, Refresh, Rotate, SwapBytes, Unmask};
+use crate::random::{Random, Rng};
+
+// Threshold implementation with three shares. The S-box only XORs products into other slices,
+// i.e. it is a sequence of Toffoli gates, so non-complete AND and OR gadgets are enough to make
+// every gate non-complete and uniform without fresh randomness. In hardware, every such gate
+// needs its own register stage to keep glitches from combining the gadgets.
+#[derive(Copy, Clone, Debug)]
+pub struct TiShares<T>(pub [T; 3]);
+
+impl<T: BitXor<Output = T> + Copy> TiShares<T> {
+    #[inline]
+    pub fn make_shares(value: T, masks: [T; 2]) -> Self {
+        TiShares([value ^ masks[0] ^ masks[1], masks[0], masks[1]])
+    }
+
+    #[inline]
+    pub fn recover_shares(self) -> T {
+        self.0[0] ^ self.0[1] ^ self.0[2]
+    }
+}
+
+// Share i of the output is computed from shares i + 1 and i + 2 of the inputs only.
+#[inline]
+fn non_complete<T: Copy>(f: impl Fn(T, T, T, T) -> T, a: [T; 3], b: [T; 3]) -> TiShares<T> {
+    TiShares(core::array::from_fn(|i| {
+        let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+        f(a[j], a[k], b[j], b[k])
+    }))
+}
+
+// The output sharing is not uniform on its own, but it is once XORed into a uniformly shared
+// slice that is not one of the inputs, as the S-box does.
+impl<T: BitAnd<Output = T> + BitXor<Output = T> + Copy> BitAnd for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        non_complete(
+            |a_j, a_k, b_j, b_k| (a_j & b_j) ^ (a_j & b_k) ^ (a_k & b_j),
+            self.0,
+            rhs.0,
+        )
+    }
+}
+
+impl<T: BitAnd<Output = T> + Copy> BitAnd<T> for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: T) -> Self::Output {
+        TiShares(self.0.map(|share| share & rhs))
+    }
+}
+
+// a | b = a ^ b ^ (a & b), where the linear terms come from the same shares as the product.
+impl<T: BitAnd<Output = T> + BitXor<Output = T> + Copy> BitOr for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        non_complete(
+            |a_j, a_k, b_j, b_k| a_j ^ b_j ^ (a_j & b_j) ^ (a_j & b_k) ^ (a_k & b_j),
+            self.0,
+            rhs.0,
+        )
+    }
+}
+
+impl<T: Not<Output = T> + Copy> Not for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn not(mut self) -> Self::Output {
+        self.0[0] = !self.0[0];
+        self
+    }
+}
+
+impl<T: Shl<R, Output = T> + Copy, R: Copy> Shl<R> for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn shl(self, rhs: R) -> Self::Output {
+        TiShares(self.0.map(|share| share << rhs))
+    }
+}
+
+impl<T: Shr<R, Output = T> + Copy, R: Copy> Shr<R> for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn shr(self, rhs: R) -> Self::Output {
+        TiShares(self.0.map(|share| share >> rhs))
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy> BitXor for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        TiShares(core::array::from_fn(|i| self.0[i] ^ rhs.0[i]))
+    }
+}
+
+impl<T: BitXorAssign + Copy> BitXorAssign for TiShares<T> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        for (share, other) in self.0.iter_mut().zip(rhs.0) {
+            *share ^= other;
+        }
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy> BitXor<T> for TiShares<T> {
+    type Output = Self;
+
+    #[inline]
+    fn bitxor(mut self, rhs: T) -> Self::Output {
+        self.0[0] = self.0[0] ^ rhs;
+        self
+    }
+}
+
+impl<T: BitXorAssign> BitXorAssign<T> for TiShares<T> {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: T) {
+        self.0[0] ^= rhs;
+    }
+}
+
+impl<T: Rotate + Copy> Rotate for TiShares<T> {
+    #[inline]
+    fn rotate_right(self, rhs: u32) -> Self {
+        TiShares(self.0.map(|share| share.rotate_right(rhs)))
+    }
+}
+
+impl<T: SwapBytes + Copy> SwapBytes for TiShares<T> {
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        TiShares(self.0.map(|share| share.swap_bytes()))
+    }
+}
+
+impl<T: BitXor<Output = T> + Copy + Random> Refresh for TiShares<T> {
+    #[inline]
+    fn refresh<R: Rng>(self, rng: &mut R) -> Self {
+        let (r1, r2) = (T::random(rng), T::random(rng));
+        TiShares([self.0[0] ^ r1 ^ r2, self.0[1] ^ r1, self.0[2] ^ r2])
+    }
+}
+
+impl From<TiShares<u32>> for TiShares<u8> {
+    #[inline]
+    fn from(x: TiShares<u32>) -> Self {
+        TiShares(x.0.map(|share| share as u8))
+    }
+}
+
+impl From<TiShares<u8>> for TiShares<u32> {
+    #[inline]
+    fn from(x: TiShares<u8>) -> Self {
+        TiShares(x.0.map(|share| share as u32))
+    }
+}
+
+impl<T: Default + Copy> Default for TiShares<T> {
+    #[inline]
+    fn default() -> Self {
+        TiShares([Default::default(); 3])
+    }
+}
+
+impl Unmask for TiShares<u32> {
+    #[inline]
+    fn unmask(self) -> u32 {
+        self.recover_shares()
+    }
+}
+
+impl MaskedByte for TiShares<u8> {
+    type Word = TiShares<u32>;
+}
+
+#[must_use]
+pub(crate) fn ti_share_bytes<const L: usize>(
+    bytes: &[u8; L],
+    masks: &[[u8; L]; 2],
+) -> [TiShares<u8>; L] {
+    core::array::from_fn(|i| TiShares::make_shares(bytes[i], [masks[0][i], masks[1][i]]))
+}
+
+#[must_use]
+pub(crate) fn ti_share_bytes_with_rng<const L: usize, R: Rng>(
+    bytes: &[u8; L],
+    rng: &mut R,
+) -> [TiShares<u8>; L] {
+    let mut masks = [[0; L]; 2];
+    for mask in &mut masks {
+        rng.fill_bytes(mask);
+    }
+    ti_share_bytes(bytes, &masks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::sbox::{inv_sbox, sbox};
+    use crate::gift128::threshold::TiShares;
+    use crate::gift128::State;
+    use crate::random::NoRng;
+
+    const VALUE1: u32 = 0xD576370D;
+    const MASKS1: [u32; 2] = [0xB751F5EF, 0x4a6e09c3];
+    const VALUE2: u32 = 0x6CC92B7B;
+    const MASKS2: [u32; 2] = [0xC2E993A4, 0x17b3e5d8];
+
+    #[test]
+    fn test_make_recover_shares() {
+        let shares = TiShares::make_shares(VALUE1, MASKS1);
+        assert_eq!(shares.0[1..], MASKS1);
+        assert_eq!(shares.recover_shares(), VALUE1);
+    }
+
+    #[test]
+    fn test_and_or() {
+        let a = TiShares::make_shares(VALUE1, MASKS1);
+        let b = TiShares::make_shares(VALUE2, MASKS2);
+        assert_eq!((a & b).recover_shares(), VALUE1 & VALUE2);
+        assert_eq!((a | b).recover_shares(), VALUE1 | VALUE2);
+        assert_eq!((a & VALUE2).recover_shares(), VALUE1 & VALUE2);
+        assert_eq!((!a ^ VALUE2).recover_shares(), !VALUE1 ^ VALUE2);
+    }
+
+    #[test]
+    fn test_non_completeness() {
+        let a = TiShares::make_shares(VALUE1, MASKS1);
+        let b = TiShares::make_shares(VALUE2, MASKS2);
+        for i in 0..3 {
+            let mut other_a = a;
+            let mut other_b = b;
+            other_a.0[i] = !other_a.0[i];
+            other_b.0[i] = other_b.0[i].rotate_left(7);
+            assert_eq!((a & b).0[i], (other_a & other_b).0[i]);
+            assert_eq!((a | b).0[i], (other_a | other_b).0[i]);
+        }
+    }
+
+    fn slices(x: u32) -> State<u32> {
+        State(x & 1, (x >> 1) & 1, (x >> 2) & 1, (x >> 3) & 1)
+    }
+
+    // Every sharing of an input is mapped to a different sharing of the output, for all inputs.
+    #[test]
+    fn test_sbox_uniform() {
+        for f in [sbox::<TiShares<u32>, NoRng>, inv_sbox] {
+            for x in 0..16 {
+                let mut outputs = [false; 1 << 12];
+                for masks in 0..1 << 8 {
+                    let State(m0, m1, m2, m3) = slices(masks);
+                    let State(n0, n1, n2, n3) = slices(masks >> 4);
+                    let State(x0, x1, x2, x3) = slices(x);
+                    let state = State(
+                        TiShares::make_shares(x0, [m0, n0]),
+                        TiShares::make_shares(x1, [m1, n1]),
+                        TiShares::make_shares(x2, [m2, n2]),
+                        TiShares::make_shares(x3, [m3, n3]),
+                    );
+                    let State(y0, y1, y2, y3) = f(state, &mut NoRng);
+
+                    let output = [y0, y1, y2, y3]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, y)| {
+                            ((y.0[0] & 1) | (y.0[1] & 1) << 1 | (y.0[2] & 1) << 2) << (3 * i)
+                        })
+                        .sum::<u32>() as usize;
+                    assert!(!outputs[output]);
+                    outputs[output] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sbox() {
+        let initial_state = State(0xd576370du32, 0x8b52c000u32, 0x2bafc0ccu32, 0xa3487987u32);
+        let state = initial_state.map(|x| TiShares::make_shares(x, MASKS1));
+        assert_eq!(
+            sbox(state, &mut NoRng).map(TiShares::recover_shares),
+            sbox(initial_state, &mut NoRng)
+        );
+        assert_eq!(
+            inv_sbox(state, &mut NoRng).map(TiShares::recover_shares),
+            inv_sbox(initial_state, &mut NoRng)
+        );
+    }
+}
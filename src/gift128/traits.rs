@@ -1,3 +1,30 @@
+use crate::gift128::rounds::RoundTraits;
+use crate::random::Rng;
+
+// A masked byte and the masked word it is widened to when packed into the state.
+pub trait MaskedByte: Copy + From<Self::Word> {
+    type Word: RoundTraits + SwapBytes + From<Self> + Default;
+}
+
+// Re-randomizes the shares of a masked value without changing the value itself.
+pub trait Refresh {
+    #[must_use]
+    fn refresh<R: Rng>(self, rng: &mut R) -> Self;
+}
+
+// Recombines the shares of a masked word. Only for values that are not secret, such as the
+// difference between two sharings of the same value.
+pub trait Unmask {
+    fn unmask(self) -> u32;
+}
+
+impl Unmask for u32 {
+    #[inline]
+    fn unmask(self) -> u32 {
+        self
+    }
+}
+
 pub trait Rotate {
     #[must_use]
     fn rotate_right(self, rhs: u32) -> Self;
@@ -0,0 +1,177 @@
+use core::ops::{BitAnd, BitXor, Shl, Shr};
+
+use crate::gift128::masking::BinaryMask;
+use crate::gift64::rounds::ROUNDS;
+use crate::gift64::{Key, KEY_SIZE};
+use crate::random::Rng;
+use crate::swapmove::{swap_move_single, SwapMoveTraits};
+
+pub type RoundKeys<T> = [T; ROUNDS * 2];
+
+#[must_use]
+#[inline]
+fn key_update_0<T>(key_word: T) -> T
+where
+    T: Copy
+        + Shr<usize, Output = T>
+        + Shl<usize, Output = T>
+        + BitAnd<u32, Output = T>
+        + BitXor<Output = T>,
+{
+    ((key_word >> 12) & 0x000f000f) ^ ((key_word & 0x0fff0fff) << 4)
+}
+
+#[must_use]
+#[inline]
+fn key_update_1<T>(key_word: T) -> T
+where
+    T: Copy
+        + Shr<usize, Output = T>
+        + Shl<usize, Output = T>
+        + BitAnd<u32, Output = T>
+        + BitXor<Output = T>,
+{
+    ((key_word >> 2) & 0x3fff3fff) ^ ((key_word & 0x00030003) << 14)
+}
+
+#[must_use]
+#[inline]
+fn transpose<T: SwapMoveTraits>(mut round_key: T) -> T {
+    round_key = swap_move_single(round_key, 0x0a0a0a0a, 3);
+    swap_move_single(round_key, 0x00cc00cc, 6)
+}
+
+#[must_use]
+#[inline]
+fn reverse_nibbles<T: SwapMoveTraits>(mut round_key: T) -> T {
+    round_key = swap_move_single(round_key, 0x11111111, 3);
+    swap_move_single(round_key, 0x22222222, 1)
+}
+
+#[must_use]
+#[inline]
+fn reverse_halves<T: SwapMoveTraits>(mut round_key: T) -> T {
+    round_key = swap_move_single(round_key, 0x000f000f, 12);
+    swap_move_single(round_key, 0x00f000f0, 4)
+}
+
+#[must_use]
+#[inline]
+fn rearrange_round_key1<T: SwapMoveTraits>(round_key: T) -> T {
+    reverse_nibbles(transpose(round_key))
+}
+
+#[must_use]
+#[inline]
+fn rearrange_round_key2<T: SwapMoveTraits>(round_key: T) -> T {
+    reverse_halves(reverse_nibbles(round_key))
+}
+
+#[must_use]
+#[inline]
+fn rearrange_round_key3<T: SwapMoveTraits>(round_key: T) -> T {
+    reverse_halves(transpose(round_key))
+}
+
+fn fill_round_keys<T>(round_keys: &mut [T; ROUNDS * 2])
+where
+    T: SwapMoveTraits + BitXor<Output = T>,
+{
+    for i in (8..ROUNDS * 2).step_by(2) {
+        round_keys[i] = key_update_0(round_keys[i - 8]);
+        round_keys[i + 1] = key_update_1(round_keys[i - 7]);
+    }
+
+    // The round key of round i is added after its permutation layer, so it has to be laid out
+    // like the state at the start of round i + 1.
+    for i in (0..ROUNDS * 2).step_by(8) {
+        round_keys[i] = rearrange_round_key1(round_keys[i]);
+        round_keys[i + 1] = rearrange_round_key1(round_keys[i + 1]);
+        round_keys[i + 2] = rearrange_round_key2(round_keys[i + 2]);
+        round_keys[i + 3] = rearrange_round_key2(round_keys[i + 3]);
+        round_keys[i + 4] = rearrange_round_key3(round_keys[i + 4]);
+        round_keys[i + 5] = rearrange_round_key3(round_keys[i + 5]);
+    }
+}
+
+#[must_use]
+pub fn precompute_round_keys(key: &Key) -> RoundKeys<u32> {
+    let mut round_keys = [0; ROUNDS * 2];
+    for i in 0..8 {
+        let key_word = u16::from_be_bytes([key[14 - 2 * i], key[15 - 2 * i]]) as u32;
+        round_keys[i] = (key_word << 16) | key_word;
+    }
+
+    fill_round_keys(&mut round_keys);
+
+    round_keys
+}
+
+#[must_use]
+pub fn mask_key(key: &[u8; KEY_SIZE], masks: &[u8; KEY_SIZE]) -> [BinaryMask<u8>; KEY_SIZE] {
+    let mut masked_key = [Default::default(); KEY_SIZE];
+    for i in 0..KEY_SIZE {
+        masked_key[i] = BinaryMask::make_shares(key[i], masks[i]);
+    }
+    masked_key
+}
+
+#[must_use]
+pub fn mask_key_with_rng<R: Rng>(key: &Key, rng: &mut R) -> [BinaryMask<u8>; KEY_SIZE] {
+    let mut masks = [0; KEY_SIZE];
+    rng.fill_bytes(&mut masks);
+    mask_key(key, &masks)
+}
+
+#[must_use]
+pub fn precompute_masked_round_keys(
+    key: &[BinaryMask<u8>; KEY_SIZE],
+) -> RoundKeys<BinaryMask<u32>> {
+    let mut round_keys = [BinaryMask::make_shares(0, 0); ROUNDS * 2];
+    for i in 0..8 {
+        let key_word = BinaryMask(
+            u16::from_be_bytes([key[14 - 2 * i].0, key[15 - 2 * i].0]) as u32,
+            u16::from_be_bytes([key[14 - 2 * i].1, key[15 - 2 * i].1]) as u32,
+        );
+        round_keys[i] = (key_word << 16) ^ key_word;
+    }
+
+    fill_round_keys(&mut round_keys);
+
+    round_keys
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift64::key_schedule::{
+        mask_key, precompute_masked_round_keys, precompute_round_keys,
+    };
+    use crate::gift64::KEY_SIZE;
+
+    const KEY: [u8; KEY_SIZE] = [
+        0xbd, 0x91, 0x73, 0x1e, 0xb6, 0xbc, 0x27, 0x13, 0xa1, 0xf9, 0xf6, 0xff, 0xc7, 0x50, 0x44,
+        0xe7u8,
+    ];
+    const KEY_MASKS: [u8; KEY_SIZE] = [
+        0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf, 0xa1,
+        0xb4u8,
+    ];
+
+    #[test]
+    fn test_masked_round_keys() {
+        let round_keys = precompute_round_keys(&KEY);
+        let masked_key = mask_key(&KEY, &KEY_MASKS);
+        let masked_rounds_keys = precompute_masked_round_keys(&masked_key);
+
+        for (masked, expected) in masked_rounds_keys.into_iter().zip(round_keys) {
+            assert_eq!(masked.recover_shares(), expected);
+        }
+    }
+
+    #[test]
+    fn test_round_keys_lanes() {
+        for round_key in precompute_round_keys(&KEY) {
+            assert_eq!(round_key >> 16, round_key & 0xffff);
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use crate::gift128::masking::BinaryMask;
+use crate::gift64::key_schedule::{precompute_masked_round_keys, precompute_round_keys};
+use crate::gift64::packing::{masked_pack, masked_unpack, pack, unpack};
+use crate::gift64::rounds::{inv_rounds, rounds};
+use crate::random::{NoRng, Rng};
+
+pub mod key_schedule;
+mod packing;
+mod rounds;
+
+const KEY_SIZE: usize = 16;
+const BLOCK_SIZE: usize = 8;
+
+pub type Block = [u8; BLOCK_SIZE];
+pub type MaskedBlock = [BinaryMask<u8>; BLOCK_SIZE];
+pub type Key = [u8; KEY_SIZE];
+pub type MaskedKey = [BinaryMask<u8>; KEY_SIZE];
+
+// The fixsliced state always holds two blocks; a lone trailing block is paired with zeroes.
+fn load_blocks<T: Copy + Default>(chunk: &[T]) -> [[T; BLOCK_SIZE]; 2] {
+    let mut blocks = [[Default::default(); BLOCK_SIZE]; 2];
+    for (block, input) in blocks.iter_mut().zip(chunk.chunks(BLOCK_SIZE)) {
+        block.copy_from_slice(input);
+    }
+    blocks
+}
+
+fn store_blocks<T: Copy>(blocks: &[[T; BLOCK_SIZE]; 2], output: &mut [T]) {
+    for (output, block) in output.chunks_mut(BLOCK_SIZE).zip(blocks) {
+        output.copy_from_slice(block);
+    }
+}
+
+pub fn encrypt(plaintext: &[u8], key: &Key, ciphertext: &mut [u8]) {
+    if !plaintext.len().is_multiple_of(BLOCK_SIZE) {
+        panic!("plaintext size is not a multiple of 8");
+    }
+
+    if plaintext.len() != ciphertext.len() {
+        panic!("ciphertext size differs from plaintext size");
+    }
+
+    let round_keys = precompute_round_keys(key);
+    for (i, chunk) in plaintext.chunks(2 * BLOCK_SIZE).enumerate() {
+        let plaintext_blocks = load_blocks(chunk);
+
+        let state = rounds(pack(&plaintext_blocks), &round_keys, &mut NoRng);
+
+        let end = ciphertext.len().min((i + 1) * 2 * BLOCK_SIZE);
+        store_blocks(&unpack(state), &mut ciphertext[i * 2 * BLOCK_SIZE..end]);
+    }
+}
+
+pub fn decrypt(ciphertext: &[u8], key: &Key, plaintext: &mut [u8]) {
+    if !ciphertext.len().is_multiple_of(BLOCK_SIZE) {
+        panic!("ciphertext size is not a multiple of 8");
+    }
+
+    if ciphertext.len() != plaintext.len() {
+        panic!("plaintext size differs from ciphertext size");
+    }
+
+    let round_keys = precompute_round_keys(key);
+    for (i, chunk) in ciphertext.chunks(2 * BLOCK_SIZE).enumerate() {
+        let ciphertext_blocks = load_blocks(chunk);
+
+        let state = inv_rounds(pack(&ciphertext_blocks), &round_keys, &mut NoRng);
+
+        let end = plaintext.len().min((i + 1) * 2 * BLOCK_SIZE);
+        store_blocks(&unpack(state), &mut plaintext[i * 2 * BLOCK_SIZE..end]);
+    }
+}
+
+#[must_use]
+pub fn mask_block(block: &Block, masks: &[u8; BLOCK_SIZE]) -> MaskedBlock {
+    let mut masked_block = [Default::default(); BLOCK_SIZE];
+    for i in 0..BLOCK_SIZE {
+        masked_block[i] = BinaryMask::make_shares(block[i], masks[i]);
+    }
+    masked_block
+}
+
+#[must_use]
+pub fn mask_block_with_rng<R: Rng>(block: &Block, rng: &mut R) -> MaskedBlock {
+    let mut masks = [0; BLOCK_SIZE];
+    rng.fill_bytes(&mut masks);
+    mask_block(block, &masks)
+}
+
+#[must_use]
+pub fn unmask_block(masked_block: &MaskedBlock) -> Block {
+    let mut block = [0; BLOCK_SIZE];
+    for i in 0..BLOCK_SIZE {
+        block[i] = masked_block[i].recover_shares();
+    }
+    block
+}
+
+pub fn encrypt_masked<R: Rng>(
+    plaintext: &[BinaryMask<u8>],
+    key: &MaskedKey,
+    ciphertext: &mut [BinaryMask<u8>],
+    rng: &mut R,
+) {
+    if !plaintext.len().is_multiple_of(BLOCK_SIZE) {
+        panic!("plaintext size is not a multiple of 8");
+    }
+
+    if plaintext.len() != ciphertext.len() {
+        panic!("ciphertext size differs from plaintext size");
+    }
+
+    let round_keys = precompute_masked_round_keys(key);
+    for (i, chunk) in plaintext.chunks(2 * BLOCK_SIZE).enumerate() {
+        let plaintext_blocks = load_blocks(chunk);
+        let state = rounds(masked_pack(&plaintext_blocks), &round_keys, rng);
+        let end = ciphertext.len().min((i + 1) * 2 * BLOCK_SIZE);
+        store_blocks(
+            &masked_unpack(state),
+            &mut ciphertext[i * 2 * BLOCK_SIZE..end],
+        );
+    }
+}
+
+pub fn decrypt_masked<R: Rng>(
+    ciphertext: &[BinaryMask<u8>],
+    key: &MaskedKey,
+    plaintext: &mut [BinaryMask<u8>],
+    rng: &mut R,
+) {
+    if !ciphertext.len().is_multiple_of(BLOCK_SIZE) {
+        panic!("ciphertext size is not a multiple of 8");
+    }
+
+    if ciphertext.len() != plaintext.len() {
+        panic!("plaintext size differs from ciphertext size");
+    }
+
+    let round_keys = precompute_masked_round_keys(key);
+    for (i, chunk) in ciphertext.chunks(2 * BLOCK_SIZE).enumerate() {
+        let ciphertext_blocks = load_blocks(chunk);
+        let state = inv_rounds(masked_pack(&ciphertext_blocks), &round_keys, rng);
+        let end = plaintext.len().min((i + 1) * 2 * BLOCK_SIZE);
+        store_blocks(
+            &masked_unpack(state),
+            &mut plaintext[i * 2 * BLOCK_SIZE..end],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift64::key_schedule::{mask_key, mask_key_with_rng};
+    use crate::gift64::*;
+    use crate::random::XorShiftRng;
+
+    struct TestTriple {
+        key: Key,
+        plaintext: Block,
+        ciphertext: Block,
+    }
+
+    const CASES: [TestTriple; 3] = [
+        TestTriple {
+            key: [
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+            plaintext: [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ciphertext: [0xf6, 0x2b, 0xc3, 0xef, 0x34, 0xf7, 0x75, 0xac],
+        },
+        TestTriple {
+            key: [
+                0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54,
+                0x32, 0x10,
+            ],
+            plaintext: [0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10],
+            ciphertext: [0xc1, 0xb7, 0x1f, 0x66, 0x16, 0x0f, 0xf5, 0x87],
+        },
+        TestTriple {
+            key: [
+                0xbd, 0x91, 0x73, 0x1e, 0xb6, 0xbc, 0x27, 0x13, 0xa1, 0xf9, 0xf6, 0xff, 0xc7, 0x50,
+                0x44, 0xe7,
+            ],
+            plaintext: [0xc4, 0x50, 0xc7, 0x72, 0x7a, 0x9b, 0x8a, 0x7d],
+            ciphertext: [0xe3, 0x27, 0x28, 0x85, 0xfa, 0x94, 0xba, 0x8b],
+        },
+    ];
+
+    #[test]
+    fn test_encrypt() {
+        for case in &CASES {
+            let mut ciphertext = [0; BLOCK_SIZE];
+            encrypt(&case.plaintext, &case.key, &mut ciphertext);
+            assert_eq!(ciphertext, case.ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_decrypt() {
+        for case in &CASES {
+            let mut plaintext = [0; BLOCK_SIZE];
+            decrypt(&case.ciphertext, &case.key, &mut plaintext);
+            assert_eq!(plaintext, case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_multiple_blocks() {
+        let key = CASES[2].key;
+        let mut plaintext = [0; 3 * BLOCK_SIZE];
+        for (i, case) in CASES.iter().enumerate() {
+            plaintext[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE].copy_from_slice(&case.plaintext);
+        }
+
+        let mut ciphertext = [0; 3 * BLOCK_SIZE];
+        encrypt(&plaintext, &key, &mut ciphertext);
+        for (i, case) in CASES.iter().enumerate() {
+            let mut expected = [0; BLOCK_SIZE];
+            encrypt(&case.plaintext, &key, &mut expected);
+            assert_eq!(ciphertext[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE], expected);
+        }
+
+        let mut decrypted = [0; 3 * BLOCK_SIZE];
+        decrypt(&ciphertext, &key, &mut decrypted);
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn test_masked_encrypt() {
+        let plaintext_masks = [0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c];
+        let key_masks = [
+            0x1d, 0x54, 0xf0, 0xae, 0x54, 0x0a, 0xaf, 0x8c, 0xb3, 0xd7, 0x7d, 0x46, 0x4a, 0xac,
+            0xa1, 0xb4,
+        ];
+        let mut rng = XorShiftRng::new(1);
+        for case in &CASES {
+            let masked_key = mask_key(&case.key, &key_masks);
+            let mut masked_ciphertext = [Default::default(); BLOCK_SIZE];
+            let masked_plaintext = mask_block(&case.plaintext, &plaintext_masks);
+            encrypt_masked(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            );
+            let ciphertext = unmask_block(&masked_ciphertext);
+            assert_eq!(ciphertext, case.ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_masked_decrypt() {
+        let ciphertext_masks = [0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf, 0xa1, 0xb4];
+        let key_masks = [
+            0x1d, 0x54, 0xf0, 0xae, 0x54, 0x0a, 0xaf, 0x8c, 0xb3, 0xd7, 0x7d, 0x46, 0x4a, 0xac,
+            0xa1, 0xb4,
+        ];
+        let mut rng = XorShiftRng::new(1);
+        for case in &CASES {
+            let masked_key = mask_key(&case.key, &key_masks);
+            let masked_ciphertext = mask_block(&case.ciphertext, &ciphertext_masks);
+            let mut masked_plaintext = [Default::default(); BLOCK_SIZE];
+            decrypt_masked(
+                &masked_ciphertext,
+                &masked_key,
+                &mut masked_plaintext,
+                &mut rng,
+            );
+            let plaintext = unmask_block(&masked_plaintext);
+            assert_eq!(plaintext, case.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_masked_encrypt_with_rng() {
+        let mut rng = XorShiftRng::new(0x9e3779b9);
+        for case in &CASES {
+            let masked_key = mask_key_with_rng(&case.key, &mut rng);
+            let masked_plaintext = mask_block_with_rng(&case.plaintext, &mut rng);
+            let mut masked_ciphertext = [Default::default(); BLOCK_SIZE];
+            encrypt_masked(
+                &masked_plaintext,
+                &masked_key,
+                &mut masked_ciphertext,
+                &mut rng,
+            );
+            assert_eq!(unmask_block(&masked_ciphertext), case.ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_mask_block() {
+        let block_masks = [0x1d, 0x54, 0xf0, 0xae, 0x54, 0x0a, 0xaf, 0x8c];
+
+        for case in &CASES {
+            let masked_block = mask_block(&case.plaintext, &block_masks);
+            let unmasked_block = unmask_block(&masked_block);
+            assert_eq!(case.plaintext, unmasked_block);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::State;
+use crate::gift64::{Block, MaskedBlock};
+use crate::{swap_move, swap_move_single};
+
+// Two blocks are processed at once: the lower 16 bits of every state word hold a slice of the
+// first block and the upper 16 bits the same slice of the second block.
+#[must_use]
+pub(super) fn pack(input: &[Block; 2]) -> State<u32> {
+    let [b0, b1] = input;
+    let mut s0 =
+        ((b1[6] as u32) << 24) | ((b1[7] as u32) << 16) | ((b0[6] as u32) << 8) | b0[7] as u32;
+    let mut s1 =
+        ((b1[4] as u32) << 24) | ((b1[5] as u32) << 16) | ((b0[4] as u32) << 8) | (b0[5] as u32);
+    let mut s2 =
+        ((b1[2] as u32) << 24) | ((b1[3] as u32) << 16) | ((b0[2] as u32) << 8) | (b0[3] as u32);
+    let mut s3 =
+        ((b1[0] as u32) << 24) | ((b1[1] as u32) << 16) | ((b0[0] as u32) << 8) | (b0[1] as u32);
+
+    s0 = swap_move_single(s0, 0x0a0a0a0a, 3);
+    s0 = swap_move_single(s0, 0x00cc00cc, 6);
+    s1 = swap_move_single(s1, 0x0a0a0a0a, 3);
+    s1 = swap_move_single(s1, 0x00cc00cc, 6);
+    s2 = swap_move_single(s2, 0x0a0a0a0a, 3);
+    s2 = swap_move_single(s2, 0x00cc00cc, 6);
+    s3 = swap_move_single(s3, 0x0a0a0a0a, 3);
+    s3 = swap_move_single(s3, 0x00cc00cc, 6);
+
+    (s0, s1) = swap_move(s0, s1, 0x0f0f0f0f, 4);
+    (s2, s3) = swap_move(s2, s3, 0x0f0f0f0f, 4);
+    (s0, s2) = swap_move(s0, s2, 0x00ff00ff, 8);
+    (s1, s3) = swap_move(s1, s3, 0x00ff00ff, 8);
+
+    State(s0, s1, s2, s3)
+}
+
+#[must_use]
+pub(super) fn unpack(state: State<u32>) -> [Block; 2] {
+    let State(mut s0, mut s1, mut s2, mut s3) = state;
+
+    (s1, s3) = swap_move(s1, s3, 0x00ff00ff, 8);
+    (s0, s2) = swap_move(s0, s2, 0x00ff00ff, 8);
+    (s2, s3) = swap_move(s2, s3, 0x0f0f0f0f, 4);
+    (s0, s1) = swap_move(s0, s1, 0x0f0f0f0f, 4);
+
+    s3 = swap_move_single(s3, 0x00cc00cc, 6);
+    s3 = swap_move_single(s3, 0x0a0a0a0a, 3);
+    s2 = swap_move_single(s2, 0x00cc00cc, 6);
+    s2 = swap_move_single(s2, 0x0a0a0a0a, 3);
+    s1 = swap_move_single(s1, 0x00cc00cc, 6);
+    s1 = swap_move_single(s1, 0x0a0a0a0a, 3);
+    s0 = swap_move_single(s0, 0x00cc00cc, 6);
+    s0 = swap_move_single(s0, 0x0a0a0a0a, 3);
+    [
+        [
+            ((s3 >> 8) & 0xff) as u8,
+            (s3 & 0xff) as u8,
+            ((s2 >> 8) & 0xff) as u8,
+            (s2 & 0xff) as u8,
+            ((s1 >> 8) & 0xff) as u8,
+            (s1 & 0xff) as u8,
+            ((s0 >> 8) & 0xff) as u8,
+            (s0 & 0xff) as u8,
+        ],
+        [
+            (s3 >> 24) as u8,
+            ((s3 >> 16) & 0xff) as u8,
+            (s2 >> 24) as u8,
+            ((s2 >> 16) & 0xff) as u8,
+            (s1 >> 24) as u8,
+            ((s1 >> 16) & 0xff) as u8,
+            (s0 >> 24) as u8,
+            ((s0 >> 16) & 0xff) as u8,
+        ],
+    ]
+}
+
+#[must_use]
+pub(super) fn masked_pack(input: &[MaskedBlock; 2]) -> State<BinaryMask<u32>> {
+    let [b0, b1] = input;
+    let mut s0 = ((BinaryMask::<u32>::from(b1[6])) << 24)
+        ^ ((BinaryMask::<u32>::from(b1[7])) << 16)
+        ^ ((BinaryMask::<u32>::from(b0[6])) << 8)
+        ^ BinaryMask::<u32>::from(b0[7]);
+    let mut s1 = ((BinaryMask::<u32>::from(b1[4])) << 24)
+        ^ ((BinaryMask::<u32>::from(b1[5])) << 16)
+        ^ ((BinaryMask::<u32>::from(b0[4])) << 8)
+        ^ (BinaryMask::<u32>::from(b0[5]));
+    let mut s2 = ((BinaryMask::<u32>::from(b1[2])) << 24)
+        ^ ((BinaryMask::<u32>::from(b1[3])) << 16)
+        ^ ((BinaryMask::<u32>::from(b0[2])) << 8)
+        ^ (BinaryMask::<u32>::from(b0[3]));
+    let mut s3 = ((BinaryMask::<u32>::from(b1[0])) << 24)
+        ^ ((BinaryMask::<u32>::from(b1[1])) << 16)
+        ^ ((BinaryMask::<u32>::from(b0[0])) << 8)
+        ^ (BinaryMask::<u32>::from(b0[1]));
+
+    s0 = swap_move_single(s0, 0x0a0a0a0a, 3);
+    s0 = swap_move_single(s0, 0x00cc00cc, 6);
+    s1 = swap_move_single(s1, 0x0a0a0a0a, 3);
+    s1 = swap_move_single(s1, 0x00cc00cc, 6);
+    s2 = swap_move_single(s2, 0x0a0a0a0a, 3);
+    s2 = swap_move_single(s2, 0x00cc00cc, 6);
+    s3 = swap_move_single(s3, 0x0a0a0a0a, 3);
+    s3 = swap_move_single(s3, 0x00cc00cc, 6);
+
+    (s0, s1) = swap_move(s0, s1, 0x0f0f0f0f, 4);
+    (s2, s3) = swap_move(s2, s3, 0x0f0f0f0f, 4);
+    (s0, s2) = swap_move(s0, s2, 0x00ff00ff, 8);
+    (s1, s3) = swap_move(s1, s3, 0x00ff00ff, 8);
+
+    State(s0, s1, s2, s3)
+}
+
+#[must_use]
+pub(super) fn masked_unpack(state: State<BinaryMask<u32>>) -> [MaskedBlock; 2] {
+    let State(mut s0, mut s1, mut s2, mut s3) = state;
+
+    (s1, s3) = swap_move(s1, s3, 0x00ff00ff, 8);
+    (s0, s2) = swap_move(s0, s2, 0x00ff00ff, 8);
+    (s2, s3) = swap_move(s2, s3, 0x0f0f0f0f, 4);
+    (s0, s1) = swap_move(s0, s1, 0x0f0f0f0f, 4);
+
+    s3 = swap_move_single(s3, 0x00cc00cc, 6);
+    s3 = swap_move_single(s3, 0x0a0a0a0a, 3);
+    s2 = swap_move_single(s2, 0x00cc00cc, 6);
+    s2 = swap_move_single(s2, 0x0a0a0a0a, 3);
+    s1 = swap_move_single(s1, 0x00cc00cc, 6);
+    s1 = swap_move_single(s1, 0x0a0a0a0a, 3);
+    s0 = swap_move_single(s0, 0x00cc00cc, 6);
+    s0 = swap_move_single(s0, 0x0a0a0a0a, 3);
+    [
+        [
+            ((s3 >> 8u32) & 0xffu32).into(),
+            (s3 & 0xffu32).into(),
+            ((s2 >> 8u32) & 0xffu32).into(),
+            (s2 & 0xffu32).into(),
+            ((s1 >> 8u32) & 0xffu32).into(),
+            (s1 & 0xffu32).into(),
+            ((s0 >> 8u32) & 0xffu32).into(),
+            (s0 & 0xffu32).into(),
+        ],
+        [
+            (s3 >> 24u32).into(),
+            ((s3 >> 16u32) & 0xffu32).into(),
+            (s2 >> 24u32).into(),
+            ((s2 >> 16u32) & 0xffu32).into(),
+            (s1 >> 24u32).into(),
+            ((s1 >> 16u32) & 0xffu32).into(),
+            (s0 >> 24u32).into(),
+            ((s0 >> 16u32) & 0xffu32).into(),
+        ],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift64::packing::*;
+    use crate::gift64::{mask_block, unmask_block};
+
+    const INPUT: [Block; 2] = [
+        [0xc4, 0x50, 0xc7, 0x72, 0x7a, 0x9b, 0x8a, 0x7d],
+        [0xe3, 0x27, 0x28, 0x85, 0xfa, 0x94, 0xba, 0x8b],
+    ];
+
+    #[test]
+    fn test_pack_unpack() {
+        let s = pack(&INPUT);
+        let output = unpack(s);
+        assert_eq!(INPUT, output);
+    }
+
+    #[test]
+    fn test_pack_slices() {
+        // Slice i of a block holds bit i of every nibble, with nibble 0 in the least significant
+        // position.
+        let State(s0, s1, s2, s3) =
+            pack(&[[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x84, 0x21], [0; 8]]);
+        assert_eq!(State(s0, s1, s2, s3), State(0x0001, 0x0002, 0x0004, 0x0008));
+    }
+
+    #[test]
+    fn test_masked_pack_unpack() {
+        let masks = [
+            [0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c],
+            [0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf, 0xa1, 0xb4u8],
+        ];
+        let masked_input = [
+            mask_block(&INPUT[0], &masks[0]),
+            mask_block(&INPUT[1], &masks[1]),
+        ];
+        let s = masked_pack(&masked_input);
+        let masked_output = masked_unpack(s);
+        let output = [
+            unmask_block(&masked_output[0]),
+            unmask_block(&masked_output[1]),
+        ];
+        assert_eq!(INPUT, output);
+    }
+}
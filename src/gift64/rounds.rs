@@ -0,0 +1,131 @@
+use crate::gift128::rounds::RoundTraits;
+use crate::gift128::sbox::{inv_sbox, sbox};
+use crate::gift128::State;
+use crate::gift64::key_schedule::RoundKeys;
+use crate::random::Rng;
+
+pub const ROUNDS: usize = 28;
+const ROUND_CONSTANTS: [u32; ROUNDS] = [
+    0x10081008, 0xc001c001, 0x11181118, 0x800f800f, 0x988c988c, 0x7c017c01, 0x32193219, 0x803b803b,
+    0x18cc18cc, 0xf401f401, 0x21192119, 0x803c803c, 0x904c904c, 0xcc01cc01, 0x13181318, 0x800e800e,
+    0x980c980c, 0x5c015c01, 0x32183218, 0x802b802b, 0x18841884, 0x34013401, 0x20092009, 0x80308030,
+    0x10481048, 0x40014001, 0x10181018, 0x800b800b,
+];
+
+// Every slice permutation of GIFT-64 has order 4, so the state is back in the classical
+// representation after four rounds and no final swap is needed as in GIFT-128.
+#[must_use]
+#[inline(always)]
+fn quadruple_round<T: RoundTraits, R: Rng>(
+    state: State<T>,
+    round_keys: &[T],
+    round_constants: &[u32],
+    rng: &mut R,
+) -> State<T> {
+    let State(mut s0, mut s1, mut s2, mut s3) = state;
+    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3), rng);
+    s3 = s3.nibble_ror_1();
+    s1 = s1.nibble_ror_2();
+    s2 = s2.nibble_ror_3();
+    s3 ^= round_keys[0];
+    s1 ^= round_keys[1];
+    s0 ^= round_constants[0];
+    State(s3, s1, s2, s0) = sbox(State(s3, s1, s2, s0), rng);
+    s0 = s0.half_ror_4();
+    s1 = s1.half_ror_8();
+    s2 = s2.half_ror_12();
+    s0 ^= round_keys[2];
+    s1 ^= round_keys[3];
+    s3 ^= round_constants[1];
+    State(s0, s1, s2, s3) = sbox(State(s0, s1, s2, s3), rng);
+    s3 = s3.nibble_ror_3();
+    s1 = s1.nibble_ror_2();
+    s2 = s2.nibble_ror_1();
+    s3 ^= round_keys[4];
+    s1 ^= round_keys[5];
+    s0 ^= round_constants[2];
+    State(s3, s1, s2, s0) = sbox(State(s3, s1, s2, s0), rng);
+    s0 = s0.half_ror_12();
+    s1 = s1.half_ror_8();
+    s2 = s2.half_ror_4();
+    s0 ^= round_keys[6];
+    s1 ^= round_keys[7];
+    s3 ^= round_constants[3];
+
+    State(s0, s1, s2, s3)
+}
+
+#[must_use]
+#[inline(always)]
+fn inv_quadruple_round<T: RoundTraits, R: Rng>(
+    state: State<T>,
+    round_keys: &[T],
+    round_constants: &[u32],
+    rng: &mut R,
+) -> State<T> {
+    let State(mut s0, mut s1, mut s2, mut s3) = state;
+    s0 ^= round_keys[6];
+    s1 ^= round_keys[7];
+    s3 ^= round_constants[3];
+    s0 = s0.half_ror_4();
+    s1 = s1.half_ror_8();
+    s2 = s2.half_ror_12();
+    State(s0, s1, s2, s3) = inv_sbox(State(s0, s1, s2, s3), rng);
+    s3 ^= round_keys[4];
+    s1 ^= round_keys[5];
+    s0 ^= round_constants[2];
+    s3 = s3.nibble_ror_1();
+    s1 = s1.nibble_ror_2();
+    s2 = s2.nibble_ror_3();
+    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0), rng);
+    s0 ^= round_keys[2];
+    s1 ^= round_keys[3];
+    s3 ^= round_constants[1];
+    s0 = s0.half_ror_12();
+    s1 = s1.half_ror_8();
+    s2 = s2.half_ror_4();
+    State(s0, s1, s2, s3) = inv_sbox(State(s0, s1, s2, s3), rng);
+    s3 ^= round_keys[0];
+    s1 ^= round_keys[1];
+    s0 ^= round_constants[0];
+    s3 = s3.nibble_ror_3();
+    s1 = s1.nibble_ror_2();
+    s2 = s2.nibble_ror_1();
+    State(s3, s1, s2, s0) = inv_sbox(State(s3, s1, s2, s0), rng);
+
+    State(s0, s1, s2, s3)
+}
+
+pub(super) fn rounds<T: RoundTraits, R: Rng>(
+    mut state: State<T>,
+    round_keys: &RoundKeys<T>,
+    rng: &mut R,
+) -> State<T> {
+    for i in (0..ROUNDS).step_by(4) {
+        state = quadruple_round(
+            state,
+            &round_keys[i * 2..i * 2 + 8],
+            &ROUND_CONSTANTS[i..i + 4],
+            rng,
+        );
+    }
+
+    state
+}
+
+pub(super) fn inv_rounds<T: RoundTraits, R: Rng>(
+    mut state: State<T>,
+    round_keys: &RoundKeys<T>,
+    rng: &mut R,
+) -> State<T> {
+    for i in (0..ROUNDS).step_by(4).rev() {
+        state = inv_quadruple_round(
+            state,
+            &round_keys[i * 2..i * 2 + 8],
+            &ROUND_CONSTANTS[i..i + 4],
+            rng,
+        );
+    }
+
+    state
+}
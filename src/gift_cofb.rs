@@ -0,0 +1,653 @@
+use crate::gift128::key_schedule::{
+    precompute_masked_round_keys, precompute_round_keys, RoundKeys,
+};
+use crate::gift128::masking::BinaryMask;
+use crate::gift128::{
+    bitsliced_encrypt_block, bitsliced_encrypt_shared_block, mask_block, unmask_block, Block, Key,
+    MaskedBlock, MaskedKey,
+};
+use crate::random::Rng;
+
+const BLOCK_SIZE: usize = 16;
+const NONCE_SIZE: usize = 16;
+const TAG_SIZE: usize = 16;
+
+pub type Nonce = [u8; NONCE_SIZE];
+pub type Tag = [u8; TAG_SIZE];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InvalidTag;
+
+#[must_use]
+#[inline]
+fn pad(input: &[u8]) -> Block {
+    let mut block = [0; BLOCK_SIZE];
+    block[..input.len()].copy_from_slice(input);
+    if input.len() < BLOCK_SIZE {
+        block[input.len()] = 0x80;
+    }
+    block
+}
+
+// Multiplication by the primitive element in GF(2^64) defined by x^64 + x^4 + x^3 + x + 1.
+#[must_use]
+#[inline]
+fn double_offset(offset: u64) -> u64 {
+    (offset << 1) ^ ((offset >> 63) * 0x1b)
+}
+
+#[must_use]
+#[inline]
+fn triple_offset(offset: u64) -> u64 {
+    double_offset(offset) ^ offset
+}
+
+#[must_use]
+#[inline]
+fn g(y: &Block) -> Block {
+    let mut output = [0; BLOCK_SIZE];
+    output[..8].copy_from_slice(&y[8..]);
+    let upper = u64::from_be_bytes([y[0], y[1], y[2], y[3], y[4], y[5], y[6], y[7]]);
+    output[8..].copy_from_slice(&upper.rotate_left(1).to_be_bytes());
+    output
+}
+
+#[must_use]
+#[inline]
+fn feedback(y: &Block, offset: u64, block: &Block) -> Block {
+    let mut x = g(y);
+    for (x, b) in x.iter_mut().zip(block) {
+        *x ^= b;
+    }
+    for (x, o) in x.iter_mut().zip(offset.to_be_bytes()) {
+        *x ^= o;
+    }
+    x
+}
+
+// The last offset update of a section depends on whether its final block was complete.
+#[must_use]
+#[inline]
+fn final_offset(offset: u64, last_block_size: usize) -> u64 {
+    let offset = triple_offset(offset);
+    if last_block_size < BLOCK_SIZE {
+        triple_offset(offset)
+    } else {
+        offset
+    }
+}
+
+fn process_associated_data(
+    round_keys: &RoundKeys<u32>,
+    nonce: &Nonce,
+    associated_data: &[u8],
+    empty_message: bool,
+) -> (Block, u64) {
+    let mut y = bitsliced_encrypt_block(nonce, round_keys);
+    let mut offset = u64::from_be_bytes([y[0], y[1], y[2], y[3], y[4], y[5], y[6], y[7]]);
+
+    // The last block is processed separately even if it is complete or empty.
+    let split = associated_data.len().saturating_sub(1) / BLOCK_SIZE * BLOCK_SIZE;
+    let (blocks, last_block) = associated_data.split_at(split);
+
+    for block in blocks.chunks(BLOCK_SIZE) {
+        offset = double_offset(offset);
+        y = bitsliced_encrypt_block(&feedback(&y, offset, &pad(block)), round_keys);
+    }
+
+    offset = final_offset(offset, last_block.len());
+    if empty_message {
+        offset = triple_offset(triple_offset(offset));
+    }
+    y = bitsliced_encrypt_block(&feedback(&y, offset, &pad(last_block)), round_keys);
+
+    (y, offset)
+}
+
+#[must_use]
+pub fn encrypt(
+    plaintext: &[u8],
+    associated_data: &[u8],
+    nonce: &Nonce,
+    key: &Key,
+    ciphertext: &mut [u8],
+) -> Tag {
+    if plaintext.len() != ciphertext.len() {
+        panic!("ciphertext size differs from plaintext size");
+    }
+
+    let round_keys = precompute_round_keys(key);
+    let (mut y, mut offset) =
+        process_associated_data(&round_keys, nonce, associated_data, plaintext.is_empty());
+
+    let mut blocks = plaintext
+        .chunks(BLOCK_SIZE)
+        .zip(ciphertext.chunks_mut(BLOCK_SIZE))
+        .peekable();
+    while let Some((plaintext_block, ciphertext_block)) = blocks.next() {
+        offset = if blocks.peek().is_some() {
+            double_offset(offset)
+        } else {
+            final_offset(offset, plaintext_block.len())
+        };
+
+        for i in 0..plaintext_block.len() {
+            ciphertext_block[i] = y[i] ^ plaintext_block[i];
+        }
+        y = bitsliced_encrypt_block(&feedback(&y, offset, &pad(plaintext_block)), &round_keys);
+    }
+
+    y
+}
+
+pub fn decrypt(
+    ciphertext: &[u8],
+    associated_data: &[u8],
+    nonce: &Nonce,
+    tag: &Tag,
+    key: &Key,
+    plaintext: &mut [u8],
+) -> Result<(), InvalidTag> {
+    if ciphertext.len() != plaintext.len() {
+        panic!("plaintext size differs from ciphertext size");
+    }
+
+    let round_keys = precompute_round_keys(key);
+    let (mut y, mut offset) =
+        process_associated_data(&round_keys, nonce, associated_data, ciphertext.is_empty());
+
+    let mut blocks = ciphertext
+        .chunks(BLOCK_SIZE)
+        .zip(plaintext.chunks_mut(BLOCK_SIZE))
+        .peekable();
+    while let Some((ciphertext_block, plaintext_block)) = blocks.next() {
+        offset = if blocks.peek().is_some() {
+            double_offset(offset)
+        } else {
+            final_offset(offset, ciphertext_block.len())
+        };
+
+        for i in 0..ciphertext_block.len() {
+            plaintext_block[i] = y[i] ^ ciphertext_block[i];
+        }
+        y = bitsliced_encrypt_block(&feedback(&y, offset, &pad(plaintext_block)), &round_keys);
+    }
+
+    let mut difference = 0;
+    for i in 0..TAG_SIZE {
+        difference |= y[i] ^ tag[i];
+    }
+
+    if difference != 0 {
+        plaintext.fill(0);
+        return Err(InvalidTag);
+    }
+
+    Ok(())
+}
+
+#[must_use]
+#[inline]
+fn split_shares(block: &MaskedBlock) -> (Block, Block) {
+    let mut shares = ([0; BLOCK_SIZE], [0; BLOCK_SIZE]);
+    for (i, share) in block.iter().enumerate() {
+        shares.0[i] = share.0;
+        shares.1[i] = share.1;
+    }
+    shares
+}
+
+#[must_use]
+#[inline]
+fn join_shares(share0: &Block, share1: &Block) -> MaskedBlock {
+    let mut block = [Default::default(); BLOCK_SIZE];
+    for i in 0..BLOCK_SIZE {
+        block[i] = BinaryMask(share0[i], share1[i]);
+    }
+    block
+}
+
+#[must_use]
+#[inline]
+fn masked_pad(input: &[BinaryMask<u8>]) -> MaskedBlock {
+    let mut block = [BinaryMask(0, 0); BLOCK_SIZE];
+    block[..input.len()].copy_from_slice(input);
+    if input.len() < BLOCK_SIZE {
+        block[input.len()] = BinaryMask(0x80, 0);
+    }
+    block
+}
+
+// Everything but the block cipher is linear, so the unmasked helpers are applied to each share.
+#[must_use]
+#[inline]
+fn masked_feedback(y: &MaskedBlock, offset: BinaryMask<u64>, block: &MaskedBlock) -> MaskedBlock {
+    let (y0, y1) = split_shares(y);
+    let (block0, block1) = split_shares(block);
+    join_shares(
+        &feedback(&y0, offset.0, &block0),
+        &feedback(&y1, offset.1, &block1),
+    )
+}
+
+fn masked_process_associated_data<R: Rng>(
+    round_keys: &RoundKeys<BinaryMask<u32>>,
+    nonce: &Nonce,
+    associated_data: &[u8],
+    empty_message: bool,
+    rng: &mut R,
+) -> (MaskedBlock, BinaryMask<u64>) {
+    let nonce = mask_block(nonce, &[0; BLOCK_SIZE]);
+    let mut y = bitsliced_encrypt_shared_block(&nonce, round_keys, rng);
+    let (y0, y1) = split_shares(&y);
+    let mut offset = BinaryMask(
+        u64::from_be_bytes([y0[0], y0[1], y0[2], y0[3], y0[4], y0[5], y0[6], y0[7]]),
+        u64::from_be_bytes([y1[0], y1[1], y1[2], y1[3], y1[4], y1[5], y1[6], y1[7]]),
+    );
+
+    let split = associated_data.len().saturating_sub(1) / BLOCK_SIZE * BLOCK_SIZE;
+    let (blocks, last_block) = associated_data.split_at(split);
+
+    for block in blocks.chunks(BLOCK_SIZE) {
+        offset = BinaryMask(double_offset(offset.0), double_offset(offset.1));
+        let block = mask_block(&pad(block), &[0; BLOCK_SIZE]);
+        y = bitsliced_encrypt_shared_block(&masked_feedback(&y, offset, &block), round_keys, rng);
+    }
+
+    offset = BinaryMask(
+        final_offset(offset.0, last_block.len()),
+        final_offset(offset.1, last_block.len()),
+    );
+    if empty_message {
+        offset = BinaryMask(
+            triple_offset(triple_offset(offset.0)),
+            triple_offset(triple_offset(offset.1)),
+        );
+    }
+    let last_block = mask_block(&pad(last_block), &[0; BLOCK_SIZE]);
+    let feedback = masked_feedback(&y, offset, &last_block);
+    y = bitsliced_encrypt_shared_block(&feedback, round_keys, rng);
+
+    (y, offset)
+}
+
+// Only the ciphertext and the tag are unmasked; the key, the COFB state and the keystream stay
+// masked throughout. The fresh randomness of the masked rounds is drawn from `rng`.
+#[must_use]
+pub fn encrypt_masked<R: Rng>(
+    plaintext: &[BinaryMask<u8>],
+    associated_data: &[u8],
+    nonce: &Nonce,
+    key: &MaskedKey,
+    ciphertext: &mut [u8],
+    rng: &mut R,
+) -> Tag {
+    if plaintext.len() != ciphertext.len() {
+        panic!("ciphertext size differs from plaintext size");
+    }
+
+    let round_keys = precompute_masked_round_keys(key);
+    let (mut y, mut offset) = masked_process_associated_data(
+        &round_keys,
+        nonce,
+        associated_data,
+        plaintext.is_empty(),
+        rng,
+    );
+
+    let mut blocks = plaintext
+        .chunks(BLOCK_SIZE)
+        .zip(ciphertext.chunks_mut(BLOCK_SIZE))
+        .peekable();
+    while let Some((plaintext_block, ciphertext_block)) = blocks.next() {
+        offset = if blocks.peek().is_some() {
+            BinaryMask(double_offset(offset.0), double_offset(offset.1))
+        } else {
+            BinaryMask(
+                final_offset(offset.0, plaintext_block.len()),
+                final_offset(offset.1, plaintext_block.len()),
+            )
+        };
+
+        for i in 0..plaintext_block.len() {
+            ciphertext_block[i] = (y[i] ^ plaintext_block[i]).recover_shares();
+        }
+        let feedback = masked_feedback(&y, offset, &masked_pad(plaintext_block));
+        y = bitsliced_encrypt_shared_block(&feedback, &round_keys, rng);
+    }
+
+    unmask_block(&y)
+}
+
+// The plaintext is written masked and is only released if the tag is valid.
+pub fn decrypt_masked<R: Rng>(
+    ciphertext: &[u8],
+    associated_data: &[u8],
+    nonce: &Nonce,
+    tag: &Tag,
+    key: &MaskedKey,
+    plaintext: &mut [BinaryMask<u8>],
+    rng: &mut R,
+) -> Result<(), InvalidTag> {
+    if ciphertext.len() != plaintext.len() {
+        panic!("plaintext size differs from ciphertext size");
+    }
+
+    let round_keys = precompute_masked_round_keys(key);
+    let (mut y, mut offset) = masked_process_associated_data(
+        &round_keys,
+        nonce,
+        associated_data,
+        ciphertext.is_empty(),
+        rng,
+    );
+
+    let mut blocks = ciphertext
+        .chunks(BLOCK_SIZE)
+        .zip(plaintext.chunks_mut(BLOCK_SIZE))
+        .peekable();
+    while let Some((ciphertext_block, plaintext_block)) = blocks.next() {
+        offset = if blocks.peek().is_some() {
+            BinaryMask(double_offset(offset.0), double_offset(offset.1))
+        } else {
+            BinaryMask(
+                final_offset(offset.0, ciphertext_block.len()),
+                final_offset(offset.1, ciphertext_block.len()),
+            )
+        };
+
+        for i in 0..ciphertext_block.len() {
+            plaintext_block[i] = y[i] ^ ciphertext_block[i];
+        }
+        let feedback = masked_feedback(&y, offset, &masked_pad(plaintext_block));
+        y = bitsliced_encrypt_shared_block(&feedback, &round_keys, rng);
+    }
+
+    let computed_tag = unmask_block(&y);
+    let mut difference = 0;
+    for i in 0..TAG_SIZE {
+        difference |= computed_tag[i] ^ tag[i];
+    }
+
+    if difference != 0 {
+        plaintext.fill(BinaryMask(0, 0));
+        return Err(InvalidTag);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gift128::key_schedule::mask_key;
+    use crate::gift_cofb::*;
+    use crate::random::XorShiftRng;
+
+    // Entries from the NIST LWC KAT file LWC_AEAD_KAT_128_128.txt. Key and nonce are always
+    // 00..0f, and the plaintext and associated data are the byte sequences 00, 01, .. of the
+    // given sizes. The ciphertext is followed by the tag.
+    struct KatEntry {
+        count: usize,
+        plaintext_size: usize,
+        associated_data_size: usize,
+        ciphertext: &'static [u8],
+    }
+
+    const KEY: Key = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+    const NONCE: Nonce = KEY;
+    const INPUT: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+
+    const KAT: [KatEntry; 9] = [
+        KatEntry {
+            count: 1,
+            plaintext_size: 0,
+            associated_data_size: 0,
+            ciphertext: &[
+                0x36, 0x89, 0x65, 0x83, 0x6d, 0x36, 0x61, 0x4d, 0xe2, 0xfc, 0x24, 0xd0, 0xf8, 0x01,
+                0xb9, 0xaf,
+            ],
+        },
+        KatEntry {
+            count: 2,
+            plaintext_size: 0,
+            associated_data_size: 1,
+            ciphertext: &[
+                0xae, 0x5d, 0xcd, 0xd1, 0x28, 0x5d, 0x51, 0x77, 0xfe, 0x25, 0x1d, 0xeb, 0x99, 0xd7,
+                0x27, 0xdc,
+            ],
+        },
+        KatEntry {
+            count: 17,
+            plaintext_size: 0,
+            associated_data_size: 16,
+            ciphertext: &[
+                0x70, 0x96, 0x57, 0xd8, 0x1d, 0xdc, 0x50, 0x9a, 0xa2, 0x0d, 0xc6, 0x6f, 0x18, 0xff,
+                0x99, 0x07,
+            ],
+        },
+        KatEntry {
+            count: 18,
+            plaintext_size: 0,
+            associated_data_size: 17,
+            ciphertext: &[
+                0x65, 0x91, 0xab, 0x7e, 0x3c, 0xac, 0xb1, 0xa5, 0xb0, 0xbe, 0x50, 0x63, 0x3e, 0x73,
+                0x52, 0x96,
+            ],
+        },
+        KatEntry {
+            count: 34,
+            plaintext_size: 1,
+            associated_data_size: 0,
+            ciphertext: &[
+                0x5d, 0xf9, 0x6d, 0xb3, 0x29, 0xe9, 0x26, 0x88, 0x24, 0x2e, 0xf4, 0xe0, 0x6f, 0x94,
+                0xfe, 0x1b, 0xd9,
+            ],
+        },
+        KatEntry {
+            count: 499,
+            plaintext_size: 15,
+            associated_data_size: 3,
+            ciphertext: &[
+                0x4a, 0xca, 0xc2, 0x7b, 0xdc, 0x76, 0xd9, 0xec, 0xb0, 0x2e, 0x3e, 0x01, 0xa6, 0x8a,
+                0x86, 0xb4, 0x05, 0x7d, 0x9e, 0xab, 0x54, 0x65, 0x1c, 0x8b, 0xdc, 0x57, 0xc1, 0x88,
+                0xf4, 0x67, 0x33,
+            ],
+        },
+        KatEntry {
+            count: 545,
+            plaintext_size: 16,
+            associated_data_size: 16,
+            ciphertext: &[
+                0x3b, 0xff, 0x71, 0x5a, 0x56, 0xcb, 0xa4, 0x9d, 0x1f, 0x7a, 0xc0, 0x69, 0x1a, 0x96,
+                0x6f, 0xdc, 0xbf, 0x77, 0x81, 0x40, 0x44, 0xbf, 0x3f, 0xc9, 0xa9, 0xde, 0xbb, 0xd3,
+                0x93, 0xf5, 0x45, 0xd4,
+            ],
+        },
+        KatEntry {
+            count: 577,
+            plaintext_size: 17,
+            associated_data_size: 15,
+            ciphertext: &[
+                0x63, 0xc2, 0x44, 0xa1, 0x71, 0xd6, 0xf7, 0xa4, 0x07, 0xc8, 0xd8, 0xf9, 0x0d, 0xcd,
+                0x2f, 0xb7, 0x4b, 0x99, 0xdf, 0x63, 0x0e, 0x90, 0xea, 0xf1, 0x5f, 0xc6, 0x29, 0x16,
+                0x37, 0xd4, 0x39, 0x59, 0x42,
+            ],
+        },
+        KatEntry {
+            count: 1089,
+            plaintext_size: 32,
+            associated_data_size: 32,
+            ciphertext: &[
+                0xba, 0xf5, 0x63, 0xc6, 0x0f, 0xbe, 0xdd, 0xc5, 0x66, 0x29, 0x95, 0xf4, 0xc6, 0x78,
+                0xbe, 0x80, 0xa7, 0xf7, 0xde, 0x9b, 0x3a, 0xd8, 0xc9, 0x7a, 0xa6, 0xca, 0x17, 0x01,
+                0x6d, 0x2a, 0xe6, 0x50, 0x8e, 0x6f, 0xb3, 0xf7, 0x9b, 0x41, 0x2a, 0x16, 0x27, 0xab,
+                0x7d, 0xfa, 0x75, 0x5e, 0x0a, 0x22,
+            ],
+        },
+    ];
+
+    #[test]
+    fn test_encrypt_kat() {
+        for entry in &KAT {
+            let plaintext = &INPUT[..entry.plaintext_size];
+            let associated_data = &INPUT[..entry.associated_data_size];
+            let mut ciphertext = [0; 32];
+            let ciphertext = &mut ciphertext[..entry.plaintext_size];
+
+            let tag = encrypt(plaintext, associated_data, &NONCE, &KEY, ciphertext);
+
+            let (expected_ciphertext, expected_tag) =
+                entry.ciphertext.split_at(entry.plaintext_size);
+            assert_eq!(ciphertext, expected_ciphertext, "count {}", entry.count);
+            assert_eq!(tag, expected_tag, "count {}", entry.count);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_kat() {
+        for entry in &KAT {
+            let associated_data = &INPUT[..entry.associated_data_size];
+            let (ciphertext, tag) = entry.ciphertext.split_at(entry.plaintext_size);
+            let mut plaintext = [0; 32];
+            let plaintext = &mut plaintext[..entry.plaintext_size];
+
+            let result = decrypt(
+                ciphertext,
+                associated_data,
+                &NONCE,
+                tag.try_into().unwrap(),
+                &KEY,
+                plaintext,
+            );
+
+            assert_eq!(result, Ok(()), "count {}", entry.count);
+            assert_eq!(
+                plaintext,
+                &INPUT[..entry.plaintext_size],
+                "count {}",
+                entry.count
+            );
+        }
+    }
+
+    #[test]
+    fn test_decrypt_invalid_tag() {
+        let entry = &KAT[7];
+        let associated_data = &INPUT[..entry.associated_data_size];
+        let (ciphertext, tag) = entry.ciphertext.split_at(entry.plaintext_size);
+        let mut tag: Tag = tag.try_into().unwrap();
+        tag[TAG_SIZE - 1] ^= 1;
+        let mut plaintext = [0xff; 17];
+
+        let result = decrypt(
+            ciphertext,
+            associated_data,
+            &NONCE,
+            &tag,
+            &KEY,
+            &mut plaintext,
+        );
+
+        assert_eq!(result, Err(InvalidTag));
+        assert_eq!(plaintext, [0; 17]);
+    }
+
+    const PLAINTEXT_MASKS: [u8; 32] = [
+        0x1d, 0x54, 0xf0, 0x8e, 0x55, 0x0a, 0xaf, 0x8c, 0xb3, 0xd2, 0x7d, 0x46, 0x4a, 0xaf, 0xa1,
+        0xb4, 0xa2, 0x4e, 0x71, 0x0c, 0x93, 0x6b, 0xde, 0x07, 0x38, 0xc5, 0x19, 0xf4, 0x60, 0x8d,
+        0x2b, 0xe9,
+    ];
+    const KEY_MASKS: [u8; 16] = [
+        0x1d, 0x54, 0xf0, 0xae, 0x54, 0x0a, 0xaf, 0x8c, 0xb3, 0xd7, 0x7d, 0x46, 0x4a, 0xac, 0xa1,
+        0xb4,
+    ];
+
+    #[test]
+    fn test_masked_encrypt_kat() {
+        let masked_key = mask_key(&KEY, &KEY_MASKS);
+        let mut rng = XorShiftRng::new(1);
+        for entry in &KAT {
+            let mut masked_plaintext = [BinaryMask(0, 0); 32];
+            for i in 0..entry.plaintext_size {
+                masked_plaintext[i] = BinaryMask::make_shares(INPUT[i], PLAINTEXT_MASKS[i]);
+            }
+            let masked_plaintext = &masked_plaintext[..entry.plaintext_size];
+            let associated_data = &INPUT[..entry.associated_data_size];
+            let mut ciphertext = [0; 32];
+            let ciphertext = &mut ciphertext[..entry.plaintext_size];
+
+            let tag = encrypt_masked(
+                masked_plaintext,
+                associated_data,
+                &NONCE,
+                &masked_key,
+                ciphertext,
+                &mut rng,
+            );
+
+            let (expected_ciphertext, expected_tag) =
+                entry.ciphertext.split_at(entry.plaintext_size);
+            assert_eq!(ciphertext, expected_ciphertext, "count {}", entry.count);
+            assert_eq!(tag, expected_tag, "count {}", entry.count);
+        }
+    }
+
+    #[test]
+    fn test_masked_decrypt_kat() {
+        let masked_key = mask_key(&KEY, &KEY_MASKS);
+        let mut rng = XorShiftRng::new(1);
+        for entry in &KAT {
+            let associated_data = &INPUT[..entry.associated_data_size];
+            let (ciphertext, tag) = entry.ciphertext.split_at(entry.plaintext_size);
+            let mut masked_plaintext = [BinaryMask(0, 0); 32];
+            let masked_plaintext = &mut masked_plaintext[..entry.plaintext_size];
+
+            let result = decrypt_masked(
+                ciphertext,
+                associated_data,
+                &NONCE,
+                tag.try_into().unwrap(),
+                &masked_key,
+                masked_plaintext,
+                &mut rng,
+            );
+
+            assert_eq!(result, Ok(()), "count {}", entry.count);
+            for (masked, expected) in masked_plaintext.iter().zip(&INPUT) {
+                assert_eq!(masked.recover_shares(), *expected, "count {}", entry.count);
+            }
+        }
+    }
+
+    #[test]
+    fn test_masked_decrypt_invalid_tag() {
+        let entry = &KAT[7];
+        let associated_data = &INPUT[..entry.associated_data_size];
+        let (ciphertext, tag) = entry.ciphertext.split_at(entry.plaintext_size);
+        let mut tag: Tag = tag.try_into().unwrap();
+        tag[0] ^= 0x80;
+        let mut masked_plaintext = [BinaryMask(0xff, 0x0f); 17];
+
+        let result = decrypt_masked(
+            ciphertext,
+            associated_data,
+            &NONCE,
+            &tag,
+            &mask_key(&KEY, &KEY_MASKS),
+            &mut masked_plaintext,
+            &mut XorShiftRng::new(1),
+        );
+
+        assert_eq!(result, Err(InvalidTag));
+        for masked in masked_plaintext {
+            assert_eq!((masked.0, masked.1), (0, 0));
+        }
+    }
+}
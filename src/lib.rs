@@ -1,6 +1,15 @@
 #![cfg_attr(not(test), no_std)]
 
+#[cfg(feature = "analysis")]
+extern crate std;
+
 use crate::swapmove::{swap_move, swap_move_single};
 
+#[cfg(any(test, feature = "analysis"))]
+pub mod analysis;
+pub mod ctr;
 pub mod gift128;
+pub mod gift64;
+pub mod gift_cofb;
+pub mod random;
 mod swapmove;
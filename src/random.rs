@@ -0,0 +1,156 @@
+// Source of randomness for drawing masks. With the `rand_core` feature enabled, every
+// `rand_core::RngCore` can be used.
+pub trait Rng {
+    fn next_u32(&mut self) -> u32;
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl<R: rand_core::RngCore> Rng for R {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        rand_core::RngCore::next_u32(self)
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::RngCore::fill_bytes(self, dest)
+    }
+}
+
+const DEFAULT_SEED: u32 = 0x2545f491;
+
+#[inline]
+fn xorshift(mut x: u32) -> u32 {
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    x
+}
+
+// A deterministic generator for reproducible tests and experiments. It is NOT cryptographically
+// secure.
+#[derive(Clone, Debug)]
+pub struct XorShiftRng {
+    state: u32,
+}
+
+impl XorShiftRng {
+    #[must_use]
+    pub fn new(seed: u32) -> Self {
+        // The all-zero state is a fixed point of xorshift.
+        let state = if seed == 0 { DEFAULT_SEED } else { seed };
+        XorShiftRng { state }
+    }
+}
+
+impl Rng for XorShiftRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        self.state = xorshift(self.state);
+        self.state
+    }
+}
+
+// Stands in for a generator where no randomness is needed, such as in the gates of unmasked
+// words. Drawing from it is a bug.
+pub(crate) struct NoRng;
+
+impl Rng for NoRng {
+    fn next_u32(&mut self) -> u32 {
+        unreachable!("unmasked words do not need randomness")
+    }
+}
+
+pub trait Random {
+    #[must_use]
+    fn random<R: Rng>(rng: &mut R) -> Self;
+}
+
+impl Random for u32 {
+    #[inline]
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        rng.next_u32()
+    }
+}
+
+impl Random for u8 {
+    #[inline]
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        rng.next_u32() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random::{Random, Rng, XorShiftRng};
+
+    #[test]
+    fn test_xorshift_rng_deterministic() {
+        let mut rng1 = XorShiftRng::new(0xdeadbeef);
+        let mut rng2 = XorShiftRng::new(0xdeadbeef);
+        let mut bytes1 = [0; 7];
+        let mut bytes2 = [0; 7];
+        rng1.fill_bytes(&mut bytes1);
+        rng2.fill_bytes(&mut bytes2);
+        assert_eq!(bytes1, bytes2);
+        assert_eq!(rng1.next_u32(), rng2.next_u32());
+
+        let mut rng3 = XorShiftRng::new(0xdeadbef0);
+        assert_ne!(rng1.next_u32(), rng3.next_u32());
+    }
+
+    #[test]
+    fn test_zero_seed() {
+        let mut rng = XorShiftRng::new(0);
+        assert_ne!(rng.next_u32(), 0);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng1 = XorShiftRng::new(0xdeadbeef);
+        let mut rng2 = XorShiftRng::new(0xdeadbeef);
+        assert_eq!(u32::random(&mut rng1), rng2.next_u32());
+        assert_eq!(u8::random(&mut rng1), rng2.next_u32() as u8);
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_rand_core() {
+        struct Counter(u32);
+
+        impl rand_core::RngCore for Counter {
+            fn next_u32(&mut self) -> u32 {
+                self.0 += 1;
+                self.0
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                rand_core::impls::next_u64_via_u32(self)
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                rand_core::impls::fill_bytes_via_next(self, dest)
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+                rand_core::impls::fill_bytes_via_next(self, dest);
+                Ok(())
+            }
+        }
+
+        fn draw<R: Rng>(rng: &mut R) -> u32 {
+            rng.next_u32()
+        }
+
+        let mut rng = Counter(0);
+        assert_eq!(draw(&mut rng), 1);
+        assert_eq!(draw(&mut rng), 2);
+    }
+}